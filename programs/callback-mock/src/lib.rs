@@ -0,0 +1,310 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::program_option::COption;
+use anchor_spl::token::{self, Burn, Mint, MintTo, Token, TokenAccount, Transfer};
+
+declare_id!("HLJpbzYoYLFQ9U7Khnz2dzTUTGquE82HcjkTj7724YbE");
+
+pub const SEED_MARKET_AUTHORITY: &[u8] = b"market";
+pub const SEED_TIER: &[u8] = b"tier";
+pub const SEED_PRICE: &[u8] = b"price";
+
+/// Test double for programs integrating with capital calls
+#[program]
+pub mod callback_mock {
+    use super::*;
+
+    /// Finalization callback invoked by capital call program
+    pub fn on_capital_call_finalized(
+        ctx: Context<OnCapitalCallFinalized>,
+        capital_call: Pubkey,
+        minted: u64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.capital_call.key() == capital_call,
+            CallbackMockError::UnexpectedCapitalCall
+        );
+
+        emit!(CallbackReceivedEvent {
+            capital_call,
+            config: ctx.accounts.config.key(),
+            minted,
+        });
+
+        Ok(())
+    }
+
+    /// Deposit to capital call with CPI as integrating vault program would, `remaining_accounts`
+    /// are forwarded
+    pub fn forward_deposit<'info>(
+        ctx: Context<'_, '_, '_, 'info, ForwardDeposit<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        capital_call::cpi::deposit(
+            CpiContext::new(
+                ctx.accounts.capital_call_program.to_account_info(),
+                capital_call::cpi::accounts::Deposit {
+                    config: ctx.accounts.config.to_account_info(),
+                    capital_call: ctx.accounts.capital_call.to_account_info(),
+                    voucher: ctx.accounts.voucher.to_account_info(),
+                    vault: ctx.accounts.vault.to_account_info(),
+                    authority: ctx.accounts.authority.to_account_info(),
+                    source: ctx.accounts.source.to_account_info(),
+                    token_program: ctx.accounts.token_program.to_account_info(),
+                    system_program: ctx.accounts.system_program.to_account_info(),
+                },
+            )
+            .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
+            amount,
+            None,
+            [0; 32],
+        )
+    }
+
+    /// Money market deposit invoked by capital call with yield strategy, receipt tokens are
+    /// minted pro rata to reserve
+    pub fn yield_deposit(ctx: Context<YieldStrategy>, amount: u64) -> Result<()> {
+        let supply = ctx.accounts.receipt_mint.supply;
+        let reserve = ctx.accounts.reserve.amount;
+        let receipt_amount = if supply == 0 || reserve == 0 {
+            amount
+        } else {
+            u64::try_from(amount as u128 * supply as u128 / reserve as u128)
+                .map_err(|_| error!(CallbackMockError::CalculationError))?
+        };
+
+        token::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.vault.to_account_info(),
+                    to: ctx.accounts.reserve.to_account_info(),
+                    authority: ctx.accounts.capital_call.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+
+        let bump = *ctx
+            .bumps
+            .get("market_authority")
+            .ok_or_else(|| error!(CallbackMockError::CalculationError))?;
+        token::mint_to(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                MintTo {
+                    mint: ctx.accounts.receipt_mint.to_account_info(),
+                    to: ctx.accounts.receipt.to_account_info(),
+                    authority: ctx.accounts.market_authority.to_account_info(),
+                },
+                &[&[SEED_MARKET_AUTHORITY, &[bump]]],
+            ),
+            receipt_amount,
+        )
+    }
+
+    /// Money market withdrawal of all receipt tokens of capital call with accrued yield
+    pub fn yield_withdraw(ctx: Context<YieldStrategy>) -> Result<()> {
+        let supply = ctx.accounts.receipt_mint.supply;
+        let receipt_amount = ctx.accounts.receipt.amount;
+        let amount = if supply == 0 {
+            0
+        } else {
+            u64::try_from(
+                receipt_amount as u128 * ctx.accounts.reserve.amount as u128 / supply as u128,
+            )
+            .map_err(|_| error!(CallbackMockError::CalculationError))?
+        };
+
+        token::burn(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Burn {
+                    mint: ctx.accounts.receipt_mint.to_account_info(),
+                    from: ctx.accounts.receipt.to_account_info(),
+                    authority: ctx.accounts.capital_call.to_account_info(),
+                },
+            ),
+            receipt_amount,
+        )?;
+
+        let bump = *ctx
+            .bumps
+            .get("market_authority")
+            .ok_or_else(|| error!(CallbackMockError::CalculationError))?;
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.reserve.to_account_info(),
+                    to: ctx.accounts.vault.to_account_info(),
+                    authority: ctx.accounts.market_authority.to_account_info(),
+                },
+                &[&[SEED_MARKET_AUTHORITY, &[bump]]],
+            ),
+            amount,
+        )
+    }
+
+    /// Tier oracle record of `wallet` read by capital call deposits
+    pub fn set_tier(ctx: Context<SetTier>, wallet: Pubkey, tier: u8) -> Result<()> {
+        ctx.accounts.tier_record.wallet = wallet;
+        ctx.accounts.tier_record.tier = tier;
+        Ok(())
+    }
+
+    /// Price account of `feed` read as oracle by capital calls with oracle conversion
+    pub fn set_price(
+        ctx: Context<SetPrice>,
+        _feed: Pubkey,
+        price: i64,
+        conf: u64,
+        expo: i32,
+        publish_time: i64,
+    ) -> Result<()> {
+        let record = &mut ctx.accounts.price_record;
+        record.price = price;
+        record.conf = conf;
+        record.expo = expo;
+        record.publish_time = publish_time;
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct OnCapitalCallFinalized<'info> {
+    /// CHECK: Only for key
+    pub capital_call: UncheckedAccount<'info>,
+
+    /// CHECK: Only for key
+    pub config: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ForwardDeposit<'info> {
+    /// CHECK: Checked by capital call program
+    #[account(mut)]
+    pub config: UncheckedAccount<'info>,
+
+    /// CHECK: Checked by capital call program
+    #[account(mut)]
+    pub capital_call: UncheckedAccount<'info>,
+
+    /// CHECK: Checked by capital call program
+    #[account(mut)]
+    pub voucher: UncheckedAccount<'info>,
+
+    /// CHECK: Checked by capital call program
+    #[account(mut)]
+    pub vault: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// CHECK: Checked by capital call program
+    #[account(mut)]
+    pub source: UncheckedAccount<'info>,
+
+    /// CHECK: Checked by capital call program
+    pub token_program: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    pub capital_call_program: Program<'info, capital_call::program::CapitalCall>,
+}
+
+#[derive(Accounts)]
+pub struct YieldStrategy<'info> {
+    pub capital_call: Signer<'info>,
+
+    #[account(mut)]
+    pub vault: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+
+    #[account(
+        mut,
+        constraint = reserve.owner == market_authority.key(),
+        constraint = reserve.mint == vault.mint,
+    )]
+    pub reserve: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = receipt_mint.mint_authority == COption::Some(market_authority.key()),
+    )]
+    pub receipt_mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = receipt.mint == receipt_mint.key(),
+        constraint = receipt.owner == capital_call.key(),
+    )]
+    pub receipt: Account<'info, TokenAccount>,
+
+    /// CHECK: Only for signing
+    #[account(seeds = [SEED_MARKET_AUTHORITY], bump)]
+    pub market_authority: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(wallet: Pubkey)]
+pub struct SetTier<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + std::mem::size_of::<TierRecord>(),
+        seeds = [SEED_TIER, wallet.as_ref()],
+        bump
+    )]
+    pub tier_record: Account<'info, TierRecord>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(feed: Pubkey)]
+pub struct SetPrice<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + std::mem::size_of::<PriceRecord>(),
+        seeds = [SEED_PRICE, feed.as_ref()],
+        bump
+    )]
+    pub price_record: Account<'info, PriceRecord>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[account]
+pub struct PriceRecord {
+    pub price: i64,
+    pub conf: u64,
+    pub expo: i32,
+    pub publish_time: i64,
+}
+
+#[account]
+pub struct TierRecord {
+    pub wallet: Pubkey,
+    pub tier: u8,
+}
+
+#[error_code]
+pub enum CallbackMockError {
+    UnexpectedCapitalCall,
+    CalculationError,
+}
+
+#[event]
+pub struct CallbackReceivedEvent {
+    pub capital_call: Pubkey,
+    pub config: Pubkey,
+    pub minted: u64,
+}
@@ -1,7 +1,19 @@
 use anchor_lang::prelude::*;
-use anchor_lang::solana_program::clock::Clock;
+use anchor_lang::solana_program::clock::{Clock, DEFAULT_MS_PER_SLOT};
+use anchor_lang::solana_program::ed25519_program;
+use anchor_lang::solana_program::hash::hash;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::program::{invoke, invoke_signed};
 use anchor_lang::solana_program::program_option::COption;
-use anchor_spl::token::{self, Burn, CloseAccount, Mint, MintTo, Token, TokenAccount, Transfer};
+use anchor_lang::solana_program::program_pack::Pack;
+use anchor_lang::solana_program::sysvar::instructions::{self, get_instruction_relative};
+use anchor_lang::system_program;
+use anchor_lang::AccountsClose;
+use anchor_spl::associated_token::get_associated_token_address;
+use anchor_spl::token::{
+    self, Burn, CloseAccount, FreezeAccount, InitializeAccount, Mint, MintTo, SyncNative, Token,
+    TokenAccount, Transfer,
+};
 
 declare_id!("HRsNi3EmPjTLwEfekPYzBQmdy5UqZ7MKmcvi5rjuHder");
 
@@ -10,6 +22,54 @@ pub const SEED_VAULT: [u8; 5] = *b"vault";
 pub const SEED_LP_TOKEN_POOL: [u8; 13] = *b"lp_token_pool";
 pub const SEED_VOUCHER: [u8; 7] = *b"voucher";
 pub const SEED_LP_MINT_AUTHORITY: [u8; 17] = *b"lp_mint_authority";
+pub const SEED_REFERRAL: [u8; 8] = *b"referral";
+pub const SEED_SNAPSHOT: [u8; 8] = *b"snapshot";
+pub const SEED_SECONDARY_VAULT: [u8; 15] = *b"secondary_vault";
+pub const SEED_LP_LOCKUP: [u8; 9] = *b"lp_lockup";
+pub const SEED_LP_ESCROW: [u8; 9] = *b"lp_escrow";
+// Seed of tier record of wallet at tier oracle program
+pub const SEED_TIER: [u8; 4] = *b"tier";
+
+pub const BPS_DENOMINATOR: u64 = 10_000;
+
+/// Version of event layouts, bumped on every upgrade changing them
+pub const PROGRAM_VERSION: u16 = 1;
+
+/// Maximum number of liquidity pools in `finalize_split`
+pub const MAX_SPLIT_POOLS: usize = 4;
+
+/// Scale of LP tokens per underlying token rate bounds of capital call
+pub const RATE_PRECISION: u64 = 1_000_000_000;
+
+/// Maximum number of bonus tiers of capital call
+pub const MAX_BONUS_TIERS: usize = 3;
+
+/// Maximum number of unlock points of capital call vesting table
+pub const MAX_UNLOCK_POINTS: usize = 4;
+
+/// Maximum number of depositors in `finalize_and_distribute`
+pub const MAX_DISTRIBUTE_DEPOSITORS: usize = 5;
+
+/// Maximum number of capital calls in `create_capital_calls_batch`
+pub const MAX_CREATE_BATCH_SIZE: usize = 4;
+
+/// Maximum number of depositors in `cancel_and_refund`
+pub const MAX_REFUND_DEPOSITORS: usize = 5;
+
+/// Maximum number of vouchers passed in `remaining_accounts` of `snapshot` and
+/// `log_allocations`, clients have to chunk larger sets to fit compute budget
+pub const MAX_BATCH_SIZE: usize = 20;
+
+/// Preimage of discriminator of instruction invoked on `finalize_callback_program`
+pub const FINALIZE_CALLBACK_IX_PREIMAGE: &[u8] = b"global:on_capital_call_finalized";
+
+/// Preimages of discriminators of instructions invoked on `yield_program` of capital call
+pub const YIELD_DEPOSIT_IX_PREIMAGE: &[u8] = b"global:yield_deposit";
+pub const YIELD_WITHDRAW_IX_PREIMAGE: &[u8] = b"global:yield_withdraw";
+
+/// Number of yield strategy accounts at the end of `remaining_accounts`: yield program followed
+/// by money market accounts, which are passed through
+pub const YIELD_STRATEGY_ACCOUNTS: usize = 5;
 
 pub const MINT_PUBKEY: &str = "ETE5KJSyx1XitibZc9hb35AneRmCH8riJzyxr9beKtZ6";
 
@@ -22,6 +82,7 @@ pub mod capital_call {
         ctx.accounts.config.authority = ctx.accounts.authority.key();
         ctx.accounts.config.liquidity_pool = ctx.accounts.liquidity_pool.key();
         ctx.accounts.config.lp_mint = ctx.accounts.lp_mint.key();
+        ctx.accounts.config.lp_decimals = ctx.accounts.lp_mint.decimals;
         ctx.accounts.config.lp_mint_authority = ctx.accounts.lp_mint_authority.key();
         ctx.accounts.config.bump = *ctx
             .bumps
@@ -30,36 +91,197 @@ pub mod capital_call {
         Ok(())
     }
 
-    /// Create new capital call
+    /// Update optional config settings, `None` leaves setting unchanged
+    pub fn update_config(ctx: Context<UpdateConfig>, params: ConfigParams) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+
+        if let Some(finalize_callback_program) = params.finalize_callback_program {
+            require!(
+                finalize_callback_program != crate::ID,
+                CapitalCallError::InvalidFinalizeCallbackProgram
+            );
+            config.finalize_callback_program = finalize_callback_program;
+        }
+        if let Some(claim_fee_bps) = params.claim_fee_bps {
+            require!(
+                claim_fee_bps as u64 <= BPS_DENOMINATOR,
+                CapitalCallError::InvalidBps
+            );
+            config.claim_fee_bps = claim_fee_bps;
+        }
+        if let Some(fee_lp_account) = params.fee_lp_account {
+            config.fee_lp_account = fee_lp_account;
+        }
+        if let Some(min_credit_outstanding) = params.min_credit_outstanding {
+            config.min_credit_outstanding = min_credit_outstanding;
+        }
+        if let Some(min_pool_balance) = params.min_pool_balance {
+            config.min_pool_balance = min_pool_balance;
+        }
+        if let Some(min_claim) = params.min_claim {
+            config.min_claim = min_claim;
+        }
+        if let Some(max_concurrent_calls) = params.max_concurrent_calls {
+            config.max_concurrent_calls = max_concurrent_calls;
+        }
+        if let Some(authority_bypass_whitelist) = params.authority_bypass_whitelist {
+            config.authority_bypass_whitelist = authority_bypass_whitelist;
+        }
+        if let Some(ata_destinations) = params.ata_destinations {
+            config.ata_destinations = ata_destinations;
+        }
+        if let Some(min_lead_time) = params.min_lead_time {
+            config.min_lead_time = min_lead_time;
+        }
+        if let Some(max_finalize_transfer) = params.max_finalize_transfer {
+            config.max_finalize_transfer = max_finalize_transfer;
+        }
+        if let Some(round_up_claims) = params.round_up_claims {
+            config.round_up_claims = round_up_claims;
+        }
+        if let Some(debt_account) = params.debt_account {
+            config.debt_account = debt_account;
+        }
+        if let Some(min_open_before_cancel) = params.min_open_before_cancel {
+            config.min_open_before_cancel = min_open_before_cancel;
+        }
+        if let Some(max_partial_claims) = params.max_partial_claims {
+            config.max_partial_claims = max_partial_claims;
+        }
+        if let Some(yield_program) = params.yield_program {
+            require!(
+                yield_program != crate::ID,
+                CapitalCallError::InvalidYieldProgram
+            );
+            config.yield_program = yield_program;
+        }
+        if let Some(min_window_slots) = params.min_window_slots {
+            config.min_window_slots = min_window_slots;
+        }
+        if let Some(min_capacity) = params.min_capacity {
+            config.min_capacity = min_capacity;
+        }
+        if let Some(max_capacity) = params.max_capacity {
+            config.max_capacity = max_capacity;
+        }
+        if let Some(tier_oracle) = params.tier_oracle {
+            config.tier_oracle = tier_oracle;
+        }
+        if let Some(rent_to_payer) = params.rent_to_payer {
+            config.rent_to_payer = rent_to_payer;
+        }
+        if let Some(reject_delegated_source) = params.reject_delegated_source {
+            config.reject_delegated_source = reject_delegated_source;
+        }
+        if let Some(participation_mint) = params.participation_mint {
+            config.participation_mint = participation_mint;
+        }
+        if let Some(surplus_treasury) = params.surplus_treasury {
+            config.surplus_treasury = surplus_treasury;
+        }
+        if let Some(min_fill_to_extend_bps) = params.min_fill_to_extend_bps {
+            require!(
+                min_fill_to_extend_bps as u64 <= BPS_DENOMINATOR,
+                CapitalCallError::InvalidBps
+            );
+            config.min_fill_to_extend_bps = min_fill_to_extend_bps;
+        }
+        if let Some(strict_claim_destination) = params.strict_claim_destination {
+            config.strict_claim_destination = strict_claim_destination;
+        }
+        if let Some(void_treasury) = params.void_treasury {
+            config.void_treasury = void_treasury;
+        }
+
+        Ok(())
+    }
+
+    /// Close config of deployment without open capital calls and vaults retained by their
+    /// close.
+    /// `remaining_accounts` are capital calls or vaults of the config known off-chain, each has
+    /// to be closed already.
+    pub fn close_config(ctx: Context<CloseConfig>) -> Result<()> {
+        for info in ctx.remaining_accounts.iter() {
+            require!(
+                info.lamports() == 0 && info.data_is_empty(),
+                CapitalCallError::CapitalCallNotClosed
+            );
+        }
+        require!(
+            ctx.accounts.config.open_calls == 0,
+            CapitalCallError::OpenCallsRemain
+        );
+        require!(
+            ctx.accounts.config.retained_vaults == 0,
+            CapitalCallError::RetainedVaultsRemain
+        );
+
+        Ok(())
+    }
+
+    /// Set guardian allowed to freeze, pause finalization of and cancel capital calls,
+    /// default pubkey removes guardian
+    pub fn set_guardian(ctx: Context<UpdateConfig>, guardian: Pubkey) -> Result<()> {
+        ctx.accounts.config.guardian = guardian;
+        Ok(())
+    }
+
+    /// Halt or resume every instruction of all capital calls of config, settable by config
+    /// authority or guardian
+    pub fn set_killed(ctx: Context<SetKilled>, killed: bool) -> Result<()> {
+        ctx.accounts.config.killed = killed;
+
+        emit!(KillSwitchEvent {
+            program_version: PROGRAM_VERSION,
+            config: ctx.accounts.config.key(),
+            killed,
+        });
+
+        Ok(())
+    }
+
+    /// Create new capital call.
+    /// If config has `debt_account`, it's the first of `remaining_accounts` and
+    /// `credit_outstanding` has to match its balance.
     pub fn create_capital_call(
         ctx: Context<CreateCapitalCall>,
         start_time: u64,
         duration: u64,
         capacity: u64,
         credit_outstanding: u64,
+        idempotency_key: [u8; 16],
+        manager: Pubkey,
+        refund_lockup: u64,
     ) -> Result<()> {
+        require!(!ctx.accounts.config.killed, CapitalCallError::ProgramKilled);
+
         let clock = Clock::get().map_err::<error::Error, _>(Into::into)?;
         let now = clock.unix_timestamp as u64;
 
-        require!(start_time >= now, CapitalCallError::StartTimeMustBeInFuture);
-        require!(duration > 0, CapitalCallError::DurationNonZero);
-        require!(capacity > 0, CapitalCallError::CapacityNonZero);
+        let params = CreateParams {
+            start_time,
+            duration,
+            capacity,
+            credit_outstanding,
+            idempotency_key,
+            manager,
+            refund_lockup,
+        };
+        validate_create_params(
+            &ctx.accounts.config,
+            &params,
+            ctx.remaining_accounts.first(),
+            now,
+        )?;
 
+        let config_key = ctx.accounts.config.key();
+        let vault = ctx.accounts.vault.key();
+        let lp_token_pool = ctx.accounts.lp_token_pool.key();
+        let round_up_claims = ctx.accounts.config.round_up_claims;
+        let payer = ctx.accounts.payer.key();
         let capital_call = &mut ctx.accounts.capital_call;
-        capital_call.config = ctx.accounts.config.key();
-        capital_call.vault = ctx.accounts.vault.key();
-        capital_call.lp_token_pool = ctx.accounts.lp_token_pool.key();
-
-        capital_call.start_time = start_time;
-        capital_call.end_time = start_time + duration;
-        capital_call.capacity = capacity;
-        capital_call.redeemed = 0;
-        capital_call.allocated = 0;
-        capital_call.is_lp_minted = false;
-
-        capital_call.token_liquidity = 0;
-        capital_call.lp_supply = 0;
-        capital_call.credit_outstanding = credit_outstanding;
+        capital_call.init(config_key, vault, lp_token_pool, &params, round_up_claims);
+        capital_call.payer = payer;
 
         capital_call.bump = *ctx
             .bumps
@@ -73,838 +295,7970 @@ pub mod capital_call {
             .bumps
             .get("lp_token_pool")
             .ok_or_else(|| error!(CapitalCallError::BumpSeedNotInHashMap))?;
+
+        ctx.accounts.capital_call.call_id = ctx.accounts.config.add_capital_call(capacity)?;
+
+        emit!(CreateCapitalCallEvent {
+            program_version: PROGRAM_VERSION,
+            config: ctx.accounts.config.key(),
+            capital_call: ctx.accounts.capital_call.key(),
+            call_id: ctx.accounts.capital_call.call_id,
+            start_time,
+            end_time: ctx.accounts.capital_call.end_time,
+            capacity,
+            refund_available_at: ctx.accounts.capital_call.refund_available_at(),
+        });
+
         Ok(())
     }
 
-    pub fn deposit(ctx: Context<Deposit>, amount: u64) -> Result<()> {
-        let clock = Clock::get().map_err::<error::Error, _>(Into::into)?;
-        let now = clock.unix_timestamp as u64;
-        let capital_call = &ctx.accounts.capital_call;
-
-        require!(
-            now >= capital_call.start_time,
-            CapitalCallError::CapitalCallNotStarted
-        );
+    /// Create several capital calls of config in one transaction, at most
+    /// `MAX_CREATE_BATCH_SIZE`.
+    /// Each capital call is the next three of `remaining_accounts`: capital call, vault and LP
+    /// token pool at their derived addresses, they are created here. If config has
+    /// `debt_account`, it's the first of `remaining_accounts`.
+    pub fn create_capital_calls_batch<'info>(
+        ctx: Context<'_, '_, '_, 'info, CreateCapitalCallsBatch<'info>>,
+        params: Vec<CreateParams>,
+    ) -> Result<()> {
+        require!(!ctx.accounts.config.killed, CapitalCallError::ProgramKilled);
         require!(
-            now < capital_call.end_time,
-            CapitalCallError::CapitalCallEnded
+            !params.is_empty() && params.len() <= MAX_CREATE_BATCH_SIZE,
+            CapitalCallError::InvalidBatch
         );
+        let (debt_info, call_accounts) = if ctx.accounts.config.debt_account != Pubkey::default()
+        {
+            let (debt_info, call_accounts) = ctx
+                .remaining_accounts
+                .split_first()
+                .ok_or_else(|| error!(CapitalCallError::InvalidDebtAccount))?;
+            (Some(debt_info), call_accounts)
+        } else {
+            (None, ctx.remaining_accounts)
+        };
         require!(
-            capital_call.capacity > capital_call.allocated,
-            CapitalCallError::CapitalCallAlreadyFullyFunded
+            call_accounts.len() == params.len() * 3,
+            CapitalCallError::InvalidBatch
         );
-        require!(amount > 0, CapitalCallError::AmountNonZero);
-
-        // Reduce amount if this tx fills vault
-        let amount = amount.min(capital_call.capacity - capital_call.allocated);
-
-        let config = capital_call.config.key();
-        let start_time = capital_call.start_time.to_le_bytes();
-        let capacity = capital_call.capacity.to_le_bytes();
-
-        let seeds = [
-            SEED_CAPITAL_CALL.as_ref(),
-            config.as_ref(),
-            start_time.as_ref(),
-            capacity.as_ref(),
-            &[ctx.accounts.capital_call.bump],
-        ];
-
-        let cpi_ctx: CpiContext<_> = ctx.accounts.into();
-        token::transfer(cpi_ctx.with_signer(&[&seeds]), amount)?;
-
-        ctx.accounts.capital_call.allocated += amount;
-
-        let voucher = &mut ctx.accounts.voucher;
-        voucher.capital_call = ctx.accounts.capital_call.key();
-        voucher.authority = ctx.accounts.authority.key();
-        voucher.amount = amount;
-        voucher.bump = *ctx
-            .bumps
-            .get("voucher")
-            .ok_or_else(|| error!(CapitalCallError::BumpSeedNotInHashMap))?;
 
-        emit!(DepositEvent {
-            config: ctx.accounts.capital_call.config,
-            capital_call: ctx.accounts.capital_call.key(),
-            authority: ctx.accounts.authority.key(),
-            amount,
-        });
+        let clock = Clock::get().map_err::<error::Error, _>(Into::into)?;
+        let now = clock.unix_timestamp as u64;
+        let config_key = ctx.accounts.config.key();
 
-        if ctx.accounts.capital_call.capacity == ctx.accounts.capital_call.allocated {
-            emit!(CapitalFullyRaisedEvent {
-                config: ctx.accounts.capital_call.config,
-                capital_call: ctx.accounts.capital_call.key(),
+        for (params, accounts) in params.iter().zip(call_accounts.chunks(3)) {
+            validate_create_params(&ctx.accounts.config, params, debt_info, now)?;
+
+            let start_time = params.start_time.to_le_bytes();
+            let capacity = params.capacity.to_le_bytes();
+            let (capital_call_key, bump) = Pubkey::find_program_address(
+                &[
+                    SEED_CAPITAL_CALL.as_ref(),
+                    config_key.as_ref(),
+                    start_time.as_ref(),
+                    capacity.as_ref(),
+                    params.idempotency_key.as_ref(),
+                ],
+                &crate::ID,
+            );
+            require!(
+                accounts[0].key() == capital_call_key,
+                CapitalCallError::InvalidBatch
+            );
+            require!(
+                accounts[0].data_is_empty(),
+                CapitalCallError::DuplicateCapitalCall
+            );
+            create_pda_account(
+                &ctx.accounts.payer,
+                &accounts[0],
+                &ctx.accounts.system_program,
+                &ctx.accounts.rent,
+                CapitalCall::SPACE,
+                &crate::ID,
+                &[
+                    SEED_CAPITAL_CALL.as_ref(),
+                    config_key.as_ref(),
+                    start_time.as_ref(),
+                    capacity.as_ref(),
+                    params.idempotency_key.as_ref(),
+                    &[bump],
+                ],
+            )?;
+
+            let mut token_accounts = Vec::with_capacity(2);
+            for (info, seed, mint) in [
+                (&accounts[1], &SEED_VAULT[..], ctx.accounts.mint.to_account_info()),
+                (
+                    &accounts[2],
+                    &SEED_LP_TOKEN_POOL[..],
+                    ctx.accounts.lp_mint.to_account_info(),
+                ),
+            ] {
+                let (key, token_bump) =
+                    Pubkey::find_program_address(&[seed, capital_call_key.as_ref()], &crate::ID);
+                require!(info.key() == key, CapitalCallError::InvalidBatch);
+                create_pda_account(
+                    &ctx.accounts.payer,
+                    info,
+                    &ctx.accounts.system_program,
+                    &ctx.accounts.rent,
+                    token::spl_token::state::Account::LEN,
+                    &token::ID,
+                    &[seed, capital_call_key.as_ref(), &[token_bump]],
+                )?;
+                token::initialize_account(CpiContext::new(
+                    ctx.accounts.token_program.to_account_info(),
+                    InitializeAccount {
+                        account: info.clone(),
+                        mint,
+                        authority: accounts[0].clone(),
+                        rent: ctx.accounts.rent.to_account_info(),
+                    },
+                ))?;
+                token_accounts.push((key, token_bump));
+            }
+            let (vault, vault_bump) = token_accounts[0];
+            let (lp_token_pool, lp_token_pool_bump) = token_accounts[1];
+
+            let mut capital_call = Account::<CapitalCall>::try_from_unchecked(&accounts[0])?;
+            capital_call.init(
+                config_key,
+                vault,
+                lp_token_pool,
+                params,
+                ctx.accounts.config.round_up_claims,
+            );
+            capital_call.bump = bump;
+            capital_call.vault_bump = vault_bump;
+            capital_call.lp_token_pool_bump = lp_token_pool_bump;
+            capital_call.payer = ctx.accounts.payer.key();
+            capital_call.call_id = ctx.accounts.config.add_capital_call(params.capacity)?;
+            capital_call.exit(&crate::ID)?;
+
+            emit!(CreateCapitalCallEvent {
+                program_version: PROGRAM_VERSION,
+                config: config_key,
+                capital_call: capital_call_key,
+                call_id: capital_call.call_id,
+                start_time: params.start_time,
+                end_time: capital_call.end_time,
+                capacity: params.capacity,
+                refund_available_at: capital_call.refund_available_at(),
             });
         }
 
         Ok(())
     }
 
-    /// Refund tokens if capital is not raised
-    pub fn refund(ctx: Context<Refund>) -> Result<()> {
-        let clock = Clock::get().map_err::<error::Error, _>(Into::into)?;
-        let now = clock.unix_timestamp as u64;
-        let capital_call = &ctx.accounts.capital_call;
+    /// Update optional capital call settings, allowed only until first deposit
+    pub fn update_capital_call(
+        ctx: Context<UpdateCapitalCall>,
+        params: CapitalCallParams,
+    ) -> Result<()> {
+        require!(!ctx.accounts.config.killed, CapitalCallError::ProgramKilled);
+        let capital_call = &mut ctx.accounts.capital_call;
 
         require!(
-            capital_call.capacity > capital_call.allocated,
-            CapitalCallError::CapitalCallIsFullyFunded
+            capital_call.allocated == 0
+                && capital_call.pending_amount == 0
+                && !capital_call.is_lp_minted,
+            CapitalCallError::CapitalCallTermsLocked
         );
+
+        if let Some(start_slot) = params.start_slot {
+            capital_call.start_slot = start_slot;
+        }
+        if let Some(refund_cooldown) = params.refund_cooldown {
+            capital_call.refund_cooldown = refund_cooldown;
+        }
+        if let Some(progress_event_step_bps) = params.progress_event_step_bps {
+            require!(
+                progress_event_step_bps as u64 <= BPS_DENOMINATOR,
+                CapitalCallError::InvalidBps
+            );
+            capital_call.progress_event_step_bps = progress_event_step_bps;
+        }
+        if let Some(vest_start) = params.vest_start {
+            capital_call.vest_start = vest_start;
+        }
+        if let Some(vest_cliff) = params.vest_cliff {
+            capital_call.vest_cliff = vest_cliff;
+        }
+        if let Some(vest_duration) = params.vest_duration {
+            capital_call.vest_duration = vest_duration;
+        }
+        if let Some(gatekeeper) = params.gatekeeper {
+            capital_call.gatekeeper = gatekeeper;
+        }
+        if let Some(allowed_depositor_program) = params.allowed_depositor_program {
+            capital_call.allowed_depositor_program = allowed_depositor_program;
+        }
+        if let Some(finalize_window) = params.finalize_window {
+            capital_call.finalize_window = finalize_window;
+        }
+        if let Some(refund_to_source) = params.refund_to_source {
+            capital_call.refund_to_source = refund_to_source;
+        }
+        if let Some(refund_to_source_owner) = params.refund_to_source_owner {
+            capital_call.refund_to_source_owner = refund_to_source_owner;
+        }
+        if let Some(reserve_bps) = params.reserve_bps {
+            require!(
+                reserve_bps as u64 <= BPS_DENOMINATOR,
+                CapitalCallError::InvalidBps
+            );
+            capital_call.reserve_bps = reserve_bps;
+        }
+        if let Some(confirmation_slots) = params.confirmation_slots {
+            capital_call.confirmation_slots = confirmation_slots;
+        }
+        if let Some(voucher_expiry) = params.voucher_expiry {
+            capital_call.voucher_expiry = voucher_expiry;
+        }
+        if let Some(claim_delay) = params.claim_delay {
+            capital_call.claim_delay = claim_delay;
+        }
+        if let Some(finalize_cooldown) = params.finalize_cooldown {
+            capital_call.finalize_cooldown = finalize_cooldown;
+        }
+        if let Some(twap_release_duration) = params.twap_release_duration {
+            capital_call.twap_release_duration = twap_release_duration;
+        }
+        if let Some(match_reserve) = params.match_reserve {
+            capital_call.match_reserve = match_reserve;
+        }
+        if let Some(match_bps) = params.match_bps {
+            require!(
+                match_bps as u64 <= BPS_DENOMINATOR,
+                CapitalCallError::InvalidBps
+            );
+            capital_call.match_bps = match_bps;
+        }
+        if let Some(auto_extend_threshold_bps) = params.auto_extend_threshold_bps {
+            require!(
+                auto_extend_threshold_bps as u64 <= BPS_DENOMINATOR,
+                CapitalCallError::InvalidBps
+            );
+            capital_call.auto_extend_threshold_bps = auto_extend_threshold_bps;
+        }
+        if let Some(auto_extend_duration) = params.auto_extend_duration {
+            capital_call.auto_extend_duration = auto_extend_duration;
+        }
+        if let Some(custodial_vault) = params.custodial_vault {
+            capital_call.custodial_vault = custodial_vault;
+        }
+        if let Some(custodian) = params.custodian {
+            capital_call.custodian = custodian;
+        }
+        if let Some(min_rate) = params.min_rate {
+            capital_call.min_rate = min_rate;
+        }
+        if let Some(max_rate) = params.max_rate {
+            capital_call.max_rate = max_rate;
+        }
+        if let Some(allow_clamp) = params.allow_clamp {
+            capital_call.allow_clamp = allow_clamp;
+        }
+        if let Some(strict_vault_balance) = params.strict_vault_balance {
+            capital_call.strict_vault_balance = strict_vault_balance;
+        }
+        if let Some(use_oracle_conversion) = params.use_oracle_conversion {
+            capital_call.use_oracle_conversion = use_oracle_conversion;
+        }
+        if let Some(oracle) = params.oracle {
+            capital_call.oracle = oracle;
+        }
+        if let Some(oracle_max_age) = params.oracle_max_age {
+            capital_call.oracle_max_age = oracle_max_age;
+        }
+        if let Some(oracle_max_conf_bps) = params.oracle_max_conf_bps {
+            require!(
+                oracle_max_conf_bps as u64 <= BPS_DENOMINATOR,
+                CapitalCallError::InvalidBps
+            );
+            capital_call.oracle_max_conf_bps = oracle_max_conf_bps;
+        }
+        if let Some(min_deposit) = params.min_deposit {
+            capital_call.min_deposit = min_deposit;
+        }
+        if let Some(stake_mint) = params.stake_mint {
+            capital_call.stake_mint = stake_mint;
+        }
+        if let Some(stake_cap_multiplier) = params.stake_cap_multiplier {
+            capital_call.stake_cap_multiplier = stake_cap_multiplier;
+        }
+        if let Some(bonus_tiers) = params.bonus_tiers {
+            require!(
+                bonus_tiers.len() <= MAX_BONUS_TIERS,
+                CapitalCallError::InvalidBonusTiers
+            );
+            let mut min_amount = 0;
+            for tier in bonus_tiers.iter() {
+                require!(
+                    tier.min_amount > min_amount && tier.bonus_bps as u64 <= BPS_DENOMINATOR,
+                    CapitalCallError::InvalidBonusTiers
+                );
+                min_amount = tier.min_amount;
+            }
+            capital_call.bonus_tiers = Default::default();
+            capital_call.bonus_tiers[..bonus_tiers.len()].copy_from_slice(&bonus_tiers);
+        }
+        if let Some(unlock_points) = params.unlock_points {
+            require!(
+                unlock_points.len() <= MAX_UNLOCK_POINTS,
+                CapitalCallError::InvalidUnlockPoints
+            );
+            let mut timestamp = 0;
+            for point in unlock_points.iter() {
+                require!(
+                    point.timestamp > timestamp && point.bps > 0,
+                    CapitalCallError::InvalidUnlockPoints
+                );
+                timestamp = point.timestamp;
+            }
+            // Empty table restores linear vesting
+            require!(
+                unlock_points.is_empty()
+                    || unlock_points.iter().map(|point| point.bps as u64).sum::<u64>()
+                        == BPS_DENOMINATOR,
+                CapitalCallError::InvalidUnlockPoints
+            );
+            capital_call.unlock_points = Default::default();
+            capital_call.unlock_points[..unlock_points.len()].copy_from_slice(&unlock_points);
+        }
+        if let Some(max_fill_bps_per_tx) = params.max_fill_bps_per_tx {
+            require!(
+                max_fill_bps_per_tx as u64 <= BPS_DENOMINATOR,
+                CapitalCallError::InvalidBps
+            );
+            capital_call.max_fill_bps_per_tx = max_fill_bps_per_tx;
+        }
+        if let Some(credit_token_mint) = params.credit_token_mint {
+            capital_call.credit_token_mint = credit_token_mint;
+        }
+        if let Some(referral_bps) = params.referral_bps {
+            require!(
+                referral_bps as u64 <= BPS_DENOMINATOR,
+                CapitalCallError::InvalidBps
+            );
+            capital_call.referral_bps = referral_bps;
+        }
+        if let Some(yield_strategy) = params.yield_strategy {
+            capital_call.yield_program = if yield_strategy {
+                require!(
+                    ctx.accounts.config.yield_program != Pubkey::default(),
+                    CapitalCallError::InvalidYieldProgram
+                );
+                ctx.accounts.config.yield_program
+            } else {
+                Pubkey::default()
+            };
+        }
+        if let Some(fast_fill_threshold) = params.fast_fill_threshold {
+            capital_call.fast_fill_threshold = fast_fill_threshold;
+        }
+        if let Some(fast_fill_fee_bps) = params.fast_fill_fee_bps {
+            require!(
+                fast_fill_fee_bps as u64 <= BPS_DENOMINATOR,
+                CapitalCallError::InvalidBps
+            );
+            capital_call.fast_fill_fee_bps = fast_fill_fee_bps;
+        }
+        if let Some(slow_fill_fee_bps) = params.slow_fill_fee_bps {
+            require!(
+                slow_fill_fee_bps as u64 <= BPS_DENOMINATOR,
+                CapitalCallError::InvalidBps
+            );
+            capital_call.slow_fill_fee_bps = slow_fill_fee_bps;
+        }
+        if let Some(max_valuation_age) = params.max_valuation_age {
+            capital_call.max_valuation_age = max_valuation_age;
+        }
+        if let Some(lp_lockup) = params.lp_lockup {
+            capital_call.lp_lockup = lp_lockup;
+        }
+        if let Some(finalize_chunk) = params.finalize_chunk {
+            capital_call.finalize_chunk = finalize_chunk;
+        }
+
+        // Checked after all params, vouchers can't expire before they can be claimed in full
         require!(
-            now >= capital_call.end_time,
-            CapitalCallError::CapitalCallNotEnded
+            capital_call.voucher_expiry == 0
+                || capital_call.voucher_expiry > capital_call.claim_horizon()?,
+            CapitalCallError::InvalidVoucherExpiry
         );
 
-        let config = capital_call.config;
-        let start_time = capital_call.start_time.to_le_bytes();
-        let capacity = capital_call.capacity.to_le_bytes();
+        Ok(())
+    }
 
-        let seeds = [
-            SEED_CAPITAL_CALL.as_ref(),
-            config.as_ref(),
-            start_time.as_ref(),
-            capacity.as_ref(),
-            &[ctx.accounts.capital_call.bump],
-        ];
+    /// Freeze or unfreeze all user actions and finalization of capital call
+    pub fn set_call_frozen(ctx: Context<SetCallFrozen>, frozen: bool) -> Result<()> {
+        let capital_call = &mut ctx.accounts.capital_call;
+        capital_call.frozen = frozen;
 
-        let amount = ctx.accounts.voucher.amount;
-        let cpi_ctx: CpiContext<_> = ctx.accounts.into();
-        token::transfer(cpi_ctx.with_signer(&[&seeds]), amount)?;
+        if frozen {
+            emit!(CapitalCallFrozenEvent {
+                program_version: PROGRAM_VERSION,
+                config: capital_call.config,
+                capital_call: capital_call.key(),
+            });
+        } else {
+            emit!(CapitalCallUnfrozenEvent {
+                program_version: PROGRAM_VERSION,
+                config: capital_call.config,
+                capital_call: capital_call.key(),
+            });
+        }
 
-        ctx.accounts.capital_call.redeemed += amount;
+        Ok(())
+    }
 
-        emit!(RefundEvent {
-            config: ctx.accounts.capital_call.config,
-            capital_call: ctx.accounts.capital_call.key(),
-            authority: ctx.accounts.authority.key(),
-            amount,
+    /// Pause or resume finalization of capital call, user actions aren't affected
+    pub fn set_finalization_paused(
+        ctx: Context<SetFinalizationPaused>,
+        paused: bool,
+    ) -> Result<()> {
+        let capital_call = &mut ctx.accounts.capital_call;
+        capital_call.finalization_paused = paused;
+
+        emit!(FinalizationPausedEvent {
+            program_version: PROGRAM_VERSION,
+            config: capital_call.config,
+            capital_call: capital_call.key(),
+            paused,
         });
 
         Ok(())
     }
 
-    /// Mint LP tokens if capital call raised
-    /// This instruction is permissionless and doesn't fail if capital call isn't fully raised or
-    /// still active.
-    pub fn mint_lp_tokens(ctx: Context<MintLpTokens>) -> Result<()> {
+    /// Stamp liquidity pool balance LP tokens are expected to be minted against. If capital
+    /// call has `max_valuation_age`, finalization snapshots only liquidity matching the stamp
+    /// within `max_valuation_age` after it.
+    pub fn update_valuation(ctx: Context<UpdateValuation>, token_liquidity: u64) -> Result<()> {
+        let clock = Clock::get().map_err::<error::Error, _>(Into::into)?;
+        let capital_call = &mut ctx.accounts.capital_call;
         require!(
-            ctx.accounts.lp_mint.mint_authority
-                == COption::Some(ctx.accounts.lp_mint_authority.key()),
-            CapitalCallError::InvalidLpMintAuthority
+            !capital_call.is_lp_minted,
+            CapitalCallError::LpTokenAlreadyMinted
+        );
+        capital_call.valuation_liquidity = token_liquidity;
+        capital_call.valuation_timestamp = clock.unix_timestamp as u64;
+
+        emit!(ValuationUpdatedEvent {
+            program_version: PROGRAM_VERSION,
+            config: capital_call.config,
+            capital_call: capital_call.key(),
+            token_liquidity,
+            valuation_timestamp: capital_call.valuation_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Cancel capital call before LP tokens are minted, not earlier than
+    /// `min_open_before_cancel` of config after start unless it's frozen
+    pub fn cancel(ctx: Context<CancelCapitalCall>) -> Result<()> {
+        require!(!ctx.accounts.config.killed, CapitalCallError::ProgramKilled);
+        let clock = Clock::get().map_err::<error::Error, _>(Into::into)?;
+        let now = clock.unix_timestamp as u64;
+        let capital_call = &mut ctx.accounts.capital_call;
+        require!(
+            !capital_call.is_lp_minted,
+            CapitalCallError::LpTokensAlreadyMinted
+        );
+        require!(
+            !capital_call.is_cancelled,
+            CapitalCallError::CapitalCallCancelled
+        );
+        require!(
+            capital_call.is_cancel_allowed(now, ctx.accounts.config.min_open_before_cancel),
+            CapitalCallError::CancelTooEarly
         );
+        // Pending deposits can't be confirmed in cancelled capital call
         require!(
-            ctx.accounts.lp_mint.supply > 0,
-            CapitalCallError::LpTokenSupplyNonZero
+            capital_call.pending_amount == 0,
+            CapitalCallError::DepositNotConfirmed
         );
 
-        // exit from instruction early if capital isn't raised or lp tokens already minted
-        if ctx.accounts.capital_call.capacity != ctx.accounts.capital_call.allocated
-            || ctx.accounts.capital_call.is_lp_minted
-        {
-            return Ok(());
-        }
+        capital_call.is_cancelled = true;
 
-        ctx.accounts.capital_call.lp_supply = ctx.accounts.lp_mint.supply;
-        ctx.accounts.capital_call.token_liquidity = ctx.accounts.liquidity_pool.amount;
+        emit!(CapitalCallCancelledEvent {
+            program_version: PROGRAM_VERSION,
+            config: capital_call.config,
+            capital_call: capital_call.key(),
+        });
 
-        let minted = ctx
-            .accounts
-            .capital_call
-            .to_lp_token(ctx.accounts.capital_call.capacity)?;
+        Ok(())
+    }
 
-        let config_key = ctx.accounts.config.key();
-        let seeds = [
-            SEED_LP_MINT_AUTHORITY.as_ref(),
-            config_key.as_ref(),
-            &[ctx.accounts.config.bump],
-        ];
+    /// Cancel capital call if it isn't cancelled yet and refund depositors to their associated
+    /// token accounts, vouchers are closed.
+    /// Each depositor is the next three of `remaining_accounts`: voucher, associated token
+    /// account of depositor for mint of vault and depositor receiving voucher rent. At most
+    /// `MAX_REFUND_DEPOSITORS` fit compute budget, larger sets are refunded in batches by
+    /// repeating the instruction. Matched and secondary deposits, credit tokens and tokens in
+    /// yield program aren't supported, those are refunded with `refund`.
+    pub fn cancel_and_refund<'info>(
+        ctx: Context<'_, '_, '_, 'info, CancelAndRefund<'info>>,
+    ) -> Result<()> {
+        require!(!ctx.accounts.config.killed, CapitalCallError::ProgramKilled);
+        let capital_call = &ctx.accounts.capital_call;
+        require!(!capital_call.frozen, CapitalCallError::CapitalCallFrozen);
+        require!(
+            !capital_call.is_lp_minted,
+            CapitalCallError::LpTokensAlreadyMinted
+        );
+        require!(
+            capital_call.credit_token_mint == Pubkey::default(),
+            CapitalCallError::InvalidCreditToken
+        );
+        require!(
+            capital_call.yield_principal == 0,
+            CapitalCallError::InvalidYieldProgram
+        );
+        require!(
+            ctx.remaining_accounts.len() % 3 == 0
+                && ctx.remaining_accounts.len() / 3 <= MAX_REFUND_DEPOSITORS,
+            CapitalCallError::InvalidDistribution
+        );
 
-        token::mint_to(
-            CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
-                MintTo {
-                    mint: ctx.accounts.lp_mint.to_account_info(),
-                    to: ctx.accounts.lp_token_pool.to_account_info(),
-                    authority: ctx.accounts.lp_mint_authority.to_account_info(),
-                },
-                &[&seeds],
-            ),
-            minted,
-        )?;
+        if !capital_call.is_cancelled {
+            let clock = Clock::get().map_err::<error::Error, _>(Into::into)?;
+            require!(
+                capital_call.is_cancel_allowed(
+                    clock.unix_timestamp as u64,
+                    ctx.accounts.config.min_open_before_cancel
+                ),
+                CapitalCallError::CancelTooEarly
+            );
+            require!(
+                capital_call.pending_amount == 0,
+                CapitalCallError::DepositNotConfirmed
+            );
+            ctx.accounts.capital_call.is_cancelled = true;
+
+            emit!(CapitalCallCancelledEvent {
+                program_version: PROGRAM_VERSION,
+                config: ctx.accounts.capital_call.config,
+                capital_call: ctx.accounts.capital_call.key(),
+            });
+        }
 
-        let start_time = ctx.accounts.capital_call.start_time.to_le_bytes();
-        let capacity_bytes = ctx.accounts.capital_call.capacity.to_le_bytes();
+        let signer_seeds = ctx.accounts.capital_call.signer_seeds();
+        let seeds = signer_seeds.as_slices();
 
-        let seeds = [
-            SEED_CAPITAL_CALL.as_ref(),
-            config_key.as_ref(),
-            start_time.as_ref(),
-            capacity_bytes.as_ref(),
-            &[ctx.accounts.capital_call.bump],
-        ];
+        for depositor_accounts in ctx.remaining_accounts.chunks(3) {
+            let voucher = Account::<Voucher>::try_from(&depositor_accounts[0])?;
+            let destination = Account::<TokenAccount>::try_from(&depositor_accounts[1])?;
+            let depositor = &depositor_accounts[2];
+            require!(
+                voucher.capital_call == ctx.accounts.capital_call.key()
+                    && voucher.authority == depositor.key(),
+                CapitalCallError::InvalidVoucher
+            );
+            require!(
+                destination.key()
+                    == get_associated_token_address(&voucher.authority, &ctx.accounts.vault.mint),
+                CapitalCallError::InvalidDestination
+            );
+            require!(
+                !ctx.accounts.capital_call.refund_to_source
+                    || destination.key() == voucher.source_snapshot,
+                CapitalCallError::RefundMustGoToSource
+            );
+            require!(
+                !ctx.accounts.capital_call.refund_to_source_owner
+                    || destination.owner == voucher.source_owner,
+                CapitalCallError::RefundMustGoToSourceOwner
+            );
+            require!(
+                voucher.pending_amount == 0,
+                CapitalCallError::DepositNotConfirmed
+            );
+            require!(
+                voucher.matched == 0 && !voucher.secondary,
+                CapitalCallError::ManualRefundRequired
+            );
 
-        let capital = ctx.accounts.capital_call.capacity;
-        token::transfer(
-            CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
-                Transfer {
-                    from: ctx.accounts.vault.to_account_info(),
-                    to: ctx.accounts.liquidity_pool.to_account_info(),
-                    authority: ctx.accounts.capital_call.to_account_info(),
-                },
-                &[&seeds],
-            ),
-            capital,
-        )?;
+            let amount = voucher.amount;
+            let refunded = ctx.accounts.capital_call.refund_after_haircut(amount)?;
+            token::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.vault.to_account_info(),
+                        to: destination.to_account_info(),
+                        authority: ctx.accounts.capital_call.to_account_info(),
+                    },
+                    &[&seeds],
+                ),
+                refunded,
+            )?;
+            let shortfall = amount - refunded;
+
+            ctx.accounts.capital_call.redeemed += amount;
+            ctx.accounts.capital_call.refund_shortfall += shortfall;
+            ctx.accounts.capital_call.bonus_allocated -=
+                ctx.accounts.capital_call.bonus(amount)?;
+            ctx.accounts.config.total_allocated = ctx
+                .accounts
+                .config
+                .total_allocated
+                .checked_sub(amount)
+                .ok_or_else(|| error!(CapitalCallError::CalculationError))?;
+            voucher.close(depositor.clone())?;
+
+            emit!(RefundEvent {
+                program_version: PROGRAM_VERSION,
+                config: ctx.accounts.capital_call.config,
+                capital_call: ctx.accounts.capital_call.key(),
+                authority: depositor.key(),
+                amount,
+                shortfall,
+            });
+        }
 
-        ctx.accounts.capital_call.is_lp_minted = true;
+        Ok(())
+    }
 
-        emit!(LpTokensMintedEvent {
-            config: ctx.accounts.config.key(),
-            capital_call: ctx.accounts.capital_call.key(),
-            token_liquidity: ctx.accounts.capital_call.token_liquidity,
-            lp_supply: ctx.accounts.capital_call.lp_supply,
-            credit_outstanding: ctx.accounts.capital_call.credit_outstanding,
-            capital: ctx.accounts.capital_call.capacity,
-            minted
+    /// Set share of refunds of cancelled capital call retained in vault for creditors, allowed
+    /// only before the first refund. Retained tokens are swept with vault by `close_cancelled`.
+    pub fn set_refund_haircut(ctx: Context<SetRefundHaircut>, haircut_bps: u16) -> Result<()> {
+        require!(!ctx.accounts.config.killed, CapitalCallError::ProgramKilled);
+        let capital_call = &mut ctx.accounts.capital_call;
+        require!(
+            capital_call.is_cancelled,
+            CapitalCallError::CapitalCallNotCancelled
+        );
+        require!(
+            capital_call.redeemed == 0,
+            CapitalCallError::CapitalCallTermsLocked
+        );
+        require!(
+            capital_call.secondary_mint == Pubkey::default(),
+            CapitalCallError::SecondaryDepositsUnsupported
+        );
+        require!(
+            haircut_bps as u64 <= BPS_DENOMINATOR,
+            CapitalCallError::InvalidBps
+        );
+
+        capital_call.refund_haircut_bps = haircut_bps;
+
+        emit!(RefundHaircutSetEvent {
+            program_version: PROGRAM_VERSION,
+            config: capital_call.config,
+            capital_call: capital_call.key(),
+            haircut_bps,
         });
 
         Ok(())
     }
 
-    pub fn claim(ctx: Context<Claim>) -> Result<()> {
-        let capital_call = &ctx.accounts.capital_call;
+    /// Move end time of active capital call closer, e.g. if it's already fully funded
+    pub fn shorten_duration(ctx: Context<ShortenDuration>, new_end_time: u64) -> Result<()> {
+        let clock = Clock::get().map_err::<error::Error, _>(Into::into)?;
+        let now = clock.unix_timestamp as u64;
+        let capital_call = &mut ctx.accounts.capital_call;
+
         require!(
-            capital_call.is_lp_minted,
-            CapitalCallError::LpTokenNotMinted
+            now < capital_call.end_time,
+            CapitalCallError::CapitalCallEnded
+        );
+        require!(
+            !capital_call.is_lp_minted,
+            CapitalCallError::LpTokensAlreadyMinted
+        );
+        require!(new_end_time >= now, CapitalCallError::EndTimeMustBeInFuture);
+        require!(
+            new_end_time > capital_call.start_time,
+            CapitalCallError::EndTimeMustBeAfterStartTime
+        );
+        require!(
+            new_end_time < capital_call.end_time,
+            CapitalCallError::EndTimeMustBeShortened
         );
 
-        let config_key = capital_call.config;
-        let start_time = capital_call.start_time.to_le_bytes();
-        let capacity = capital_call.capacity.to_le_bytes();
+        let old_end_time = capital_call.end_time;
+        capital_call.end_time = new_end_time;
 
-        let seeds = [
-            SEED_CAPITAL_CALL.as_ref(),
-            config_key.as_ref(),
-            start_time.as_ref(),
-            capacity.as_ref(),
-            &[capital_call.bump],
-        ];
+        emit!(EndTimeShortenedEvent {
+            program_version: PROGRAM_VERSION,
+            config: capital_call.config,
+            capital_call: capital_call.key(),
+            old_end_time,
+            new_end_time,
+        });
 
-        let amount = ctx.accounts.voucher.amount;
-        let lp_amount = capital_call.to_lp_token(amount)?;
+        Ok(())
+    }
 
-        let cpi_ctx: CpiContext<_> = ctx.accounts.into();
-        token::transfer(cpi_ctx.with_signer(&[&seeds]), lp_amount)?;
+    /// Push end time of nearly funded capital call out once, anyone can crank
+    pub fn try_auto_extend(ctx: Context<TryAutoExtend>) -> Result<()> {
+        require!(!ctx.accounts.config.killed, CapitalCallError::ProgramKilled);
+        let clock = Clock::get().map_err::<error::Error, _>(Into::into)?;
+        let now = clock.unix_timestamp as u64;
+        let capital_call = &mut ctx.accounts.capital_call;
 
-        ctx.accounts.capital_call.redeemed += amount;
+        require!(!capital_call.frozen, CapitalCallError::CapitalCallFrozen);
+        require!(
+            now >= capital_call.end_time,
+            CapitalCallError::CapitalCallNotEnded
+        );
+        let min_fill = u64::try_from(
+            capital_call.capacity as u128 * ctx.accounts.config.min_fill_to_extend_bps as u128
+                / BPS_DENOMINATOR as u128,
+        )
+        .map_err(|_| error!(CapitalCallError::CalculationError))?;
+        require!(
+            capital_call.allocated >= min_fill,
+            CapitalCallError::InsufficientFillToExtend
+        );
+        require!(
+            capital_call.is_auto_extendable()?,
+            CapitalCallError::AutoExtendNotAllowed
+        );
 
-        emit!(ClaimEvent {
-            config: ctx.accounts.capital_call.config,
-            capital_call: ctx.accounts.capital_call.key(),
-            authority: ctx.accounts.authority.key(),
-            amount,
-            lp_amount
+        let old_end_time = capital_call.end_time;
+        let new_end_time = old_end_time
+            .checked_add(capital_call.auto_extend_duration)
+            .ok_or_else(|| error!(CapitalCallError::CalculationError))?;
+        capital_call.end_time = new_end_time;
+        capital_call.auto_extended = true;
+
+        emit!(EndTimeExtendedEvent {
+            program_version: PROGRAM_VERSION,
+            config: capital_call.config,
+            capital_call: capital_call.key(),
+            old_end_time,
+            new_end_time,
         });
 
         Ok(())
     }
 
-    /// Close capital call and related accounts
-    pub fn close(ctx: Context<CloseCapitalCall>) -> Result<()> {
+    /// Deposit tokens to capital call.
+    /// Accounts of enabled features are passed in `remaining_accounts` in order of
+    /// `DepositAccounts`, followed by yield strategy accounts if capital call has
+    /// `yield_program`.
+    /// Frozen `source` is rejected, as is `source` with active delegate if config has
+    /// `reject_delegated_source`.
+    /// `memo` is opaque to the program, it's stored on voucher and echoed in DepositEvent.
+    pub fn deposit<'info>(
+        ctx: Context<'_, '_, '_, 'info, Deposit<'info>>,
+        amount: u64,
+        referrer: Option<Pubkey>,
+        memo: [u8; 32],
+    ) -> Result<()> {
+        require!(!ctx.accounts.config.killed, CapitalCallError::ProgramKilled);
+        require!(
+            !ctx.accounts.capital_call.frozen,
+            CapitalCallError::CapitalCallFrozen
+        );
+        require!(
+            !ctx.accounts.capital_call.is_cancelled,
+            CapitalCallError::CapitalCallCancelled
+        );
+        require!(
+            !ctx.accounts.capital_call.retained,
+            CapitalCallError::CapitalCallRetained
+        );
+        // Voucher is created with init_if_needed, so a repeated deposit lands here.
+        // Voucher emptied with withdraw can be reused.
+        require!(
+            ctx.accounts.voucher.amount == 0 && ctx.accounts.voucher.pending_amount == 0,
+            CapitalCallError::VoucherAlreadyExists
+        );
+        check_deposit_source(
+            &ctx.accounts.source,
+            ctx.accounts.config.reject_delegated_source,
+        )?;
+
         let clock = Clock::get().map_err::<error::Error, _>(Into::into)?;
         let now = clock.unix_timestamp as u64;
         let capital_call = &ctx.accounts.capital_call;
 
-        if !capital_call.is_lp_minted {
-            if now > capital_call.end_time {
-                require!(
-                    capital_call.allocated == capital_call.redeemed,
-                    CapitalCallError::CapitalCallHasToBeFullyRefunded
-                );
-            }
-        } else {
+        let accounts = split_deposit_accounts(
+            &ctx.accounts.config,
+            capital_call,
+            referrer.is_some(),
+            ctx.remaining_accounts,
+        );
+
+        // Top level instruction has to be of allowed program, which invokes deposit with CPI
+        if capital_call.allowed_depositor_program != Pubkey::default() {
+            let instructions_info = load_instructions_sysvar(accounts.instructions)?;
+            let current_ix = get_instruction_relative(0, instructions_info)?;
             require!(
-                capital_call.allocated == capital_call.redeemed,
-                CapitalCallError::LpTokensHasToBeFullyDistributed
+                current_ix.program_id == capital_call.allowed_depositor_program,
+                CapitalCallError::UnauthorizedDepositorProgram
             );
         }
 
-        // Someone can transfer tokens directly to vault
-        let config_key = capital_call.config;
-        let start_time = capital_call.start_time.to_le_bytes();
-        let capacity = capital_call.capacity.to_le_bytes();
+        let bypass_whitelist = ctx.accounts.config.authority_bypass_whitelist
+            && ctx.accounts.authority.key() == ctx.accounts.config.authority;
+        let max_amount = if capital_call.gatekeeper != Pubkey::default() && !bypass_whitelist {
+            verify_gatekeeper_allowance(
+                capital_call,
+                &ctx.accounts.authority.key(),
+                load_instructions_sysvar(accounts.instructions)?,
+                now,
+            )?
+        } else {
+            u64::MAX
+        };
+        require!(
+            amount <= max_amount,
+            CapitalCallError::InvalidGatekeeperSignature
+        );
 
-        let seeds = [
-            SEED_CAPITAL_CALL.as_ref(),
-            config_key.as_ref(),
-            start_time.as_ref(),
-            capacity.as_ref(),
-            &[capital_call.bump],
-        ];
+        // Finalization would strand funds in missing or drained liquidity pool
+        if ctx.accounts.config.min_pool_balance > 0 {
+            let liquidity_pool =
+                load_liquidity_pool(&ctx.accounts.config, accounts.liquidity_pool)?;
+            require!(
+                liquidity_pool.mint == ctx.accounts.vault.mint
+                    && liquidity_pool.amount >= ctx.accounts.config.min_pool_balance,
+                CapitalCallError::LiquidityPoolUnhealthy
+            );
+        }
 
-        token::transfer(
-            CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
-                Transfer {
-                    from: ctx.accounts.vault.to_account_info(),
-                    to: ctx.accounts.destination.to_account_info(),
-                    authority: ctx.accounts.capital_call.to_account_info(),
-                },
-                &[&seeds],
-            ),
-            ctx.accounts.vault.amount,
-        )?;
+        require!(
+            now >= capital_call.start_time,
+            CapitalCallError::CapitalCallNotStarted
+        );
+        require!(
+            clock.slot >= capital_call.start_slot,
+            CapitalCallError::CapitalCallNotStarted
+        );
+        require!(
+            capital_call.refund_cooldown == 0
+                || ctx.accounts.voucher.last_refund_at == 0
+                || now >= ctx.accounts.voucher.last_refund_at + capital_call.refund_cooldown,
+            CapitalCallError::RefundCooldown
+        );
+        require!(
+            now < capital_call.end_time,
+            CapitalCallError::CapitalCallEnded
+        );
+        let remaining = capital_call.remaining_capacity()?;
+        require!(
+            remaining > 0,
+            CapitalCallError::CapitalCallAlreadyFullyFunded
+        );
+        require!(amount > 0, CapitalCallError::AmountNonZero);
 
-        token::close_account(CpiContext::new_with_signer(
-            ctx.accounts.token_program.to_account_info(),
-            CloseAccount {
-                account: ctx.accounts.vault.to_account_info(),
-                destination: ctx.accounts.receiver.to_account_info(),
-                authority: ctx.accounts.capital_call.to_account_info(),
-            },
-            &[&seeds],
-        ))?;
+        let amount = capital_call.clamp_amount(amount, remaining)?;
 
-        // burn leftover LP tokens
-        token::burn(
-            CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
-                Burn {
-                    mint: ctx.accounts.lp_mint.to_account_info(),
-                    from: ctx.accounts.lp_token_pool.to_account_info(),
-                    authority: ctx.accounts.capital_call.to_account_info(),
-                },
-                &[&seeds],
-            ),
-            ctx.accounts.lp_token_pool.amount,
-        )?;
+        if capital_call.stake_mint != Pubkey::default() {
+            let stake_info = accounts
+                .stake
+                .first()
+                .ok_or_else(|| error!(CapitalCallError::InvalidStakeAccount))?;
+            let stake = Account::<TokenAccount>::try_from(stake_info)?;
+            require!(
+                stake.mint == capital_call.stake_mint
+                    && stake.owner == ctx.accounts.authority.key(),
+                CapitalCallError::InvalidStakeAccount
+            );
+            require!(
+                amount <= stake.amount.saturating_mul(capital_call.stake_cap_multiplier),
+                CapitalCallError::StakeCapExceeded
+            );
+        }
 
-        token::close_account(CpiContext::new_with_signer(
-            ctx.accounts.token_program.to_account_info(),
-            CloseAccount {
-                account: ctx.accounts.lp_token_pool.to_account_info(),
-                destination: ctx.accounts.receiver.to_account_info(),
-                authority: ctx.accounts.capital_call.to_account_info(),
+        let tier = if ctx.accounts.config.tier_oracle != Pubkey::default() {
+            let tier_info = accounts
+                .tier
+                .first()
+                .ok_or_else(|| error!(CapitalCallError::InvalidTierRecord))?;
+            load_oracle_tier(
+                &ctx.accounts.config,
+                &ctx.accounts.authority.key(),
+                tier_info,
+            )?
+        } else {
+            0
+        };
+
+        let match_reserve = if capital_call.is_match_enabled() {
+            Some(load_match_reserve(capital_call, accounts.match_reserve)?)
+        } else {
+            None
+        };
+        let matched = match &match_reserve {
+            Some(reserve) if reserve.delegate == COption::Some(capital_call.key()) => {
+                let matched = u64::try_from(
+                    amount as u128 * capital_call.match_bps as u128 / BPS_DENOMINATOR as u128,
+                )
+                .map_err(|_| error!(CapitalCallError::CalculationError))?;
+                matched
+                    .min(reserve.amount)
+                    .min(reserve.delegated_amount)
+                    .min(remaining - amount)
+            }
+            _ => 0,
+        };
+
+        let signer_seeds = capital_call.signer_seeds();
+        let seeds = signer_seeds.as_slices();
+
+        let cpi_ctx: CpiContext<_> = ctx.accounts.into();
+        token::transfer(cpi_ctx.with_signer(&[&seeds]), amount)?;
+
+        if let Some(reserve) = match_reserve.filter(|_| matched > 0) {
+            token::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: reserve.to_account_info(),
+                        to: ctx.accounts.vault.to_account_info(),
+                        authority: ctx.accounts.capital_call.to_account_info(),
+                    },
+                    &[&seeds],
+                ),
+                matched,
+            )?;
+        }
+        // Voucher is credited with matched tokens as well
+        let amount = amount + matched;
+
+        // Pending deposit is allocated by confirm_deposit
+        let pending = ctx.accounts.capital_call.confirmation_slots > 0;
+        if pending {
+            ctx.accounts.capital_call.pending_amount += amount;
+        } else {
+            ctx.accounts.capital_call.allocated += amount;
+            ctx.accounts.capital_call.bonus_allocated += ctx.accounts.capital_call.bonus(amount)?;
+            ctx.accounts.config.total_allocated = ctx
+                .accounts
+                .config
+                .total_allocated
+                .checked_add(amount)
+                .ok_or_else(|| error!(CapitalCallError::CalculationError))?;
+        }
+
+        let (_, yield_accounts) =
+            split_yield_accounts(&ctx.accounts.capital_call, ctx.remaining_accounts)?;
+        if !yield_accounts.is_empty() {
+            deposit_yield(
+                &mut ctx.accounts.capital_call,
+                &ctx.accounts.vault,
+                &ctx.accounts.token_program,
+                yield_accounts,
+                amount,
+            )?;
+        }
+
+        // Detect tokens injected into vault with direct transfer if capital call is strict,
+        // otherwise surplus is swept at finalization
+        ctx.accounts.vault.reload()?;
+        let expected_vault_balance = ctx.accounts.capital_call.expected_vault_balance()?;
+        require!(
+            ctx.accounts.vault.amount == expected_vault_balance
+                || !ctx.accounts.capital_call.strict_vault_balance
+                    && ctx.accounts.vault.amount > expected_vault_balance,
+            CapitalCallError::VaultBalanceMismatch
+        );
+
+        let referrer = match referrer {
+            Some(referrer) => {
+                require!(
+                    referrer != ctx.accounts.authority.key(),
+                    CapitalCallError::InvalidReferral
+                );
+                let referral_info = accounts
+                    .referral
+                    .first()
+                    .ok_or_else(|| error!(CapitalCallError::InvalidReferral))?;
+                let referral = Account::<Referral>::try_from(referral_info)?;
+                require!(
+                    referral.capital_call == ctx.accounts.capital_call.key()
+                        && referral.referrer == referrer,
+                    CapitalCallError::InvalidReferral
+                );
+                referrer
+            }
+            None => Pubkey::default(),
+        };
+
+        if ctx.accounts.capital_call.credit_token_mint != Pubkey::default() {
+            let (credit_mint, credit_token) = load_credit_accounts(
+                &ctx.accounts.capital_call,
+                &ctx.accounts.authority.key(),
+                accounts.credit,
+            )?;
+            require!(
+                credit_mint.mint_authority == COption::Some(ctx.accounts.capital_call.key()),
+                CapitalCallError::InvalidCreditToken
+            );
+
+            token::mint_to(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    MintTo {
+                        mint: credit_mint.to_account_info(),
+                        to: credit_token.to_account_info(),
+                        authority: ctx.accounts.capital_call.to_account_info(),
+                    },
+                    &[&seeds],
+                ),
+                amount,
+            )?;
+        }
+
+        if ctx.accounts.config.participation_mint != Pubkey::default() {
+            mint_participation_badge(
+                &ctx.accounts.config,
+                &ctx.accounts.authority.key(),
+                &ctx.accounts.token_program,
+                accounts.participation,
+            )?;
+        }
+
+        let voucher = &mut ctx.accounts.voucher;
+        voucher.capital_call = ctx.accounts.capital_call.key();
+        voucher.authority = ctx.accounts.authority.key();
+        if pending {
+            voucher.amount = 0;
+            voucher.pending_amount = amount;
+            voucher.pending_since_slot = clock.slot;
+        } else {
+            voucher.amount = amount;
+        }
+        voucher.referrer = referrer;
+        voucher.source_snapshot = ctx.accounts.source.key();
+        voucher.source_owner = ctx.accounts.source.owner;
+        ctx.accounts.capital_call.deposit_count += 1;
+        voucher.matched = matched;
+        voucher.secondary = false;
+        voucher.settled_via = SettlementKind::Unsettled;
+        voucher.memo = memo;
+        voucher.tier = tier;
+        voucher.bump = *ctx
+            .bumps
+            .get("voucher")
+            .ok_or_else(|| error!(CapitalCallError::BumpSeedNotInHashMap))?;
+
+        emit!(DepositEvent {
+            program_version: PROGRAM_VERSION,
+            config: ctx.accounts.capital_call.config,
+            capital_call: ctx.accounts.capital_call.key(),
+            authority: ctx.accounts.authority.key(),
+            amount,
+            matched,
+            memo,
+        });
+
+        // Emit progress only when fill crosses next step
+        let fill_bps = ctx.accounts.capital_call.fill_bps()?;
+        let step = ctx.accounts.capital_call.progress_event_step_bps as u64;
+        let last_emitted_bps = ctx.accounts.capital_call.last_emitted_bps as u64;
+        if step == 0 || fill_bps / step > last_emitted_bps / step {
+            ctx.accounts.capital_call.last_emitted_bps = fill_bps as u16;
+            emit!(FundingProgressEvent {
+                program_version: PROGRAM_VERSION,
+                config: ctx.accounts.capital_call.config,
+                capital_call: ctx.accounts.capital_call.key(),
+                allocated: ctx.accounts.capital_call.allocated,
+                capacity: ctx.accounts.capital_call.capacity,
+                fill_bps,
+            });
+        }
+
+        let capital_call_key = ctx.accounts.capital_call.key();
+        if let Some(event) = ctx.accounts.capital_call.mark_filled(capital_call_key, now) {
+            emit!(event);
+        }
+
+        Ok(())
+    }
+
+    /// Accept `secondary_mint` at par with mint of vault, allowed only until first deposit and
+    /// only once
+    pub fn init_secondary_vault(ctx: Context<InitSecondaryVault>) -> Result<()> {
+        let capital_call = &mut ctx.accounts.capital_call;
+        require!(
+            capital_call.allocated == 0
+                && capital_call.pending_amount == 0
+                && !capital_call.is_lp_minted,
+            CapitalCallError::CapitalCallTermsLocked
+        );
+        require!(
+            capital_call.secondary_mint == Pubkey::default(),
+            CapitalCallError::SecondaryMintAlreadySet
+        );
+        require!(
+            ctx.accounts.secondary_mint.decimals == ctx.accounts.mint.decimals,
+            CapitalCallError::SecondaryMintDecimalsMismatch
+        );
+
+        capital_call.secondary_mint = ctx.accounts.secondary_mint.key();
+        capital_call.secondary_vault = ctx.accounts.secondary_vault.key();
+        capital_call.secondary_liquidity_pool = ctx.accounts.secondary_liquidity_pool.key();
+        capital_call.secondary_vault_bump = *ctx
+            .bumps
+            .get("secondary_vault")
+            .ok_or_else(|| error!(CapitalCallError::BumpSeedNotInHashMap))?;
+
+        Ok(())
+    }
+
+    /// Deposit secondary mint tokens to secondary vault, voucher is credited at par.
+    /// Deposits gated by gatekeeper, depositor program or stake, and capital calls with credit
+    /// tokens or pending deposits accept only mint of vault.
+    pub fn deposit_secondary(
+        ctx: Context<DepositSecondary>,
+        amount: u64,
+        memo: [u8; 32],
+    ) -> Result<()> {
+        require!(!ctx.accounts.config.killed, CapitalCallError::ProgramKilled);
+        require!(
+            !ctx.accounts.capital_call.frozen,
+            CapitalCallError::CapitalCallFrozen
+        );
+        require!(
+            !ctx.accounts.capital_call.is_cancelled,
+            CapitalCallError::CapitalCallCancelled
+        );
+        require!(
+            !ctx.accounts.capital_call.retained,
+            CapitalCallError::CapitalCallRetained
+        );
+        require!(
+            ctx.accounts.voucher.amount == 0,
+            CapitalCallError::VoucherAlreadyExists
+        );
+
+        let clock = Clock::get().map_err::<error::Error, _>(Into::into)?;
+        let now = clock.unix_timestamp as u64;
+        let capital_call = &ctx.accounts.capital_call;
+
+        require!(
+            capital_call.gatekeeper == Pubkey::default()
+                && capital_call.allowed_depositor_program == Pubkey::default()
+                && capital_call.credit_token_mint == Pubkey::default()
+                && capital_call.stake_mint == Pubkey::default()
+                && capital_call.confirmation_slots == 0,
+            CapitalCallError::SecondaryDepositsUnsupported
+        );
+        require!(
+            now >= capital_call.start_time && clock.slot >= capital_call.start_slot,
+            CapitalCallError::CapitalCallNotStarted
+        );
+        require!(
+            capital_call.refund_cooldown == 0
+                || ctx.accounts.voucher.last_refund_at == 0
+                || now >= ctx.accounts.voucher.last_refund_at + capital_call.refund_cooldown,
+            CapitalCallError::RefundCooldown
+        );
+        require!(
+            now < capital_call.end_time,
+            CapitalCallError::CapitalCallEnded
+        );
+        let remaining = capital_call.remaining_capacity()?;
+        require!(
+            remaining > 0,
+            CapitalCallError::CapitalCallAlreadyFullyFunded
+        );
+        require!(amount > 0, CapitalCallError::AmountNonZero);
+
+        let amount = capital_call.clamp_amount(amount, remaining)?;
+
+        token::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.source.to_account_info(),
+                    to: ctx.accounts.secondary_vault.to_account_info(),
+                    authority: ctx.accounts.authority.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+
+        ctx.accounts.capital_call.allocated += amount;
+        ctx.accounts.capital_call.bonus_allocated += ctx.accounts.capital_call.bonus(amount)?;
+        ctx.accounts.capital_call.secondary_allocated += amount;
+        ctx.accounts.config.total_allocated = ctx
+            .accounts
+            .config
+            .total_allocated
+            .checked_add(amount)
+            .ok_or_else(|| error!(CapitalCallError::CalculationError))?;
+
+        let voucher = &mut ctx.accounts.voucher;
+        voucher.capital_call = ctx.accounts.capital_call.key();
+        voucher.authority = ctx.accounts.authority.key();
+        voucher.amount = amount;
+        voucher.referrer = Pubkey::default();
+        voucher.source_snapshot = ctx.accounts.source.key();
+        voucher.source_owner = ctx.accounts.source.owner;
+        ctx.accounts.capital_call.deposit_count += 1;
+        voucher.matched = 0;
+        voucher.secondary = true;
+        voucher.settled_via = SettlementKind::Unsettled;
+        voucher.memo = memo;
+        voucher.bump = *ctx
+            .bumps
+            .get("voucher")
+            .ok_or_else(|| error!(CapitalCallError::BumpSeedNotInHashMap))?;
+
+        emit!(DepositEvent {
+            program_version: PROGRAM_VERSION,
+            config: ctx.accounts.capital_call.config,
+            capital_call: ctx.accounts.capital_call.key(),
+            authority: ctx.accounts.authority.key(),
+            amount,
+            matched: 0,
+            memo,
+        });
+
+        let capital_call_key = ctx.accounts.capital_call.key();
+        if let Some(event) = ctx.accounts.capital_call.mark_filled(capital_call_key, now) {
+            emit!(event);
+        }
+
+        Ok(())
+    }
+
+    /// Deposit native SOL to capital call raising wrapped SOL.
+    /// `source` is an empty temporary wrapped SOL account of depositor, lamports are wrapped
+    /// into it before deposit and it is closed to depositor afterwards with any amount not
+    /// deposited.
+    pub fn deposit_sol<'info>(
+        ctx: Context<'_, '_, '_, 'info, Deposit<'info>>,
+        amount: u64,
+        referrer: Option<Pubkey>,
+        memo: [u8; 32],
+    ) -> Result<()> {
+        // Source is closed after deposit, so it can't receive refunds
+        require!(
+            !ctx.accounts.capital_call.refund_to_source,
+            CapitalCallError::RefundToClosedSource
+        );
+        require!(
+            ctx.accounts.vault.is_native() && ctx.accounts.source.is_native(),
+            CapitalCallError::MintNotNative
+        );
+        // Wrapped balance held by depositor before, e.g. in their ATA, would be unwrapped
+        require!(
+            ctx.accounts.source.amount == 0,
+            CapitalCallError::WrappedSourceNotEmpty
+        );
+
+        system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.authority.to_account_info(),
+                    to: ctx.accounts.source.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+        token::sync_native(CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            SyncNative {
+                account: ctx.accounts.source.to_account_info(),
+            },
+        ))?;
+
+        let token_program = ctx.accounts.token_program.to_account_info();
+        let source = ctx.accounts.source.to_account_info();
+        let authority = ctx.accounts.authority.to_account_info();
+
+        deposit(ctx, amount, referrer, memo)?;
+
+        token::close_account(CpiContext::new(
+            token_program,
+            CloseAccount {
+                account: source,
+                destination: authority.clone(),
+                authority,
             },
-            &[&seeds],
         ))?;
 
         Ok(())
     }
-}
 
-#[derive(Accounts)]
-pub struct Initialize<'info> {
-    #[account(
-        init,
-        payer = payer,
-        space = Config::SPACE,
-    )]
-    pub config: Account<'info, Config>,
+    /// Allocate pending deposit of voucher after confirmation slots, anyone can crank
+    pub fn confirm_deposit(ctx: Context<ConfirmDeposit>) -> Result<()> {
+        require!(!ctx.accounts.config.killed, CapitalCallError::ProgramKilled);
+        let clock = Clock::get().map_err::<error::Error, _>(Into::into)?;
+        let voucher = &mut ctx.accounts.voucher;
+        let capital_call = &mut ctx.accounts.capital_call;
+
+        require!(
+            !capital_call.is_cancelled,
+            CapitalCallError::CapitalCallCancelled
+        );
+        require!(
+            !capital_call.is_lp_minted,
+            CapitalCallError::LpTokensAlreadyMinted
+        );
+
+        let amount = voucher.pending_amount;
+        require!(amount > 0, CapitalCallError::AmountNonZero);
+        require!(
+            clock.slot >= voucher.pending_since_slot + capital_call.confirmation_slots,
+            CapitalCallError::DepositNotConfirmed
+        );
+
+        voucher.amount = amount;
+        voucher.pending_amount = 0;
+        capital_call.pending_amount -= amount;
+        capital_call.allocated += amount;
+        capital_call.bonus_allocated += capital_call.bonus(amount)?;
+        ctx.accounts.config.total_allocated = ctx
+            .accounts
+            .config
+            .total_allocated
+            .checked_add(amount)
+            .ok_or_else(|| error!(CapitalCallError::CalculationError))?;
+
+        emit!(DepositConfirmedEvent {
+            program_version: PROGRAM_VERSION,
+            config: capital_call.config,
+            capital_call: capital_call.key(),
+            authority: voucher.authority,
+            amount,
+        });
+
+        let capital_call_key = capital_call.key();
+        let now = clock.unix_timestamp as u64;
+        if let Some(event) = capital_call.mark_filled(capital_call_key, now) {
+            emit!(event);
+        }
+
+        Ok(())
+    }
+
+    /// Refund tokens if capital is not raised, voucher is closed, so unlike `withdraw` refund
+    /// doesn't start refund cooldown.
+    /// If capital call has `credit_token_mint`, credit token mint and credit token account of
+    /// depositor have to be `remaining_accounts`, refunded amount of credit tokens is burned.
+    /// If voucher has matched tokens, match reserve follows in `remaining_accounts` to receive
+    /// them back.
+    /// If voucher is deposited in secondary mint, secondary vault has to be the first of
+    /// `remaining_accounts`.
+    /// If capital call has tokens in `yield_program`, they are withdrawn to vault first and
+    /// yield strategy accounts are the last of `remaining_accounts`.
+    /// Haircut of cancelled capital call reduces tokens refunded to depositor, matched tokens
+    /// are returned to match reserve in full.
+    pub fn refund<'info>(ctx: Context<'_, '_, '_, 'info, Refund<'info>>) -> Result<()> {
+        require!(!ctx.accounts.config.killed, CapitalCallError::ProgramKilled);
+        require!(
+            !ctx.accounts.capital_call.frozen,
+            CapitalCallError::CapitalCallFrozen
+        );
+        ctx.accounts.voucher.settle(SettlementKind::Refund)?;
+
+        let clock = Clock::get().map_err::<error::Error, _>(Into::into)?;
+        let now = clock.unix_timestamp as u64;
+        let capital_call = &ctx.accounts.capital_call;
+
+        // Cancelled capital call is refundable right away
+        if !capital_call.is_cancelled {
+            // Fully funded capital call can be refunded if it's not finalized before deadline
+            require!(
+                capital_call.capacity > capital_call.allocated
+                    || capital_call.is_finalize_overdue(now),
+                CapitalCallError::CapitalCallIsFullyFunded
+            );
+            require!(
+                now >= capital_call.end_time,
+                CapitalCallError::CapitalCallNotEnded
+            );
+            require!(
+                now >= capital_call.refund_available_at(),
+                CapitalCallError::RefundLockup
+            );
+        }
+        require!(
+            !capital_call.refund_to_source
+                || ctx.accounts.destination.key() == ctx.accounts.voucher.source_snapshot,
+            CapitalCallError::RefundMustGoToSource
+        );
+        require!(
+            !capital_call.refund_to_source_owner
+                || ctx.accounts.destination.owner == ctx.accounts.voucher.source_owner,
+            CapitalCallError::RefundMustGoToSourceOwner
+        );
+        require!(
+            ctx.accounts.voucher.pending_amount == 0,
+            CapitalCallError::DepositNotConfirmed
+        );
+
+        let signer_seeds = capital_call.signer_seeds();
+        let seeds = signer_seeds.as_slices();
+
+        let remaining_accounts = if ctx.accounts.capital_call.yield_principal > 0 {
+            let (accounts, yield_accounts) =
+                split_yield_accounts(&ctx.accounts.capital_call, ctx.remaining_accounts)?;
+            withdraw_yield(
+                &mut ctx.accounts.capital_call,
+                &mut ctx.accounts.vault,
+                &ctx.accounts.token_program,
+                yield_accounts,
+            )?;
+            accounts
+        } else {
+            ctx.remaining_accounts
+        };
+
+        let amount = ctx.accounts.voucher.amount;
+        burn_credit_tokens(
+            &ctx.accounts.capital_call,
+            &ctx.accounts.authority,
+            &ctx.accounts.token_program,
+            remaining_accounts,
+            amount,
+        )?;
+
+        let matched = ctx.accounts.voucher.matched;
+        return_matched_tokens(
+            &ctx.accounts.capital_call,
+            &ctx.accounts.vault,
+            &ctx.accounts.token_program,
+            remaining_accounts,
+            &seeds,
+            matched,
+        )?;
+
+        // Haircut isn't allowed with secondary mint
+        let shortfall = if ctx.accounts.voucher.secondary {
+            let secondary_vault =
+                load_secondary_vault(&ctx.accounts.capital_call, remaining_accounts)?;
+            token::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: secondary_vault.to_account_info(),
+                        to: ctx.accounts.destination.to_account_info(),
+                        authority: ctx.accounts.capital_call.to_account_info(),
+                    },
+                    &[&seeds],
+                ),
+                amount,
+            )?;
+            ctx.accounts.capital_call.secondary_allocated -= amount;
+            0
+        } else {
+            let refunded = ctx
+                .accounts
+                .capital_call
+                .refund_after_haircut(amount - matched)?;
+            let cpi_ctx: CpiContext<_> = ctx.accounts.into();
+            token::transfer(cpi_ctx.with_signer(&[&seeds]), refunded)?;
+            amount - matched - refunded
+        };
+
+        ctx.accounts.capital_call.redeemed += amount;
+        ctx.accounts.capital_call.refund_shortfall += shortfall;
+        ctx.accounts.capital_call.bonus_allocated -= ctx.accounts.capital_call.bonus(amount)?;
+        ctx.accounts.config.total_allocated = ctx
+            .accounts
+            .config
+            .total_allocated
+            .checked_sub(amount)
+            .ok_or_else(|| error!(CapitalCallError::CalculationError))?;
+
+        emit!(RefundEvent {
+            program_version: PROGRAM_VERSION,
+            config: ctx.accounts.capital_call.config,
+            capital_call: ctx.accounts.capital_call.key(),
+            authority: ctx.accounts.authority.key(),
+            amount,
+            shortfall,
+        });
+
+        Ok(())
+    }
+
+    /// Emit whether voucher can be refunded now and refund amount, doesn't transfer tokens
+    pub fn preview_refund(ctx: Context<PreviewRefund>) -> Result<()> {
+        let clock = Clock::get().map_err::<error::Error, _>(Into::into)?;
+        let now = clock.unix_timestamp as u64;
+
+        let eligible = ctx.accounts.capital_call.is_refundable(now);
+        let amount = if eligible {
+            ctx.accounts.voucher.amount
+        } else {
+            0
+        };
+
+        emit!(RefundPreviewEvent {
+            program_version: PROGRAM_VERSION,
+            config: ctx.accounts.capital_call.config,
+            capital_call: ctx.accounts.capital_call.key(),
+            authority: ctx.accounts.voucher.authority,
+            eligible,
+            amount,
+        });
+
+        Ok(())
+    }
+
+    /// Withdraw deposit while capital call is active.
+    /// Voucher is kept to track withdrawal time, it can be used for a new deposit.
+    /// Credit tokens are burned, secondary vault and yield strategy accounts are passed as in
+    /// `refund`.
+    pub fn withdraw<'info>(ctx: Context<'_, '_, '_, 'info, Withdraw<'info>>) -> Result<()> {
+        require!(!ctx.accounts.config.killed, CapitalCallError::ProgramKilled);
+        require!(
+            !ctx.accounts.capital_call.frozen,
+            CapitalCallError::CapitalCallFrozen
+        );
+
+        let clock = Clock::get().map_err::<error::Error, _>(Into::into)?;
+        let now = clock.unix_timestamp as u64;
+        let capital_call = &ctx.accounts.capital_call;
+
+        // Withdraw stays available during finalize cooldown after end time
+        require!(
+            now < capital_call.end_time || capital_call.is_in_finalize_cooldown(now),
+            CapitalCallError::CapitalCallEnded
+        );
+        require!(
+            !capital_call.is_lp_minted,
+            CapitalCallError::LpTokensAlreadyMinted
+        );
+
+        let amount = ctx.accounts.voucher.amount;
+        require!(amount > 0, CapitalCallError::AmountNonZero);
+        // Voucher emptied by withdraw starts unsettled again with the next deposit
+        ctx.accounts.voucher.settle(SettlementKind::Refund)?;
+
+        let signer_seeds = capital_call.signer_seeds();
+        let seeds = signer_seeds.as_slices();
+
+        let remaining_accounts = if ctx.accounts.capital_call.yield_principal > 0 {
+            let (accounts, yield_accounts) =
+                split_yield_accounts(&ctx.accounts.capital_call, ctx.remaining_accounts)?;
+            withdraw_yield(
+                &mut ctx.accounts.capital_call,
+                &mut ctx.accounts.vault,
+                &ctx.accounts.token_program,
+                yield_accounts,
+            )?;
+            accounts
+        } else {
+            ctx.remaining_accounts
+        };
+
+        burn_credit_tokens(
+            &ctx.accounts.capital_call,
+            &ctx.accounts.authority,
+            &ctx.accounts.token_program,
+            remaining_accounts,
+            amount,
+        )?;
+
+        let matched = ctx.accounts.voucher.matched;
+        return_matched_tokens(
+            &ctx.accounts.capital_call,
+            &ctx.accounts.vault,
+            &ctx.accounts.token_program,
+            remaining_accounts,
+            &seeds,
+            matched,
+        )?;
+
+        let secondary = ctx.accounts.voucher.secondary;
+        let vault = if secondary {
+            load_secondary_vault(&ctx.accounts.capital_call, remaining_accounts)?
+                .to_account_info()
+        } else {
+            ctx.accounts.vault.to_account_info()
+        };
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: vault,
+                    to: ctx.accounts.destination.to_account_info(),
+                    authority: ctx.accounts.capital_call.to_account_info(),
+                },
+                &[&seeds],
+            ),
+            amount - matched,
+        )?;
+
+        ctx.accounts.capital_call.allocated -= amount;
+        ctx.accounts.capital_call.bonus_allocated -= ctx.accounts.capital_call.bonus(amount)?;
+        if secondary {
+            ctx.accounts.capital_call.secondary_allocated -= amount;
+        }
+        ctx.accounts.config.total_allocated = ctx
+            .accounts
+            .config
+            .total_allocated
+            .checked_sub(amount)
+            .ok_or_else(|| error!(CapitalCallError::CalculationError))?;
+
+        ctx.accounts.voucher.amount = 0;
+        ctx.accounts.voucher.matched = 0;
+        ctx.accounts.voucher.last_refund_at = now;
+
+        emit!(WithdrawEvent {
+            program_version: PROGRAM_VERSION,
+            config: ctx.accounts.capital_call.config,
+            capital_call: ctx.accounts.capital_call.key(),
+            authority: ctx.accounts.authority.key(),
+            amount,
+        });
+
+        Ok(())
+    }
+
+    /// Mint LP tokens if capital call raised
+    /// This instruction is permissionless and doesn't fail if capital call isn't fully raised or
+    /// still active.
+    /// Failure at any step reverts minting with the whole transaction, so retry either finalizes
+    /// capital call or no-ops if it's already finalized.
+    /// If capital call has `custodial_vault`, custodian co-signs the transfer from vault and is
+    /// the first of `remaining_accounts`.
+    /// If capital call charges fill fee, `fee_lp_account` of config follows and receives the fee
+    /// share of minted LP tokens.
+    /// If config has `surplus_treasury`, it follows and receives tokens transferred directly to
+    /// vault, so vault keeps only reserve.
+    /// If capital call has `use_oracle_conversion`, its `oracle` price account follows and
+    /// capital is valued in base units of liquidity pool by the price for LP tokens math.
+    /// If capital call has `secondary_mint`, secondary vault and secondary liquidity pool
+    /// follow, then finalize callback accounts.
+    /// If capital call has tokens in `yield_program`, they are withdrawn to vault with yield
+    /// and yield strategy accounts are the last of `remaining_accounts`.
+    /// Reserve share of capital in mint of vault stays in vault for `redeem`.
+    /// Capital calls with `finalize_chunk` are finalized by `finalize_chunked` instead.
+    pub fn mint_lp_tokens<'info>(
+        ctx: Context<'_, '_, '_, 'info, MintLpTokens<'info>>,
+    ) -> Result<()> {
+        require!(!ctx.accounts.config.killed, CapitalCallError::ProgramKilled);
+        require!(
+            ctx.accounts.capital_call.finalize_chunk == 0,
+            CapitalCallError::ChunkedFinalizeRequired
+        );
+        let clock = Clock::get().map_err::<error::Error, _>(Into::into)?;
+        ctx.accounts.capital_call.check_finalizable(
+            &ctx.accounts.config,
+            &ctx.accounts.lp_mint,
+            &ctx.accounts.lp_mint_authority.key(),
+            clock.unix_timestamp as u64,
+        )?;
+
+        // exit from instruction early if capital isn't raised or lp tokens already minted
+        if ctx.accounts.capital_call.capacity != ctx.accounts.capital_call.allocated
+            || ctx.accounts.capital_call.is_lp_minted
+        {
+            return Ok(());
+        }
+
+        let remaining_accounts = if ctx.accounts.capital_call.custodial_vault {
+            let custodian = ctx
+                .remaining_accounts
+                .first()
+                .ok_or_else(|| error!(CapitalCallError::InvalidCustodian))?;
+            require!(
+                custodian.key() == ctx.accounts.capital_call.custodian && custodian.is_signer,
+                CapitalCallError::InvalidCustodian
+            );
+            &ctx.remaining_accounts[1..]
+        } else {
+            ctx.remaining_accounts
+        };
+        let fill_fee_bps = ctx.accounts.capital_call.fill_fee()?;
+        let (fee_lp_account, remaining_accounts) = if fill_fee_bps > 0 {
+            let fee_lp_account = remaining_accounts
+                .first()
+                .ok_or_else(|| error!(CapitalCallError::InvalidFeeLpAccount))?;
+            require!(
+                ctx.accounts.config.fee_lp_account != Pubkey::default()
+                    && fee_lp_account.key() == ctx.accounts.config.fee_lp_account,
+                CapitalCallError::InvalidFeeLpAccount
+            );
+            (Some(fee_lp_account), &remaining_accounts[1..])
+        } else {
+            (None, remaining_accounts)
+        };
+        let (surplus_treasury, remaining_accounts) =
+            if ctx.accounts.config.surplus_treasury != Pubkey::default() {
+                let surplus_treasury = remaining_accounts
+                    .first()
+                    .ok_or_else(|| error!(CapitalCallError::InvalidSurplusTreasury))?;
+                require!(
+                    surplus_treasury.key() == ctx.accounts.config.surplus_treasury,
+                    CapitalCallError::InvalidSurplusTreasury
+                );
+                (Some(surplus_treasury), &remaining_accounts[1..])
+            } else {
+                (None, remaining_accounts)
+            };
+        let remaining_accounts = if ctx.accounts.capital_call.use_oracle_conversion {
+            let (oracle_info, remaining_accounts) = remaining_accounts
+                .split_first()
+                .ok_or_else(|| error!(CapitalCallError::InvalidOracle))?;
+            let price = load_oracle_price(&ctx.accounts.capital_call, oracle_info)?;
+            ctx.accounts.capital_call.oracle_rate = ctx
+                .accounts
+                .capital_call
+                .oracle_rate(&price, clock.unix_timestamp as u64)?;
+            remaining_accounts
+        } else {
+            remaining_accounts
+        };
+        let remaining_accounts = if ctx.accounts.capital_call.yield_principal > 0 {
+            let (accounts, yield_accounts) =
+                split_yield_accounts(&ctx.accounts.capital_call, remaining_accounts)?;
+            withdraw_yield(
+                &mut ctx.accounts.capital_call,
+                &mut ctx.accounts.vault,
+                &ctx.accounts.token_program,
+                yield_accounts,
+            )?;
+            accounts
+        } else {
+            remaining_accounts
+        };
+
+        ctx.accounts.capital_call.take_snapshot(
+            ctx.accounts.lp_mint.supply,
+            ctx.accounts.liquidity_pool.amount,
+            clock.unix_timestamp as u64,
+        )?;
+
+        let minted = ctx
+            .accounts
+            .capital_call
+            .lp_to_mint()?;
+        // Vouchers are scaled down by the same fee rounding down, so pool covers all claims
+        let fill_fee = u64::try_from(
+            minted as u128 * fill_fee_bps as u128 / BPS_DENOMINATOR as u128,
+        )
+        .map_err(|_| error!(CapitalCallError::CalculationError))?;
+
+        let config_key = ctx.accounts.config.key();
+        let seeds = [
+            SEED_LP_MINT_AUTHORITY.as_ref(),
+            config_key.as_ref(),
+            &[ctx.accounts.config.bump],
+        ];
+
+        token::mint_to(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                MintTo {
+                    mint: ctx.accounts.lp_mint.to_account_info(),
+                    to: ctx.accounts.lp_token_pool.to_account_info(),
+                    authority: ctx.accounts.lp_mint_authority.to_account_info(),
+                },
+                &[&seeds],
+            ),
+            minted - fill_fee,
+        )?;
+        if let Some(fee_lp_account) = fee_lp_account {
+            token::mint_to(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    MintTo {
+                        mint: ctx.accounts.lp_mint.to_account_info(),
+                        to: fee_lp_account.clone(),
+                        authority: ctx.accounts.lp_mint_authority.to_account_info(),
+                    },
+                    &[&seeds],
+                ),
+                fill_fee,
+            )?;
+        }
+
+        ctx.accounts.lp_mint.reload()?;
+        ctx.accounts
+            .capital_call
+            .check_lp_supply(ctx.accounts.lp_mint.supply, minted)?;
+
+        let signer_seeds = ctx.accounts.capital_call.signer_seeds();
+        let seeds = signer_seeds.as_slices();
+
+        // Vault balance over expected is transferred directly, yield earned stays in vault
+        if let Some(surplus_treasury) = surplus_treasury {
+            let surplus = ctx
+                .accounts
+                .vault
+                .amount
+                .saturating_sub(ctx.accounts.capital_call.expected_vault_balance()?);
+            if surplus > 0 {
+                token::transfer(
+                    CpiContext::new_with_signer(
+                        ctx.accounts.token_program.to_account_info(),
+                        Transfer {
+                            from: ctx.accounts.vault.to_account_info(),
+                            to: surplus_treasury.clone(),
+                            authority: ctx.accounts.capital_call.to_account_info(),
+                        },
+                        &[&seeds],
+                    ),
+                    surplus,
+                )?;
+
+                emit!(SurplusSweptEvent {
+                    program_version: PROGRAM_VERSION,
+                    config: config_key,
+                    capital_call: ctx.accounts.capital_call.key(),
+                    treasury: surplus_treasury.key(),
+                    amount: surplus,
+                });
+            }
+        }
+
+        let secondary_capital = ctx.accounts.capital_call.secondary_allocated;
+        let reserve = ctx.accounts.capital_call.finalize_reserve()?;
+        let amount_to_transfer = ctx.accounts.capital_call.capacity - secondary_capital - reserve;
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.vault.to_account_info(),
+                    to: ctx.accounts.liquidity_pool.to_account_info(),
+                    authority: ctx.accounts.capital_call.to_account_info(),
+                },
+                &[&seeds],
+            ),
+            amount_to_transfer,
+        )?;
+
+        let callback_accounts = if ctx.accounts.capital_call.secondary_mint != Pubkey::default() {
+            let secondary_vault =
+                load_secondary_vault(&ctx.accounts.capital_call, remaining_accounts)?;
+            let secondary_pool_info = remaining_accounts
+                .get(1)
+                .ok_or_else(|| error!(CapitalCallError::InvalidSecondaryVault))?;
+            require!(
+                secondary_pool_info.key() == ctx.accounts.capital_call.secondary_liquidity_pool,
+                CapitalCallError::InvalidSecondaryVault
+            );
+            token::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: secondary_vault.to_account_info(),
+                        to: secondary_pool_info.clone(),
+                        authority: ctx.accounts.capital_call.to_account_info(),
+                    },
+                    &[&seeds],
+                ),
+                secondary_capital,
+            )?;
+            &remaining_accounts[2..]
+        } else {
+            remaining_accounts
+        };
+
+        ctx.accounts.capital_call.is_lp_minted = true;
+        ctx.accounts.capital_call.lp_minted = minted - fill_fee;
+        ctx.accounts.capital_call.finalized_at = clock.unix_timestamp as u64;
+        ctx.accounts.capital_call.secondary_allocated = 0;
+        ctx.accounts.capital_call.vault_reserve = reserve;
+        ctx.accounts.capital_call.fill_fee_bps = fill_fee_bps;
+        ctx.accounts.capital_call.fill_fee_lp = fill_fee;
+
+        emit!(LpTokensMintedEvent {
+            program_version: PROGRAM_VERSION,
+            config: ctx.accounts.config.key(),
+            capital_call: ctx.accounts.capital_call.key(),
+            token_liquidity: ctx.accounts.capital_call.token_liquidity,
+            lp_supply: ctx.accounts.capital_call.lp_supply,
+            credit_outstanding: ctx.accounts.capital_call.credit_outstanding,
+            capital: ctx.accounts.capital_call.capacity,
+            minted,
+            fill_fee,
+        });
+
+        invoke_finalize_callback(
+            &ctx.accounts.config,
+            &ctx.accounts.capital_call,
+            callback_accounts,
+            minted,
+        )?;
+
+        Ok(())
+    }
+
+    /// Finalize capital call splitting raised capital between several liquidity pools.
+    /// Pools are the first `bps.len()` of `remaining_accounts`, LP tokens are minted against
+    /// combined liquidity of all pools.
+    pub fn finalize_split<'info>(
+        ctx: Context<'_, '_, '_, 'info, FinalizeSplit<'info>>,
+        bps: Vec<u16>,
+    ) -> Result<()> {
+        require!(!ctx.accounts.config.killed, CapitalCallError::ProgramKilled);
+        let clock = Clock::get().map_err::<error::Error, _>(Into::into)?;
+        ctx.accounts.capital_call.check_finalizable(
+            &ctx.accounts.config,
+            &ctx.accounts.lp_mint,
+            &ctx.accounts.lp_mint_authority.key(),
+            clock.unix_timestamp as u64,
+        )?;
+        require!(
+            ctx.accounts.capital_call.finalize_chunk == 0,
+            CapitalCallError::ChunkedFinalizeRequired
+        );
+        ctx.accounts
+            .capital_call
+            .check_plain_finalize(&ctx.accounts.config)?;
+        require!(
+            ctx.accounts.capital_call.capacity == ctx.accounts.capital_call.allocated,
+            CapitalCallError::CapitalCallNotFullyFunded
+        );
+        require!(
+            !ctx.accounts.capital_call.is_lp_minted,
+            CapitalCallError::LpTokensAlreadyMinted
+        );
+        require!(
+            !bps.is_empty() && bps.len() <= MAX_SPLIT_POOLS,
+            CapitalCallError::InvalidSplit
+        );
+        require!(
+            bps.iter().map(|share| *share as u64).sum::<u64>() == BPS_DENOMINATOR,
+            CapitalCallError::InvalidSplit
+        );
+        require!(
+            ctx.remaining_accounts.len() >= bps.len(),
+            CapitalCallError::InvalidSplit
+        );
+
+        let (pool_infos, callback_accounts) = ctx.remaining_accounts.split_at(bps.len());
+
+        let mint = MINT_PUBKEY.parse::<Pubkey>().unwrap();
+        let mut token_liquidity: u64 = 0;
+        for (i, pool_info) in pool_infos.iter().enumerate() {
+            require!(
+                pool_infos[..i].iter().all(|other| other.key() != pool_info.key()),
+                CapitalCallError::InvalidSplit
+            );
+            let pool = Account::<TokenAccount>::try_from(pool_info)?;
+            require!(pool.mint == mint, CapitalCallError::InvalidSplit);
+            token_liquidity = token_liquidity
+                .checked_add(pool.amount)
+                .ok_or_else(|| error!(CapitalCallError::CalculationError))?;
+        }
+
+        ctx.accounts.capital_call.take_snapshot(
+            ctx.accounts.lp_mint.supply,
+            token_liquidity,
+            clock.unix_timestamp as u64,
+        )?;
+
+        let minted = ctx
+            .accounts
+            .capital_call
+            .lp_to_mint()?;
+
+        let config_key = ctx.accounts.config.key();
+        let seeds = [
+            SEED_LP_MINT_AUTHORITY.as_ref(),
+            config_key.as_ref(),
+            &[ctx.accounts.config.bump],
+        ];
+
+        token::mint_to(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                MintTo {
+                    mint: ctx.accounts.lp_mint.to_account_info(),
+                    to: ctx.accounts.lp_token_pool.to_account_info(),
+                    authority: ctx.accounts.lp_mint_authority.to_account_info(),
+                },
+                &[&seeds],
+            ),
+            minted,
+        )?;
+
+        ctx.accounts.lp_mint.reload()?;
+        ctx.accounts
+            .capital_call
+            .check_lp_supply(ctx.accounts.lp_mint.supply, minted)?;
+
+        let signer_seeds = ctx.accounts.capital_call.signer_seeds();
+        let seeds = signer_seeds.as_slices();
+
+        let capital = ctx.accounts.capital_call.capacity;
+        let mut deployed: u64 = 0;
+        for (i, (pool_info, share)) in pool_infos.iter().zip(bps.iter()).enumerate() {
+            // Last pool receives rounding remainder
+            let amount = if i == bps.len() - 1 {
+                capital - deployed
+            } else {
+                u64::try_from(capital as u128 * *share as u128 / BPS_DENOMINATOR as u128)
+                    .map_err(|_| error!(CapitalCallError::CalculationError))?
+            };
+            deployed += amount;
+
+            token::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.vault.to_account_info(),
+                        to: pool_info.clone(),
+                        authority: ctx.accounts.capital_call.to_account_info(),
+                    },
+                    &[&seeds],
+                ),
+                amount,
+            )?;
+
+            emit!(CapitalDeployedEvent {
+                program_version: PROGRAM_VERSION,
+                config: config_key,
+                capital_call: ctx.accounts.capital_call.key(),
+                liquidity_pool: pool_info.key(),
+                amount,
+            });
+        }
+
+        ctx.accounts.capital_call.is_lp_minted = true;
+        ctx.accounts.capital_call.lp_minted = minted;
+        ctx.accounts.capital_call.finalized_at = clock.unix_timestamp as u64;
+        ctx.accounts.capital_call.split_finalized = true;
+
+        emit!(LpTokensMintedEvent {
+            program_version: PROGRAM_VERSION,
+            config: config_key,
+            capital_call: ctx.accounts.capital_call.key(),
+            token_liquidity: ctx.accounts.capital_call.token_liquidity,
+            lp_supply: ctx.accounts.capital_call.lp_supply,
+            credit_outstanding: ctx.accounts.capital_call.credit_outstanding,
+            capital,
+            minted,
+            fill_fee: 0,
+        });
+
+        invoke_finalize_callback(
+            &ctx.accounts.config,
+            &ctx.accounts.capital_call,
+            callback_accounts,
+            minted,
+        )?;
+
+        Ok(())
+    }
+
+    /// Finalize capital call, distribute LP tokens to depositors and close capital call if all
+    /// vouchers are settled.
+    /// Each depositor is the next three of `remaining_accounts`: voucher, LP token destination of
+    /// depositor and depositor receiving voucher rent. The rest of `remaining_accounts` are
+    /// passed to finalize callback. Referred vouchers and credit tokens aren't supported.
+    pub fn finalize_and_distribute<'info>(
+        ctx: Context<'_, '_, '_, 'info, FinalizeAndDistribute<'info>>,
+        depositors: u8,
+    ) -> Result<()> {
+        require!(!ctx.accounts.config.killed, CapitalCallError::ProgramKilled);
+        let clock = Clock::get().map_err::<error::Error, _>(Into::into)?;
+        let now = clock.unix_timestamp as u64;
+        ctx.accounts.capital_call.check_finalizable(
+            &ctx.accounts.config,
+            &ctx.accounts.lp_mint,
+            &ctx.accounts.lp_mint_authority.key(),
+            now,
+        )?;
+        require!(
+            ctx.accounts.capital_call.finalize_chunk == 0,
+            CapitalCallError::ChunkedFinalizeRequired
+        );
+        ctx.accounts
+            .capital_call
+            .check_plain_finalize(&ctx.accounts.config)?;
+        require!(
+            ctx.accounts.capital_call.capacity == ctx.accounts.capital_call.allocated,
+            CapitalCallError::CapitalCallNotFullyFunded
+        );
+        require!(
+            !ctx.accounts.capital_call.is_lp_minted,
+            CapitalCallError::LpTokensAlreadyMinted
+        );
+        require!(
+            ctx.accounts.capital_call.credit_token_mint == Pubkey::default(),
+            CapitalCallError::InvalidCreditToken
+        );
+        // Distributed LP tokens bypass lockup escrow
+        require!(
+            ctx.accounts.capital_call.lp_lockup == 0,
+            CapitalCallError::InvalidLpLockup
+        );
+        // Distribution happens at finalization, so claims can't be delayed
+        require!(
+            ctx.accounts.capital_call.claim_delay == 0,
+            CapitalCallError::ClaimNotOpenYet
+        );
+
+        let depositors = depositors as usize;
+        require!(
+            depositors <= MAX_DISTRIBUTE_DEPOSITORS
+                && ctx.remaining_accounts.len() >= depositors * 3,
+            CapitalCallError::InvalidDistribution
+        );
+        let (depositor_infos, callback_accounts) =
+            ctx.remaining_accounts.split_at(depositors * 3);
+
+        ctx.accounts.capital_call.take_snapshot(
+            ctx.accounts.lp_mint.supply,
+            ctx.accounts.liquidity_pool.amount,
+            clock.unix_timestamp as u64,
+        )?;
+
+        let minted = ctx
+            .accounts
+            .capital_call
+            .lp_to_mint()?;
+
+        let config_key = ctx.accounts.config.key();
+        let seeds = [
+            SEED_LP_MINT_AUTHORITY.as_ref(),
+            config_key.as_ref(),
+            &[ctx.accounts.config.bump],
+        ];
+
+        token::mint_to(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                MintTo {
+                    mint: ctx.accounts.lp_mint.to_account_info(),
+                    to: ctx.accounts.lp_token_pool.to_account_info(),
+                    authority: ctx.accounts.lp_mint_authority.to_account_info(),
+                },
+                &[&seeds],
+            ),
+            minted,
+        )?;
+
+        ctx.accounts.lp_mint.reload()?;
+        ctx.accounts
+            .capital_call
+            .check_lp_supply(ctx.accounts.lp_mint.supply, minted)?;
+
+        let signer_seeds = ctx.accounts.capital_call.signer_seeds();
+        let seeds = signer_seeds.as_slices();
+
+        let capital = ctx.accounts.capital_call.capacity;
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.vault.to_account_info(),
+                    to: ctx.accounts.liquidity_pool.to_account_info(),
+                    authority: ctx.accounts.capital_call.to_account_info(),
+                },
+                &[&seeds],
+            ),
+            capital,
+        )?;
+
+        ctx.accounts.capital_call.is_lp_minted = true;
+        ctx.accounts.capital_call.lp_minted = minted;
+        ctx.accounts.capital_call.finalized_at = now;
+
+        emit!(LpTokensMintedEvent {
+            program_version: PROGRAM_VERSION,
+            config: config_key,
+            capital_call: ctx.accounts.capital_call.key(),
+            token_liquidity: ctx.accounts.capital_call.token_liquidity,
+            lp_supply: ctx.accounts.capital_call.lp_supply,
+            credit_outstanding: ctx.accounts.capital_call.credit_outstanding,
+            capital,
+            minted,
+            fill_fee: 0,
+        });
+
+        invoke_finalize_callback(
+            &ctx.accounts.config,
+            &ctx.accounts.capital_call,
+            callback_accounts,
+            minted,
+        )?;
+
+        for depositor_accounts in depositor_infos.chunks(3) {
+            let voucher = Account::<Voucher>::try_from(&depositor_accounts[0])?;
+            let destination = Account::<TokenAccount>::try_from(&depositor_accounts[1])?;
+            let depositor = &depositor_accounts[2];
+            require!(
+                voucher.capital_call == ctx.accounts.capital_call.key()
+                    && voucher.authority == depositor.key(),
+                CapitalCallError::InvalidVoucher
+            );
+            require!(
+                voucher.referrer == Pubkey::default(),
+                CapitalCallError::InvalidReferral
+            );
+            require!(
+                destination.owner == voucher.authority
+                    && destination.mint == ctx.accounts.lp_mint.key(),
+                CapitalCallError::InvalidDistribution
+            );
+
+            let amount = voucher.amount;
+            let total_lp = ctx.accounts.capital_call.voucher_lp(amount)?;
+            require!(
+                voucher.vested_lp(&ctx.accounts.capital_call, total_lp, now)? == total_lp,
+                CapitalCallError::VestingNotComplete
+            );
+            let distributed = total_lp
+                .checked_sub(voucher.claimed_lp)
+                .ok_or_else(|| error!(CapitalCallError::CalculationError))?;
+
+            let claim_fee = ctx.accounts.config.claim_fee(distributed)?;
+            transfer_claim_fee(
+                &ctx.accounts.capital_call,
+                &ctx.accounts.lp_token_pool,
+                &ctx.accounts.fee_lp_account,
+                &ctx.accounts.token_program,
+                &seeds,
+                claim_fee,
+            )?;
+            let lp_amount = distributed - claim_fee;
+
+            token::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.lp_token_pool.to_account_info(),
+                        to: destination.to_account_info(),
+                        authority: ctx.accounts.capital_call.to_account_info(),
+                    },
+                    &[&seeds],
+                ),
+                lp_amount,
+            )?;
+
+            ctx.accounts.capital_call.redeemed += amount;
+            ctx.accounts.capital_call.distribute_lp(distributed)?;
+            voucher.close(depositor.clone())?;
+
+            emit!(ClaimEvent {
+                program_version: PROGRAM_VERSION,
+                config: config_key,
+                capital_call: ctx.accounts.capital_call.key(),
+                authority: depositor.key(),
+                amount,
+                lp_amount,
+                referral_fee: 0,
+                claim_fee,
+                lp_minted: ctx.accounts.capital_call.lp_minted,
+                lp_distributed: ctx.accounts.capital_call.lp_distributed,
+            });
+        }
+
+        // Capital call stays open for `close` if vouchers are left or vault has extra tokens
+        ctx.accounts.vault.reload()?;
+        if ctx.accounts.capital_call.allocated != ctx.accounts.capital_call.redeemed
+            || ctx.accounts.capital_call.referral_lp_outstanding != 0
+            || ctx.accounts.vault.amount != 0
+        {
+            return Ok(());
+        }
+
+        token::close_account(CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            CloseAccount {
+                account: ctx.accounts.vault.to_account_info(),
+                destination: ctx.accounts.receiver.to_account_info(),
+                authority: ctx.accounts.capital_call.to_account_info(),
+            },
+            &[&seeds],
+        ))?;
+
+        // burn leftover LP tokens
+        ctx.accounts.lp_token_pool.reload()?;
+        token::burn(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Burn {
+                    mint: ctx.accounts.lp_mint.to_account_info(),
+                    from: ctx.accounts.lp_token_pool.to_account_info(),
+                    authority: ctx.accounts.capital_call.to_account_info(),
+                },
+                &[&seeds],
+            ),
+            ctx.accounts.lp_token_pool.amount,
+        )?;
+
+        token::close_account(CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            CloseAccount {
+                account: ctx.accounts.lp_token_pool.to_account_info(),
+                destination: ctx.accounts.receiver.to_account_info(),
+                authority: ctx.accounts.capital_call.to_account_info(),
+            },
+            &[&seeds],
+        ))?;
+
+        let allocated = ctx.accounts.capital_call.allocated;
+        let config = &mut ctx.accounts.config;
+        config.total_allocated = config
+            .total_allocated
+            .checked_sub(allocated)
+            .ok_or_else(|| error!(CapitalCallError::CalculationError))?;
+        config.total_capacity = config
+            .total_capacity
+            .checked_sub(capital)
+            .ok_or_else(|| error!(CapitalCallError::CalculationError))?;
+        config.open_calls = config
+            .open_calls
+            .checked_sub(1)
+            .ok_or_else(|| error!(CapitalCallError::CalculationError))?;
+
+        ctx.accounts
+            .capital_call
+            .close(ctx.accounts.receiver.to_account_info())?;
+
+        Ok(())
+    }
+
+    /// Finalize capital call transferring raised capital to liquidity pool in chunks of
+    /// `finalize_chunk`, so finalization is resumable across transactions.
+    /// The first call mints LP tokens as `mint_lp_tokens` and passes `remaining_accounts` to
+    /// finalize callback, each call transfers the next chunk and advances `finalize_cursor`.
+    /// LP tokens are claimable once the last chunk is transferred.
+    pub fn finalize_chunked<'info>(
+        ctx: Context<'_, '_, '_, 'info, FinalizeChunked<'info>>,
+    ) -> Result<()> {
+        require!(!ctx.accounts.config.killed, CapitalCallError::ProgramKilled);
+        require!(
+            ctx.accounts.capital_call.finalize_chunk > 0,
+            CapitalCallError::FinalizeNotChunked
+        );
+
+        let clock = Clock::get().map_err::<error::Error, _>(Into::into)?;
+        let now = clock.unix_timestamp as u64;
+        let config_key = ctx.accounts.config.key();
+
+        if !ctx.accounts.capital_call.is_lp_minted {
+            ctx.accounts.capital_call.check_finalizable(
+                &ctx.accounts.config,
+                &ctx.accounts.lp_mint,
+                &ctx.accounts.lp_mint_authority.key(),
+                now,
+            )?;
+            ctx.accounts
+                .capital_call
+                .check_plain_finalize(&ctx.accounts.config)?;
+            require!(
+                ctx.accounts.capital_call.capacity == ctx.accounts.capital_call.allocated,
+                CapitalCallError::CapitalCallNotFullyFunded
+            );
+
+            ctx.accounts.capital_call.take_snapshot(
+                ctx.accounts.lp_mint.supply,
+                ctx.accounts.liquidity_pool.amount,
+                now,
+            )?;
+
+            let minted = ctx
+            .accounts
+            .capital_call
+            .lp_to_mint()?;
+
+            let seeds = [
+                SEED_LP_MINT_AUTHORITY.as_ref(),
+                config_key.as_ref(),
+                &[ctx.accounts.config.bump],
+            ];
+            token::mint_to(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    MintTo {
+                        mint: ctx.accounts.lp_mint.to_account_info(),
+                        to: ctx.accounts.lp_token_pool.to_account_info(),
+                        authority: ctx.accounts.lp_mint_authority.to_account_info(),
+                    },
+                    &[&seeds],
+                ),
+                minted,
+            )?;
+
+            ctx.accounts.lp_mint.reload()?;
+            ctx.accounts
+                .capital_call
+                .check_lp_supply(ctx.accounts.lp_mint.supply, minted)?;
+
+            ctx.accounts.capital_call.is_lp_minted = true;
+            ctx.accounts.capital_call.lp_minted = minted;
+            ctx.accounts.capital_call.finalized_at = now;
+
+            emit!(LpTokensMintedEvent {
+                program_version: PROGRAM_VERSION,
+                config: config_key,
+                capital_call: ctx.accounts.capital_call.key(),
+                token_liquidity: ctx.accounts.capital_call.token_liquidity,
+                lp_supply: ctx.accounts.capital_call.lp_supply,
+                credit_outstanding: ctx.accounts.capital_call.credit_outstanding,
+                capital: ctx.accounts.capital_call.capacity,
+                minted,
+                fill_fee: 0,
+            });
+
+            invoke_finalize_callback(
+                &ctx.accounts.config,
+                &ctx.accounts.capital_call,
+                ctx.remaining_accounts,
+                minted,
+            )?;
+        }
+
+        let capital = ctx.accounts.capital_call.capacity;
+        let cursor = ctx.accounts.capital_call.finalize_cursor;
+        require!(cursor < capital, CapitalCallError::FinalizeAlreadyComplete);
+        let amount = (capital - cursor).min(ctx.accounts.capital_call.finalize_chunk);
+
+        let signer_seeds = ctx.accounts.capital_call.signer_seeds();
+        let seeds = signer_seeds.as_slices();
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.vault.to_account_info(),
+                    to: ctx.accounts.liquidity_pool.to_account_info(),
+                    authority: ctx.accounts.capital_call.to_account_info(),
+                },
+                &[&seeds],
+            ),
+            amount,
+        )?;
+        ctx.accounts.capital_call.finalize_cursor = cursor + amount;
+
+        emit!(FinalizeProgressEvent {
+            program_version: PROGRAM_VERSION,
+            config: config_key,
+            capital_call: ctx.accounts.capital_call.key(),
+            amount,
+            finalize_cursor: ctx.accounts.capital_call.finalize_cursor,
+            capital,
+        });
+
+        Ok(())
+    }
+
+    /// Unwind LP tokens minting before any LP tokens are claimed: deployed capital is returned
+    /// from liquidity pool to vault with signature of pool owner and minted LP tokens are burned.
+    /// Capital call is active again, so `mint_lp_tokens` takes fresh snapshots of pool state.
+    /// Capital calls with secondary mint, charged fill fee or capital split between several
+    /// liquidity pools aren't supported.
+    pub fn unwind(ctx: Context<Unwind>) -> Result<()> {
+        require!(!ctx.accounts.config.killed, CapitalCallError::ProgramKilled);
+        let capital_call = &ctx.accounts.capital_call;
+        require!(
+            capital_call.is_lp_minted,
+            CapitalCallError::LpTokenNotMinted
+        );
+        require!(
+            capital_call.lp_distributed == 0
+                && capital_call.secondary_mint == Pubkey::default()
+                && capital_call.fill_fee_lp == 0
+                && !capital_call.split_finalized
+                && !capital_call.is_finalizing(),
+            CapitalCallError::UnwindNotAllowed
+        );
+
+        let deployed = capital_call
+            .capacity
+            .checked_sub(capital_call.vault_reserve)
+            .ok_or_else(|| error!(CapitalCallError::CalculationError))?;
+        token::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.liquidity_pool.to_account_info(),
+                    to: ctx.accounts.vault.to_account_info(),
+                    authority: ctx.accounts.pool_authority.to_account_info(),
+                },
+            ),
+            deployed,
+        )?;
+
+        let signer_seeds = capital_call.signer_seeds();
+        let seeds = signer_seeds.as_slices();
+
+        token::burn(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Burn {
+                    mint: ctx.accounts.lp_mint.to_account_info(),
+                    from: ctx.accounts.lp_token_pool.to_account_info(),
+                    authority: ctx.accounts.capital_call.to_account_info(),
+                },
+                &[&seeds],
+            ),
+            ctx.accounts.capital_call.lp_minted,
+        )?;
+
+        let capital_call = &mut ctx.accounts.capital_call;
+        capital_call.is_lp_minted = false;
+        capital_call.lp_minted = 0;
+        capital_call.lp_supply = 0;
+        capital_call.token_liquidity = 0;
+        capital_call.finalized_at = 0;
+        capital_call.vault_reserve = 0;
+        capital_call.fill_fee_bps = 0;
+        capital_call.fill_fee_lp = 0;
+        capital_call.lp_locked = 0;
+        capital_call.finalize_cursor = 0;
+        capital_call.oracle_rate = 0;
+
+        emit!(CapitalCallUnwoundEvent {
+            program_version: PROGRAM_VERSION,
+            config: capital_call.config,
+            capital_call: capital_call.key(),
+            amount: deployed,
+        });
+
+        Ok(())
+    }
+
+    /// Claim LP tokens for voucher, LP tokens have to be fully vested.
+    /// If voucher has referrer, Referral account of referrer has to be the first of
+    /// `remaining_accounts`.
+    /// Credit tokens are burned as in `refund`, credit accounts follow Referral account.
+    /// If capital call has `lp_lockup`, destination is LP escrow of depositor and its LpLockup
+    /// is the last of `remaining_accounts`.
+    pub fn claim<'info>(ctx: Context<'_, '_, '_, 'info, Claim<'info>>) -> Result<()> {
+        require!(!ctx.accounts.config.killed, CapitalCallError::ProgramKilled);
+        let capital_call = &ctx.accounts.capital_call;
+        require!(!capital_call.frozen, CapitalCallError::CapitalCallFrozen);
+        require!(
+            capital_call.is_lp_minted,
+            CapitalCallError::LpTokenNotMinted
+        );
+        require!(
+            !capital_call.is_finalizing(),
+            CapitalCallError::FinalizationInProgress
+        );
+        ctx.accounts.voucher.settle(SettlementKind::Claim)?;
+
+        let clock = Clock::get().map_err::<error::Error, _>(Into::into)?;
+        let now = clock.unix_timestamp as u64;
+        require!(
+            now >= capital_call
+                .finalized_at
+                .checked_add(capital_call.claim_delay)
+                .ok_or_else(|| error!(CapitalCallError::CalculationError))?,
+            CapitalCallError::ClaimNotOpenYet
+        );
+        capital_call.check_lp_pool_balance(ctx.accounts.lp_token_pool.amount)?;
+
+        let signer_seeds = capital_call.signer_seeds();
+        let seeds = signer_seeds.as_slices();
+
+        let amount = ctx.accounts.voucher.amount;
+        let total_lp = capital_call.voucher_lp(amount)?;
+        require!(
+            ctx.accounts.voucher.vested_lp(capital_call, total_lp, now)? == total_lp,
+            CapitalCallError::VestingNotComplete
+        );
+
+        // LP tokens claimed with claim_vested are excluded
+        let mut lp_amount = total_lp
+            .checked_sub(ctx.accounts.voucher.claimed_lp)
+            .ok_or_else(|| error!(CapitalCallError::CalculationError))?;
+        let referral_fee = withhold_referral_fee(
+            capital_call,
+            &ctx.accounts.voucher,
+            ctx.remaining_accounts,
+            lp_amount,
+        )?;
+        lp_amount -= referral_fee;
+
+        let credit_accounts = if ctx.accounts.voucher.referrer != Pubkey::default() {
+            &ctx.remaining_accounts[1..]
+        } else {
+            ctx.remaining_accounts
+        };
+        burn_credit_tokens(
+            &ctx.accounts.capital_call,
+            &ctx.accounts.authority,
+            &ctx.accounts.token_program,
+            credit_accounts,
+            amount,
+        )?;
+
+        let claim_fee = ctx.accounts.config.claim_fee(lp_amount)?;
+        transfer_claim_fee(
+            &ctx.accounts.capital_call,
+            &ctx.accounts.lp_token_pool,
+            &ctx.accounts.fee_lp_account,
+            &ctx.accounts.token_program,
+            &seeds,
+            claim_fee,
+        )?;
+        let distributed = lp_amount;
+        let lp_amount = lp_amount - claim_fee;
+
+        let cpi_ctx: CpiContext<_> = ctx.accounts.into();
+        token::transfer(cpi_ctx.with_signer(&[&seeds]), lp_amount)?;
+        let locked = lock_claimed_lp(
+            &ctx.accounts.capital_call,
+            &ctx.accounts.authority.key(),
+            &ctx.accounts.destination.key(),
+            ctx.remaining_accounts,
+            lp_amount,
+            now,
+        )?;
+
+        ctx.accounts.capital_call.redeemed += amount;
+        ctx.accounts.capital_call.lp_locked += locked;
+        ctx.accounts.capital_call.distribute_lp(distributed)?;
+        ctx.accounts.capital_call.referral_lp_outstanding += referral_fee;
+
+        emit!(ClaimEvent {
+            program_version: PROGRAM_VERSION,
+            config: ctx.accounts.capital_call.config,
+            capital_call: ctx.accounts.capital_call.key(),
+            authority: ctx.accounts.authority.key(),
+            amount,
+            lp_amount,
+            referral_fee,
+            claim_fee,
+            lp_minted: ctx.accounts.capital_call.lp_minted,
+            lp_distributed: ctx.accounts.capital_call.lp_distributed,
+        });
+
+        Ok(())
+    }
+
+    /// Claim LP tokens vested so far, voucher is kept until remaining LP tokens are claimed
+    /// with `claim`.
+    /// Claims before LP tokens are fully vested are limited by `max_partial_claims` of config,
+    /// final claim is always allowed.
+    /// If voucher has referrer, Referral account of referrer has to be the first of
+    /// `remaining_accounts`.
+    /// If capital call has `lp_lockup`, LP tokens are escrowed as in `claim`.
+    pub fn claim_vested<'info>(ctx: Context<'_, '_, '_, 'info, ClaimVested<'info>>) -> Result<()> {
+        require!(!ctx.accounts.config.killed, CapitalCallError::ProgramKilled);
+        let capital_call = &ctx.accounts.capital_call;
+        require!(!capital_call.frozen, CapitalCallError::CapitalCallFrozen);
+        require!(
+            capital_call.is_lp_minted,
+            CapitalCallError::LpTokenNotMinted
+        );
+        require!(
+            !capital_call.is_finalizing(),
+            CapitalCallError::FinalizationInProgress
+        );
+        ctx.accounts.voucher.settle(SettlementKind::Claim)?;
+
+        let clock = Clock::get().map_err::<error::Error, _>(Into::into)?;
+        let now = clock.unix_timestamp as u64;
+        require!(
+            now >= capital_call
+                .finalized_at
+                .checked_add(capital_call.claim_delay)
+                .ok_or_else(|| error!(CapitalCallError::CalculationError))?,
+            CapitalCallError::ClaimNotOpenYet
+        );
+        capital_call.check_lp_pool_balance(ctx.accounts.lp_token_pool.amount)?;
+
+        let signer_seeds = capital_call.signer_seeds();
+        let seeds = signer_seeds.as_slices();
+
+        let total_lp = capital_call.voucher_lp(ctx.accounts.voucher.amount)?;
+        let vested = ctx.accounts.voucher.vested_lp(capital_call, total_lp, now)?;
+        let mut lp_amount = vested
+            .checked_sub(ctx.accounts.voucher.claimed_lp)
+            .ok_or_else(|| error!(CapitalCallError::CalculationError))?;
+        require!(lp_amount > 0, CapitalCallError::AmountNonZero);
+
+        // Dust claims are rejected unless nothing is left to vest
+        if vested < total_lp {
+            let amount = u64::try_from(
+                lp_amount as u128 * ctx.accounts.voucher.amount as u128 / total_lp as u128,
+            )
+            .map_err(|_| error!(CapitalCallError::CalculationError))?;
+            require!(
+                amount >= ctx.accounts.config.min_claim,
+                CapitalCallError::ClaimBelowMinimum
+            );
+            require!(
+                ctx.accounts.config.max_partial_claims == 0
+                    || ctx.accounts.voucher.claim_count < ctx.accounts.config.max_partial_claims,
+                CapitalCallError::TooManyPartialClaims
+            );
+            ctx.accounts.voucher.claim_count += 1;
+        }
+
+        let referral_fee = withhold_referral_fee(
+            capital_call,
+            &ctx.accounts.voucher,
+            ctx.remaining_accounts,
+            lp_amount,
+        )?;
+        lp_amount -= referral_fee;
+
+        let claim_fee = ctx.accounts.config.claim_fee(lp_amount)?;
+        transfer_claim_fee(
+            &ctx.accounts.capital_call,
+            &ctx.accounts.lp_token_pool,
+            &ctx.accounts.fee_lp_account,
+            &ctx.accounts.token_program,
+            &seeds,
+            claim_fee,
+        )?;
+        let distributed = lp_amount;
+        let lp_amount = lp_amount - claim_fee;
+
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.lp_token_pool.to_account_info(),
+                    to: ctx.accounts.destination.to_account_info(),
+                    authority: ctx.accounts.capital_call.to_account_info(),
+                },
+                &[&seeds],
+            ),
+            lp_amount,
+        )?;
+        let locked = lock_claimed_lp(
+            &ctx.accounts.capital_call,
+            &ctx.accounts.authority.key(),
+            &ctx.accounts.destination.key(),
+            ctx.remaining_accounts,
+            lp_amount,
+            now,
+        )?;
+
+        ctx.accounts.voucher.claimed_lp = vested;
+        ctx.accounts.capital_call.distribute_lp(distributed)?;
+        ctx.accounts.capital_call.lp_locked += locked;
+        ctx.accounts.capital_call.referral_lp_outstanding += referral_fee;
+
+        emit!(VestedClaimEvent {
+            program_version: PROGRAM_VERSION,
+            config: ctx.accounts.capital_call.config,
+            capital_call: ctx.accounts.capital_call.key(),
+            authority: ctx.accounts.authority.key(),
+            lp_amount,
+            referral_fee,
+            claim_fee,
+            claimed_lp: vested,
+            lp_minted: ctx.accounts.capital_call.lp_minted,
+            lp_distributed: ctx.accounts.capital_call.lp_distributed,
+        });
+
+        Ok(())
+    }
+
+    /// Create LP escrow and LpLockup of depositor, claims of capital call with `lp_lockup` go
+    /// to the escrow
+    pub fn init_lp_lockup(ctx: Context<InitLpLockup>) -> Result<()> {
+        require!(!ctx.accounts.config.killed, CapitalCallError::ProgramKilled);
+        require!(
+            ctx.accounts.capital_call.lp_lockup > 0,
+            CapitalCallError::InvalidLpLockup
+        );
+
+        let lp_lockup = &mut ctx.accounts.lp_lockup;
+        lp_lockup.capital_call = ctx.accounts.capital_call.key();
+        lp_lockup.authority = ctx.accounts.authority.key();
+        lp_lockup.escrow = ctx.accounts.escrow.key();
+        lp_lockup.amount = 0;
+        lp_lockup.release_at = 0;
+        lp_lockup.bump = *ctx
+            .bumps
+            .get("lp_lockup")
+            .ok_or_else(|| error!(CapitalCallError::BumpSeedNotInHashMap))?;
+
+        Ok(())
+    }
+
+    /// Transfer escrowed LP tokens to depositor once lockup after the latest claim elapsed,
+    /// LP escrow and LpLockup are closed
+    pub fn release_lp(ctx: Context<ReleaseLp>) -> Result<()> {
+        require!(!ctx.accounts.config.killed, CapitalCallError::ProgramKilled);
+        let capital_call = &ctx.accounts.capital_call;
+        require!(!capital_call.frozen, CapitalCallError::CapitalCallFrozen);
+
+        let clock = Clock::get().map_err::<error::Error, _>(Into::into)?;
+        require!(
+            clock.unix_timestamp as u64 >= ctx.accounts.lp_lockup.release_at,
+            CapitalCallError::LpLockupNotElapsed
+        );
+
+        let signer_seeds = capital_call.signer_seeds();
+        let seeds = signer_seeds.as_slices();
+
+        // Escrow is emptied with LP tokens sent to it directly, so it can be closed
+        let amount = ctx.accounts.escrow.amount;
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.escrow.to_account_info(),
+                    to: ctx.accounts.destination.to_account_info(),
+                    authority: ctx.accounts.capital_call.to_account_info(),
+                },
+                &[&seeds],
+            ),
+            amount,
+        )?;
+        token::close_account(CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            CloseAccount {
+                account: ctx.accounts.escrow.to_account_info(),
+                destination: ctx.accounts.authority.to_account_info(),
+                authority: ctx.accounts.capital_call.to_account_info(),
+            },
+            &[&seeds],
+        ))?;
+
+        ctx.accounts.capital_call.lp_locked = ctx
+            .accounts
+            .capital_call
+            .lp_locked
+            .checked_sub(ctx.accounts.lp_lockup.amount)
+            .ok_or_else(|| error!(CapitalCallError::CalculationError))?;
+
+        emit!(LpReleasedEvent {
+            program_version: PROGRAM_VERSION,
+            config: ctx.accounts.capital_call.config,
+            capital_call: ctx.accounts.capital_call.key(),
+            authority: ctx.accounts.authority.key(),
+            amount,
+        });
+
+        Ok(())
+    }
+
+    /// Set custom vesting schedule of voucher taking precedence over schedule of capital call
+    pub fn set_vesting_override(
+        ctx: Context<SetVestingOverride>,
+        start: u64,
+        cliff: u64,
+        duration: u64,
+    ) -> Result<()> {
+        let voucher = &mut ctx.accounts.voucher;
+        require!(voucher.claimed_lp == 0, CapitalCallError::VestingAlreadyClaimed);
+
+        voucher.vest_override = true;
+        voucher.vest_override_start = start;
+        voucher.vest_override_cliff = cliff;
+        voucher.vest_override_duration = duration;
+
+        Ok(())
+    }
+
+    /// Close voucher not claimed before voucher expiry of capital call, LP tokens of voucher
+    /// not claimed yet are forfeited to `void_treasury` of config
+    pub fn void_voucher(ctx: Context<VoidVoucher>) -> Result<()> {
+        require!(!ctx.accounts.config.killed, CapitalCallError::ProgramKilled);
+        let capital_call = &ctx.accounts.capital_call;
+        require!(!capital_call.frozen, CapitalCallError::CapitalCallFrozen);
+        require!(
+            capital_call.is_lp_minted,
+            CapitalCallError::LpTokenNotMinted
+        );
+
+        let clock = Clock::get().map_err::<error::Error, _>(Into::into)?;
+        let now = clock.unix_timestamp as u64;
+        require!(
+            capital_call.voucher_expiry > 0 && now >= capital_call.voucher_expiry,
+            CapitalCallError::VoucherNotExpired
+        );
+        // Finalization or vesting override may end past the horizon checked with expiry
+        require!(
+            now >= capital_call
+                .finalized_at
+                .checked_add(capital_call.claim_delay)
+                .ok_or_else(|| error!(CapitalCallError::CalculationError))?,
+            CapitalCallError::VoucherNotExpired
+        );
+        let voucher = &ctx.accounts.voucher;
+        let total_lp = capital_call.voucher_lp(voucher.amount)?;
+        require!(
+            voucher.vested_lp(capital_call, total_lp, now)? == total_lp,
+            CapitalCallError::VestingNotComplete
+        );
+
+        let signer_seeds = capital_call.signer_seeds();
+        let seeds = signer_seeds.as_slices();
+
+        let amount = ctx.accounts.voucher.amount;
+        let lp_amount = capital_call
+            .voucher_lp(amount)?
+            .checked_sub(ctx.accounts.voucher.claimed_lp)
+            .ok_or_else(|| error!(CapitalCallError::CalculationError))?;
+
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.lp_token_pool.to_account_info(),
+                    to: ctx.accounts.treasury.to_account_info(),
+                    authority: ctx.accounts.capital_call.to_account_info(),
+                },
+                &[&seeds],
+            ),
+            lp_amount,
+        )?;
+
+        ctx.accounts.capital_call.redeemed = ctx
+            .accounts
+            .capital_call
+            .redeemed
+            .checked_add(amount)
+            .ok_or_else(|| error!(CapitalCallError::CalculationError))?;
+        ctx.accounts.capital_call.distribute_lp(lp_amount)?;
+
+        emit!(VoucherVoidedEvent {
+            program_version: PROGRAM_VERSION,
+            config: ctx.accounts.capital_call.config,
+            capital_call: ctx.accounts.capital_call.key(),
+            authority: ctx.accounts.voucher.authority,
+            amount,
+            lp_amount,
+            treasury: ctx.accounts.treasury.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Close voucher left behind by capital call which was closed before, rent of voucher is
+    /// returned to depositor. Capital call is derived from config and its creation arguments.
+    pub fn recover_orphan_voucher(
+        ctx: Context<RecoverOrphanVoucher>,
+        _start_time: u64,
+        _capacity: u64,
+        _idempotency_key: [u8; 16],
+    ) -> Result<()> {
+        let capital_call = &ctx.accounts.capital_call;
+        require!(
+            capital_call.lamports() == 0
+                && capital_call.data_is_empty()
+                && capital_call.owner == &system_program::ID,
+            CapitalCallError::CapitalCallNotClosed
+        );
+
+        emit!(OrphanVoucherRecoveredEvent {
+            program_version: PROGRAM_VERSION,
+            config: ctx.accounts.config.key(),
+            capital_call: capital_call.key(),
+            voucher: ctx.accounts.voucher.key(),
+            authority: ctx.accounts.voucher.authority,
+            amount: ctx.accounts.voucher.amount,
+        });
+
+        Ok(())
+    }
+
+    /// Register referrer of capital call, deposits can refer only registered referrers
+    pub fn register_referral(ctx: Context<RegisterReferral>) -> Result<()> {
+        require!(!ctx.accounts.config.killed, CapitalCallError::ProgramKilled);
+        let referral = &mut ctx.accounts.referral;
+        referral.capital_call = ctx.accounts.capital_call.key();
+        referral.referrer = ctx.accounts.referrer.key();
+        referral.accrued_lp = 0;
+        referral.bump = *ctx
+            .bumps
+            .get("referral")
+            .ok_or_else(|| error!(CapitalCallError::BumpSeedNotInHashMap))?;
+        Ok(())
+    }
+
+    /// Claim LP tokens accrued as referral fees
+    pub fn claim_referral(ctx: Context<ClaimReferral>) -> Result<()> {
+        require!(!ctx.accounts.config.killed, CapitalCallError::ProgramKilled);
+        let capital_call = &ctx.accounts.capital_call;
+
+        let signer_seeds = capital_call.signer_seeds();
+        let seeds = signer_seeds.as_slices();
+
+        let lp_amount = ctx.accounts.referral.accrued_lp;
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.lp_token_pool.to_account_info(),
+                    to: ctx.accounts.destination.to_account_info(),
+                    authority: ctx.accounts.capital_call.to_account_info(),
+                },
+                &[&seeds],
+            ),
+            lp_amount,
+        )?;
+
+        ctx.accounts.referral.accrued_lp = 0;
+        ctx.accounts.capital_call.referral_lp_outstanding -= lp_amount;
+        ctx.accounts.capital_call.distribute_lp(lp_amount)?;
+
+        emit!(ReferralClaimedEvent {
+            program_version: PROGRAM_VERSION,
+            config: ctx.accounts.capital_call.config,
+            capital_call: ctx.accounts.capital_call.key(),
+            referrer: ctx.accounts.referrer.key(),
+            lp_amount,
+        });
+
+        Ok(())
+    }
+
+    /// Close capital call and related accounts.
+    /// Non-zero `close_retain` leaves that amount in the vault, which then stays open with
+    /// capital call marked `retained` and counted in `retained_vaults` of config until
+    /// `sweep_retained`. Only capital call without outstanding deposits can retain.
+    pub fn close(ctx: Context<CloseCapitalCall>, close_retain: u64) -> Result<()> {
+        require!(!ctx.accounts.config.killed, CapitalCallError::ProgramKilled);
+        let clock = Clock::get().map_err::<error::Error, _>(Into::into)?;
+        let now = clock.unix_timestamp as u64;
+        let capital_call = &ctx.accounts.capital_call;
+        require!(
+            !capital_call.retained,
+            CapitalCallError::CapitalCallRetained
+        );
+        require!(
+            capital_call.pending_amount == 0,
+            CapitalCallError::DepositNotConfirmed
+        );
+        require!(
+            close_retain == 0 || capital_call.allocated == capital_call.redeemed,
+            CapitalCallError::CapitalCallHasToBeFullyRefunded
+        );
+
+        if !capital_call.is_lp_minted {
+            if now > capital_call.end_time {
+                require!(
+                    capital_call.allocated == capital_call.redeemed,
+                    CapitalCallError::CapitalCallHasToBeFullyRefunded
+                );
+            }
+        } else {
+            require!(
+                capital_call.allocated == capital_call.redeemed
+                    && capital_call.referral_lp_outstanding == 0
+                    && capital_call.lp_locked == 0,
+                CapitalCallError::LpTokensHasToBeFullyDistributed
+            );
+        }
+
+        // Someone can transfer tokens directly to vault
+        let signer_seeds = capital_call.signer_seeds();
+        let seeds = signer_seeds.as_slices();
+
+        require!(
+            close_retain <= ctx.accounts.vault.amount,
+            CapitalCallError::InsufficientVaultBalance
+        );
+
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.vault.to_account_info(),
+                    to: ctx.accounts.destination.to_account_info(),
+                    authority: ctx.accounts.capital_call.to_account_info(),
+                },
+                &[&seeds],
+            ),
+            ctx.accounts.vault.amount - close_retain,
+        )?;
+
+        if close_retain == 0 {
+            token::close_account(CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                CloseAccount {
+                    account: ctx.accounts.vault.to_account_info(),
+                    destination: ctx.accounts.receiver.to_account_info(),
+                    authority: ctx.accounts.capital_call.to_account_info(),
+                },
+                &[&seeds],
+            ))?;
+        }
+
+        // burn leftover LP tokens
+        token::burn(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Burn {
+                    mint: ctx.accounts.lp_mint.to_account_info(),
+                    from: ctx.accounts.lp_token_pool.to_account_info(),
+                    authority: ctx.accounts.capital_call.to_account_info(),
+                },
+                &[&seeds],
+            ),
+            ctx.accounts.lp_token_pool.amount,
+        )?;
+
+        token::close_account(CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            CloseAccount {
+                account: ctx.accounts.lp_token_pool.to_account_info(),
+                destination: ctx.accounts.receiver.to_account_info(),
+                authority: ctx.accounts.capital_call.to_account_info(),
+            },
+            &[&seeds],
+        ))?;
+
+        // Refunded tokens are already excluded from config totals
+        let outstanding = if ctx.accounts.capital_call.is_lp_minted {
+            ctx.accounts.capital_call.allocated
+        } else {
+            ctx.accounts.capital_call.allocated - ctx.accounts.capital_call.redeemed
+        };
+        let capacity = ctx.accounts.capital_call.capacity;
+
+        let config = &mut ctx.accounts.config;
+        config.total_allocated = config
+            .total_allocated
+            .checked_sub(outstanding)
+            .ok_or_else(|| error!(CapitalCallError::CalculationError))?;
+        config.total_capacity = config
+            .total_capacity
+            .checked_sub(capacity)
+            .ok_or_else(|| error!(CapitalCallError::CalculationError))?;
+        config.open_calls = config
+            .open_calls
+            .checked_sub(1)
+            .ok_or_else(|| error!(CapitalCallError::CalculationError))?;
+        if close_retain > 0 {
+            config.retained_vaults = config
+                .retained_vaults
+                .checked_add(1)
+                .ok_or_else(|| error!(CapitalCallError::CalculationError))?;
+            ctx.accounts.capital_call.retained = true;
+        } else {
+            ctx.accounts
+                .capital_call
+                .close(ctx.accounts.receiver.to_account_info())?;
+        }
+
+        Ok(())
+    }
+
+    /// Move balance retained by `close` from vault to `destination` and close vault and
+    /// capital call
+    pub fn sweep_retained(ctx: Context<SweepRetained>) -> Result<()> {
+        require!(!ctx.accounts.config.killed, CapitalCallError::ProgramKilled);
+        require!(
+            ctx.accounts.capital_call.retained,
+            CapitalCallError::CapitalCallNotRetained
+        );
+
+        let signer_seeds = ctx.accounts.capital_call.signer_seeds();
+        let seeds = signer_seeds.as_slices();
+
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.vault.to_account_info(),
+                    to: ctx.accounts.destination.to_account_info(),
+                    authority: ctx.accounts.capital_call.to_account_info(),
+                },
+                &[&seeds],
+            ),
+            ctx.accounts.vault.amount,
+        )?;
+
+        token::close_account(CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            CloseAccount {
+                account: ctx.accounts.vault.to_account_info(),
+                destination: ctx.accounts.receiver.to_account_info(),
+                authority: ctx.accounts.capital_call.to_account_info(),
+            },
+            &[&seeds],
+        ))?;
+
+        let config = &mut ctx.accounts.config;
+        config.retained_vaults = config
+            .retained_vaults
+            .checked_sub(1)
+            .ok_or_else(|| error!(CapitalCallError::CalculationError))?;
+
+        ctx.accounts
+            .capital_call
+            .close(ctx.accounts.receiver.to_account_info())?;
+
+        Ok(())
+    }
+
+    /// Close fully refunded cancelled capital call with its vault and lp token pool.
+    /// Tokens transferred directly to vault are moved to `destination`.
+    pub fn close_cancelled(ctx: Context<CloseCancelled>) -> Result<()> {
+        require!(!ctx.accounts.config.killed, CapitalCallError::ProgramKilled);
+        let capital_call = &ctx.accounts.capital_call;
+        require!(
+            capital_call.is_cancelled,
+            CapitalCallError::CapitalCallNotCancelled
+        );
+        require!(
+            !capital_call.retained,
+            CapitalCallError::CapitalCallRetained
+        );
+        require!(
+            capital_call.allocated == capital_call.redeemed && capital_call.pending_amount == 0,
+            CapitalCallError::CapitalCallHasToBeFullyRefunded
+        );
+
+        let signer_seeds = capital_call.signer_seeds();
+        let seeds = signer_seeds.as_slices();
+
+        if ctx.accounts.vault.amount > 0 {
+            token::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.vault.to_account_info(),
+                        to: ctx.accounts.destination.to_account_info(),
+                        authority: ctx.accounts.capital_call.to_account_info(),
+                    },
+                    &[&seeds],
+                ),
+                ctx.accounts.vault.amount,
+            )?;
+        }
+
+        for account in [
+            ctx.accounts.vault.to_account_info(),
+            ctx.accounts.lp_token_pool.to_account_info(),
+        ] {
+            token::close_account(CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                CloseAccount {
+                    account,
+                    destination: ctx.accounts.receiver.to_account_info(),
+                    authority: ctx.accounts.capital_call.to_account_info(),
+                },
+                &[&seeds],
+            ))?;
+        }
+
+        let capacity = ctx.accounts.capital_call.capacity;
+        let config = &mut ctx.accounts.config;
+        config.total_capacity = config
+            .total_capacity
+            .checked_sub(capacity)
+            .ok_or_else(|| error!(CapitalCallError::CalculationError))?;
+        config.open_calls = config
+            .open_calls
+            .checked_sub(1)
+            .ok_or_else(|| error!(CapitalCallError::CalculationError))?;
+
+        Ok(())
+    }
+
+    /// Burn LP tokens for underlying tokens retained in vault at finalization, priced as LP
+    /// tokens were minted. LP mint is shared by all capital calls of config, so the reserve is
+    /// an exit open to any LP holder, not only to depositors of this capital call. It opens with
+    /// claims, so depositors of this capital call can redeem alongside other holders.
+    pub fn redeem(ctx: Context<Redeem>, lp_amount: u64) -> Result<()> {
+        require!(!ctx.accounts.config.killed, CapitalCallError::ProgramKilled);
+        let capital_call = &ctx.accounts.capital_call;
+        require!(!capital_call.frozen, CapitalCallError::CapitalCallFrozen);
+        require!(
+            capital_call.is_lp_minted,
+            CapitalCallError::LpTokenNotMinted
+        );
+        require!(lp_amount > 0, CapitalCallError::AmountNonZero);
+
+        let clock = Clock::get().map_err::<error::Error, _>(Into::into)?;
+        require!(
+            clock.unix_timestamp as u64
+                >= capital_call
+                    .finalized_at
+                    .checked_add(capital_call.claim_delay)
+                    .ok_or_else(|| error!(CapitalCallError::CalculationError))?,
+            CapitalCallError::ClaimNotOpenYet
+        );
+
+        let value = capital_call.token_liquidity as u128 + capital_call.credit_outstanding as u128;
+        require!(value > 0, CapitalCallError::CalculationError);
+        let amount = u64::try_from(lp_amount as u128 * capital_call.lp_supply as u128 / value)
+            .map_err(|_| error!(CapitalCallError::CalculationError))?;
+        let amount = capital_call.from_pool_units(amount)?;
+        require!(amount > 0, CapitalCallError::AmountNonZero);
+        require!(
+            amount <= capital_call.vault_reserve,
+            CapitalCallError::InsufficientVaultBalance
+        );
+
+        let signer_seeds = capital_call.signer_seeds();
+        let seeds = signer_seeds.as_slices();
+
+        token::burn(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Burn {
+                    mint: ctx.accounts.lp_mint.to_account_info(),
+                    from: ctx.accounts.source.to_account_info(),
+                    authority: ctx.accounts.authority.to_account_info(),
+                },
+            ),
+            lp_amount,
+        )?;
+
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.vault.to_account_info(),
+                    to: ctx.accounts.destination.to_account_info(),
+                    authority: ctx.accounts.capital_call.to_account_info(),
+                },
+                &[&seeds],
+            ),
+            amount,
+        )?;
+
+        ctx.accounts.capital_call.vault_reserve -= amount;
+
+        emit!(RedeemEvent {
+            program_version: PROGRAM_VERSION,
+            config: ctx.accounts.capital_call.config,
+            capital_call: ctx.accounts.capital_call.key(),
+            authority: ctx.accounts.authority.key(),
+            lp_amount,
+            amount,
+            vault_reserve: ctx.accounts.capital_call.vault_reserve,
+        });
+
+        Ok(())
+    }
+
+    /// Transfer LP tokens sent directly to lp token pool, in excess of minted and not yet
+    /// distributed LP tokens
+    pub fn recover_stranded_lp(ctx: Context<RecoverStrandedLp>) -> Result<()> {
+        require!(!ctx.accounts.config.killed, CapitalCallError::ProgramKilled);
+        let capital_call = &ctx.accounts.capital_call;
+        require!(
+            capital_call.is_lp_minted,
+            CapitalCallError::LpTokenNotMinted
+        );
+
+        let surplus = ctx
+            .accounts
+            .lp_token_pool
+            .amount
+            .checked_sub(capital_call.expected_lp_pool_balance()?)
+            .ok_or_else(|| error!(CapitalCallError::CalculationError))?;
+
+        let signer_seeds = capital_call.signer_seeds();
+        let seeds = signer_seeds.as_slices();
+
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.lp_token_pool.to_account_info(),
+                    to: ctx.accounts.destination.to_account_info(),
+                    authority: ctx.accounts.capital_call.to_account_info(),
+                },
+                &[&seeds],
+            ),
+            surplus,
+        )?;
+
+        emit!(StrandedLpRecoveredEvent {
+            program_version: PROGRAM_VERSION,
+            config: ctx.accounts.capital_call.config,
+            capital_call: ctx.accounts.capital_call.key(),
+            destination: ctx.accounts.destination.key(),
+            amount: surplus,
+        });
+
+        Ok(())
+    }
+
+    /// Record committed amount of capital call, amounts of vouchers are recorded against it by
+    /// `snapshot_vouchers`
+    pub fn snapshot(ctx: Context<TakeSnapshot>) -> Result<()> {
+        require!(!ctx.accounts.config.killed, CapitalCallError::ProgramKilled);
+        let clock = Clock::get().map_err::<error::Error, _>(Into::into)?;
+        let now = clock.unix_timestamp as u64;
+
+        let snapshot = &mut ctx.accounts.snapshot;
+        snapshot.capital_call = ctx.accounts.capital_call.key();
+        snapshot.taken_at = now;
+        snapshot.total_allocated = ctx.accounts.capital_call.allocated;
+        snapshot.bump = *ctx
+            .bumps
+            .get("snapshot")
+            .ok_or_else(|| error!(CapitalCallError::BumpSeedNotInHashMap))?;
+
+        emit!(SnapshotEvent {
+            program_version: PROGRAM_VERSION,
+            config: ctx.accounts.config.key(),
+            capital_call: ctx.accounts.capital_call.key(),
+            taken_at: now,
+            total_allocated: ctx.accounts.capital_call.allocated,
+            vouchers: 0,
+        });
+
+        Ok(())
+    }
+
+    /// Record amounts of vouchers passed in `remaining_accounts` against snapshot of capital
+    /// call, in batches of at most `MAX_BATCH_SIZE` while allocation is unchanged
+    pub fn snapshot_vouchers<'info>(
+        ctx: Context<'_, '_, '_, 'info, SnapshotVouchers<'info>>,
+    ) -> Result<()> {
+        require!(!ctx.accounts.config.killed, CapitalCallError::ProgramKilled);
+        require!(
+            ctx.remaining_accounts.len() <= MAX_BATCH_SIZE,
+            CapitalCallError::BatchTooLarge
+        );
+        require!(
+            ctx.accounts.capital_call.allocated == ctx.accounts.snapshot.total_allocated,
+            CapitalCallError::SnapshotOutdated
+        );
+
+        for voucher_info in ctx.remaining_accounts {
+            let mut voucher = Account::<Voucher>::try_from(voucher_info)?;
+            require!(
+                voucher.capital_call == ctx.accounts.capital_call.key(),
+                CapitalCallError::InvalidVoucher
+            );
+            require!(
+                !voucher.snapshotted,
+                CapitalCallError::VoucherAlreadySnapshotted
+            );
+            voucher.snapshot_amount = voucher.amount;
+            voucher.snapshotted = true;
+            voucher.exit(&crate::ID)?;
+        }
+
+        let snapshot = &mut ctx.accounts.snapshot;
+        snapshot.vouchers = snapshot
+            .vouchers
+            .checked_add(ctx.remaining_accounts.len() as u32)
+            .ok_or_else(|| error!(CapitalCallError::CalculationError))?;
+
+        emit!(SnapshotEvent {
+            program_version: PROGRAM_VERSION,
+            config: ctx.accounts.config.key(),
+            capital_call: ctx.accounts.capital_call.key(),
+            taken_at: snapshot.taken_at,
+            total_allocated: snapshot.total_allocated,
+            vouchers: snapshot.vouchers,
+        });
+
+        Ok(())
+    }
+
+    /// Emit LP tokens entitlement of each voucher passed in `remaining_accounts`
+    pub fn log_allocations<'info>(
+        ctx: Context<'_, '_, '_, 'info, LogAllocations<'info>>,
+    ) -> Result<()> {
+        require!(
+            ctx.remaining_accounts.len() <= MAX_BATCH_SIZE,
+            CapitalCallError::BatchTooLarge
+        );
+        require!(
+            ctx.accounts.capital_call.is_lp_minted,
+            CapitalCallError::LpTokenNotMinted
+        );
+
+        for voucher_info in ctx.remaining_accounts {
+            let voucher = Account::<Voucher>::try_from(voucher_info)?;
+            require!(
+                voucher.capital_call == ctx.accounts.capital_call.key(),
+                CapitalCallError::InvalidVoucher
+            );
+
+            emit!(AllocationEvent {
+                program_version: PROGRAM_VERSION,
+                config: ctx.accounts.capital_call.config,
+                capital_call: ctx.accounts.capital_call.key(),
+                voucher: voucher.key(),
+                authority: voucher.authority,
+                amount: voucher.amount,
+                computed_lp: ctx.accounts.capital_call.voucher_lp(voucher.amount)?,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Emit voucher details derived from capital call, LP amounts are zero until LP tokens
+    /// are minted
+    pub fn voucher_status(ctx: Context<VoucherStatus>) -> Result<()> {
+        let clock = Clock::get().map_err::<error::Error, _>(Into::into)?;
+        let now = clock.unix_timestamp as u64;
+
+        let capital_call = &ctx.accounts.capital_call;
+        let voucher = &ctx.accounts.voucher;
+
+        let (vested, claimable_lp) = if capital_call.is_lp_minted {
+            let total_lp = capital_call.voucher_lp(voucher.amount)?;
+            let vested = voucher.vested_lp(capital_call, total_lp, now)?;
+            let claimable_lp = vested
+                .checked_sub(voucher.claimed_lp)
+                .ok_or_else(|| error!(CapitalCallError::CalculationError))?;
+            (vested, claimable_lp)
+        } else {
+            (0, 0)
+        };
+
+        emit!(VoucherStatusEvent {
+            program_version: PROGRAM_VERSION,
+            config: capital_call.config,
+            capital_call: capital_call.key(),
+            authority: voucher.authority,
+            amount: voucher.amount,
+            claimable_lp,
+            vested,
+            is_claimable: !capital_call.frozen && claimable_lp > 0,
+        });
+
+        Ok(())
+    }
+
+    /// Check that vault holds exactly the tokens accounted by capital call
+    pub fn verify_vault(ctx: Context<VerifyVault>) -> Result<()> {
+        require!(
+            ctx.accounts.vault.amount == ctx.accounts.capital_call.expected_vault_balance()?,
+            CapitalCallError::VaultBalanceMismatch
+        );
+
+        Ok(())
+    }
+
+    /// Emit capital raised across all active capital calls of config
+    pub fn config_stats(ctx: Context<ConfigStats>) -> Result<()> {
+        emit!(ConfigStatsEvent {
+            program_version: PROGRAM_VERSION,
+            config: ctx.accounts.config.key(),
+            total_allocated: ctx.accounts.config.total_allocated,
+            total_capacity: ctx.accounts.config.total_capacity,
+        });
+
+        Ok(())
+    }
+}
+
+/// Verify gatekeeper signature checked by ed25519 program instruction preceding deposit.
+/// Signed message is capital call, depositor, max amount and expiry, returns max amount.
+fn verify_gatekeeper_allowance(
+    capital_call: &Account<CapitalCall>,
+    depositor: &Pubkey,
+    instructions: &AccountInfo,
+    now: u64,
+) -> Result<u64> {
+    let ix = get_instruction_relative(-1, instructions)
+        .map_err(|_| error!(CapitalCallError::InvalidGatekeeperSignature))?;
+    require!(
+        ix.program_id == ed25519_program::ID,
+        CapitalCallError::InvalidGatekeeperSignature
+    );
+
+    // Single signature with offsets into the same instruction
+    let data = &ix.data;
+    let read_u16 = |offset: usize| -> Result<usize> {
+        data.get(offset..offset + 2)
+            .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]) as usize)
+            .ok_or_else(|| error!(CapitalCallError::InvalidGatekeeperSignature))
+    };
+    require!(
+        data.first() == Some(&1),
+        CapitalCallError::InvalidGatekeeperSignature
+    );
+    require!(
+        read_u16(4)? == u16::MAX as usize
+            && read_u16(8)? == u16::MAX as usize
+            && read_u16(14)? == u16::MAX as usize,
+        CapitalCallError::InvalidGatekeeperSignature
+    );
+    let public_key_offset = read_u16(6)?;
+    let message_offset = read_u16(10)?;
+    let message_size = read_u16(12)?;
+
+    let public_key = data
+        .get(public_key_offset..public_key_offset + 32)
+        .ok_or_else(|| error!(CapitalCallError::InvalidGatekeeperSignature))?;
+    require!(
+        public_key == capital_call.gatekeeper.as_ref(),
+        CapitalCallError::InvalidGatekeeperSignature
+    );
+
+    require!(
+        message_size == 80,
+        CapitalCallError::InvalidGatekeeperSignature
+    );
+    let message = data
+        .get(message_offset..message_offset + message_size)
+        .ok_or_else(|| error!(CapitalCallError::InvalidGatekeeperSignature))?;
+    let max_amount = u64::from_le_bytes(message[64..72].try_into().unwrap());
+    let expiry = u64::from_le_bytes(message[72..80].try_into().unwrap());
+    require!(
+        &message[..32] == capital_call.key().as_ref()
+            && &message[32..64] == depositor.as_ref()
+            && now <= expiry,
+        CapitalCallError::InvalidGatekeeperSignature
+    );
+
+    Ok(max_amount)
+}
+
+/// Withhold referral fee of `lp_amount` if voucher has referrer.
+/// Referral fee stays in lp token pool until claimed by referrer, Referral account of referrer
+/// has to be the first of `remaining_accounts`.
+fn withhold_referral_fee<'info>(
+    capital_call: &Account<'info, CapitalCall>,
+    voucher: &Account<'info, Voucher>,
+    remaining_accounts: &[AccountInfo<'info>],
+    lp_amount: u64,
+) -> Result<u64> {
+    if voucher.referrer == Pubkey::default() {
+        return Ok(0);
+    }
+
+    let referral_fee = u64::try_from(
+        lp_amount as u128 * capital_call.referral_bps as u128 / BPS_DENOMINATOR as u128,
+    )
+    .map_err(|_| error!(CapitalCallError::CalculationError))?;
+
+    let referral_info = remaining_accounts
+        .first()
+        .ok_or_else(|| error!(CapitalCallError::InvalidReferral))?;
+    let mut referral = Account::<Referral>::try_from(referral_info)?;
+    require!(
+        referral.capital_call == capital_call.key() && referral.referrer == voucher.referrer,
+        CapitalCallError::InvalidReferral
+    );
+    referral.accrued_lp += referral_fee;
+    referral.exit(&crate::ID)?;
+
+    Ok(referral_fee)
+}
+
+/// Transfer claim fee from lp token pool to `fee_lp_account` of config
+fn transfer_claim_fee<'info>(
+    capital_call: &Account<'info, CapitalCall>,
+    lp_token_pool: &Account<'info, TokenAccount>,
+    fee_lp_account: &Account<'info, TokenAccount>,
+    token_program: &Program<'info, Token>,
+    seeds: &[&[u8]],
+    claim_fee: u64,
+) -> Result<()> {
+    if claim_fee == 0 {
+        return Ok(());
+    }
+
+    token::transfer(
+        CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            Transfer {
+                from: lp_token_pool.to_account_info(),
+                to: fee_lp_account.to_account_info(),
+                authority: capital_call.to_account_info(),
+            },
+            &[seeds],
+        ),
+        claim_fee,
+    )
+}
+
+/// Record `lp_amount` claimed to LP escrow of `authority` if capital call has `lp_lockup`,
+/// LpLockup of `authority` is the last of `remaining_accounts`. Returns locked LP tokens.
+fn lock_claimed_lp<'info>(
+    capital_call: &Account<'info, CapitalCall>,
+    authority: &Pubkey,
+    destination: &Pubkey,
+    remaining_accounts: &[AccountInfo<'info>],
+    lp_amount: u64,
+    now: u64,
+) -> Result<u64> {
+    if capital_call.lp_lockup == 0 {
+        return Ok(0);
+    }
+
+    let lockup_info = remaining_accounts
+        .last()
+        .ok_or_else(|| error!(CapitalCallError::InvalidLpLockup))?;
+    let mut lp_lockup = Account::<LpLockup>::try_from(lockup_info)?;
+    require!(
+        lp_lockup.capital_call == capital_call.key()
+            && lp_lockup.authority == *authority
+            && lp_lockup.escrow == *destination,
+        CapitalCallError::InvalidLpLockup
+    );
+
+    lp_lockup.amount = lp_lockup
+        .amount
+        .checked_add(lp_amount)
+        .ok_or_else(|| error!(CapitalCallError::CalculationError))?;
+    lp_lockup.release_at = now
+        .checked_add(capital_call.lp_lockup)
+        .ok_or_else(|| error!(CapitalCallError::CalculationError))?;
+    lp_lockup.exit(&crate::ID)?;
+
+    Ok(lp_amount)
+}
+
+/// Load credit token mint and credit token account of `depositor`, the first two of
+/// `credit_accounts`
+fn load_credit_accounts<'info>(
+    capital_call: &Account<'info, CapitalCall>,
+    depositor: &Pubkey,
+    credit_accounts: &[AccountInfo<'info>],
+) -> Result<(Account<'info, Mint>, Account<'info, TokenAccount>)> {
+    require!(
+        credit_accounts.len() >= 2,
+        CapitalCallError::InvalidCreditToken
+    );
+    let credit_mint = Account::<Mint>::try_from(&credit_accounts[0])?;
+    let credit_token = Account::<TokenAccount>::try_from(&credit_accounts[1])?;
+    require!(
+        credit_mint.key() == capital_call.credit_token_mint
+            && credit_token.mint == credit_mint.key()
+            && credit_token.owner == *depositor,
+        CapitalCallError::InvalidCreditToken
+    );
+    Ok((credit_mint, credit_token))
+}
+
+/// Burn `amount` of credit tokens of depositor if capital call has `credit_token_mint`
+fn burn_credit_tokens<'info>(
+    capital_call: &Account<'info, CapitalCall>,
+    authority: &Signer<'info>,
+    token_program: &Program<'info, Token>,
+    credit_accounts: &[AccountInfo<'info>],
+    amount: u64,
+) -> Result<()> {
+    if capital_call.credit_token_mint == Pubkey::default() {
+        return Ok(());
+    }
+
+    let (credit_mint, credit_token) =
+        load_credit_accounts(capital_call, &authority.key(), credit_accounts)?;
+    require!(
+        credit_token.amount >= amount,
+        CapitalCallError::InsufficientCreditTokens
+    );
+
+    token::burn(
+        CpiContext::new(
+            token_program.to_account_info(),
+            Burn {
+                mint: credit_mint.to_account_info(),
+                from: credit_token.to_account_info(),
+                authority: authority.to_account_info(),
+            },
+        ),
+        amount,
+    )
+}
+
+/// Check deposit `source` isn't frozen and, if `reject_delegated`, has no active delegate
+fn check_deposit_source(source: &TokenAccount, reject_delegated: bool) -> Result<()> {
+    require!(!source.is_frozen(), CapitalCallError::SourceAccountFrozen);
+    require!(
+        !reject_delegated
+            || source.delegate.is_none()
+            || source.delegated_amount == 0,
+        CapitalCallError::SourceAccountDelegated
+    );
+    Ok(())
+}
+
+/// Mint participation badge to `depositor` and freeze it, so the badge can't be transferred.
+/// Participation mint, token account of depositor and LP mint authority are the first of
+/// `participation_accounts`, frozen token account holds a badge already and gets none.
+fn mint_participation_badge<'info>(
+    config: &Account<'info, Config>,
+    depositor: &Pubkey,
+    token_program: &Program<'info, Token>,
+    participation_accounts: &[AccountInfo<'info>],
+) -> Result<()> {
+    let accounts = participation_accounts
+        .get(..3)
+        .ok_or_else(|| error!(CapitalCallError::InvalidParticipationAccount))?;
+    let participation_mint = Account::<Mint>::try_from(&accounts[0])?;
+    let participation_token = Account::<TokenAccount>::try_from(&accounts[1])?;
+    let mint_authority = &accounts[2];
+
+    let config_key = config.key();
+    let seeds = [
+        SEED_LP_MINT_AUTHORITY.as_ref(),
+        config_key.as_ref(),
+        &[config.bump],
+    ];
+    let expected_authority = Pubkey::create_program_address(&seeds, &crate::ID)
+        .map_err(|_| error!(CapitalCallError::InvalidParticipationAccount))?;
+    require!(
+        participation_mint.key() == config.participation_mint
+            && participation_mint.mint_authority == COption::Some(expected_authority)
+            && participation_mint.freeze_authority == COption::Some(expected_authority)
+            && participation_token.mint == config.participation_mint
+            && participation_token.owner == *depositor
+            && mint_authority.key() == expected_authority,
+        CapitalCallError::InvalidParticipationAccount
+    );
+    if participation_token.is_frozen() {
+        return Ok(());
+    }
+
+    token::mint_to(
+        CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            MintTo {
+                mint: participation_mint.to_account_info(),
+                to: participation_token.to_account_info(),
+                authority: mint_authority.clone(),
+            },
+            &[&seeds],
+        ),
+        1,
+    )?;
+    token::freeze_account(CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        FreezeAccount {
+            account: participation_token.to_account_info(),
+            mint: participation_mint.to_account_info(),
+            authority: mint_authority.clone(),
+        },
+        &[&seeds],
+    ))
+}
+
+/// Tier of `depositor` from its record at `tier_oracle` of config
+fn load_oracle_tier(config: &Config, depositor: &Pubkey, tier_info: &AccountInfo) -> Result<u8> {
+    let (expected, _) = Pubkey::find_program_address(
+        &[SEED_TIER.as_ref(), depositor.as_ref()],
+        &config.tier_oracle,
+    );
+    require!(
+        tier_info.key() == expected && *tier_info.owner == config.tier_oracle,
+        CapitalCallError::InvalidTierRecord
+    );
+
+    // Record is an account of oracle program, after 8 bytes of discriminator
+    let data = tier_info.try_borrow_data()?;
+    let record = data
+        .get(8..)
+        .and_then(|mut record| OracleTier::deserialize(&mut record).ok())
+        .ok_or_else(|| error!(CapitalCallError::InvalidTierRecord))?;
+    require!(
+        record.wallet == *depositor,
+        CapitalCallError::InvalidTierRecord
+    );
+
+    Ok(record.tier)
+}
+
+/// Price of `oracle` of capital call from its price account
+fn load_oracle_price(capital_call: &CapitalCall, oracle_info: &AccountInfo) -> Result<OraclePrice> {
+    require!(
+        oracle_info.key() == capital_call.oracle,
+        CapitalCallError::InvalidOracle
+    );
+
+    // Price is an account of oracle program, after 8 bytes of discriminator
+    let data = oracle_info.try_borrow_data()?;
+    data.get(8..)
+        .and_then(|mut price| OraclePrice::deserialize(&mut price).ok())
+        .ok_or_else(|| error!(CapitalCallError::InvalidOracle))
+}
+
+/// Instructions sysvar of `deposit` checked by allowed depositor program and gatekeeper
+fn load_instructions_sysvar<'a, 'info>(
+    instructions_accounts: &'a [AccountInfo<'info>],
+) -> Result<&'a AccountInfo<'info>> {
+    let instructions_info = instructions_accounts
+        .first()
+        .ok_or_else(|| error!(CapitalCallError::InvalidInstructionsSysvar))?;
+    require!(
+        instructions_info.key() == instructions::ID,
+        CapitalCallError::InvalidInstructionsSysvar
+    );
+    Ok(instructions_info)
+}
+
+/// Load `liquidity_pool` of config checked against `min_pool_balance` on deposit
+fn load_liquidity_pool<'info>(
+    config: &Config,
+    pool_accounts: &[AccountInfo<'info>],
+) -> Result<Account<'info, TokenAccount>> {
+    let pool_info = pool_accounts
+        .first()
+        .ok_or_else(|| error!(CapitalCallError::InvalidLiquidityPool))?;
+    require!(
+        pool_info.key() == config.liquidity_pool,
+        CapitalCallError::InvalidLiquidityPool
+    );
+    Account::<TokenAccount>::try_from(pool_info)
+}
+
+/// Load `match_reserve` of capital call, the first of `reserve_accounts`
+fn load_match_reserve<'info>(
+    capital_call: &Account<'info, CapitalCall>,
+    reserve_accounts: &[AccountInfo<'info>],
+) -> Result<Account<'info, TokenAccount>> {
+    let reserve_info = reserve_accounts
+        .first()
+        .ok_or_else(|| error!(CapitalCallError::InvalidMatchReserve))?;
+    let reserve = Account::<TokenAccount>::try_from(reserve_info)?;
+    require!(
+        reserve.key() == capital_call.match_reserve && reserve.key() != capital_call.vault,
+        CapitalCallError::InvalidMatchReserve
+    );
+    Ok(reserve)
+}
+
+/// Transfer `matched` tokens of voucher from vault back to match reserve, which follows
+/// credit accounts (if capital call has `credit_token_mint`) in `remaining_accounts`
+fn return_matched_tokens<'info>(
+    capital_call: &Account<'info, CapitalCall>,
+    vault: &Account<'info, TokenAccount>,
+    token_program: &Program<'info, Token>,
+    remaining_accounts: &[AccountInfo<'info>],
+    seeds: &[&[u8]],
+    matched: u64,
+) -> Result<()> {
+    if matched == 0 {
+        return Ok(());
+    }
+
+    let offset = if capital_call.credit_token_mint != Pubkey::default() {
+        2
+    } else {
+        0
+    };
+    let reserve = load_match_reserve(
+        capital_call,
+        remaining_accounts.get(offset..).unwrap_or(&[]),
+    )?;
+
+    token::transfer(
+        CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            Transfer {
+                from: vault.to_account_info(),
+                to: reserve.to_account_info(),
+                authority: capital_call.to_account_info(),
+            },
+            &[seeds],
+        ),
+        matched,
+    )
+}
+
+/// Load `secondary_vault` of capital call, the first of `vault_accounts`
+fn load_secondary_vault<'info>(
+    capital_call: &Account<'info, CapitalCall>,
+    vault_accounts: &[AccountInfo<'info>],
+) -> Result<Account<'info, TokenAccount>> {
+    let vault_info = vault_accounts
+        .first()
+        .ok_or_else(|| error!(CapitalCallError::InvalidSecondaryVault))?;
+    let vault = Account::<TokenAccount>::try_from(vault_info)?;
+    require!(
+        capital_call.secondary_vault != Pubkey::default()
+            && vault.key() == capital_call.secondary_vault,
+        CapitalCallError::InvalidSecondaryVault
+    );
+    Ok(vault)
+}
+
+/// Notify `finalize_callback_program` of config (if set) about finalized capital call.
+/// Callback program has to be the first of `remaining_accounts`, the rest are passed through.
+fn invoke_finalize_callback<'info>(
+    config: &Account<'info, Config>,
+    capital_call: &Account<'info, CapitalCall>,
+    remaining_accounts: &[AccountInfo<'info>],
+    minted: u64,
+) -> Result<()> {
+    let callback_program = config.finalize_callback_program;
+    if callback_program == Pubkey::default() {
+        return Ok(());
+    }
+
+    // Self-recursion is the only reentrancy allowed by runtime
+    require!(
+        callback_program != crate::ID,
+        CapitalCallError::InvalidFinalizeCallbackProgram
+    );
+    let (program_info, extra_accounts) = remaining_accounts
+        .split_first()
+        .ok_or_else(|| error!(CapitalCallError::InvalidFinalizeCallbackProgram))?;
+    require!(
+        program_info.key() == callback_program,
+        CapitalCallError::InvalidFinalizeCallbackProgram
+    );
+
+    // Persist finalized state before handing control to external program
+    capital_call.exit(&crate::ID)?;
+
+    let mut data = hash(FINALIZE_CALLBACK_IX_PREIMAGE).to_bytes()[..8].to_vec();
+    data.extend_from_slice(
+        &FinalizeCallbackArgs {
+            capital_call: capital_call.key(),
+            minted,
+        }
+        .try_to_vec()
+        .map_err(|_| error!(error::ErrorCode::InstructionDidNotSerialize))?,
+    );
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(capital_call.key(), false),
+        AccountMeta::new_readonly(config.key(), false),
+    ];
+    accounts.extend(extra_accounts.iter().map(|account| {
+        if account.is_writable {
+            AccountMeta::new(account.key(), account.is_signer)
+        } else {
+            AccountMeta::new_readonly(account.key(), account.is_signer)
+        }
+    }));
+
+    let mut account_infos = vec![capital_call.to_account_info(), config.to_account_info()];
+    account_infos.extend_from_slice(extra_accounts);
+    account_infos.push(program_info.clone());
+
+    invoke(
+        &Instruction {
+            program_id: callback_program,
+            accounts,
+            data,
+        },
+        &account_infos,
+    )?;
+
+    Ok(())
+}
+
+/// Check parameters of new capital call against config. `debt_info` is token account of
+/// `debt_account` if config has it.
+fn validate_create_params(
+    config: &Config,
+    params: &CreateParams,
+    debt_info: Option<&AccountInfo>,
+    now: u64,
+) -> Result<()> {
+    require!(
+        params.start_time >= now,
+        CapitalCallError::StartTimeMustBeInFuture
+    );
+    require!(
+        params.start_time
+            >= now
+                .checked_add(config.min_lead_time)
+                .ok_or_else(|| error!(CapitalCallError::CalculationError))?,
+        CapitalCallError::StartTimeTooSoon
+    );
+    require!(params.duration > 0, CapitalCallError::DurationNonZero);
+    // Slots are estimated from duration by default slot time
+    require!(
+        params.duration as u128 * 1_000 / DEFAULT_MS_PER_SLOT as u128
+            >= config.min_window_slots as u128,
+        CapitalCallError::WindowTooShort
+    );
+    require!(params.capacity > 0, CapitalCallError::CapacityNonZero);
+    require!(
+        params.capacity >= config.min_capacity
+            && (config.max_capacity == 0 || params.capacity <= config.max_capacity),
+        CapitalCallError::CapacityOutOfRange
+    );
+    require!(
+        params.credit_outstanding >= config.min_credit_outstanding,
+        CapitalCallError::CreditTooLow
+    );
+    if config.debt_account != Pubkey::default() {
+        let debt_info = debt_info.ok_or_else(|| error!(CapitalCallError::InvalidDebtAccount))?;
+        require!(
+            debt_info.key() == config.debt_account,
+            CapitalCallError::InvalidDebtAccount
+        );
+        let debt = Account::<TokenAccount>::try_from(debt_info)?;
+        require!(
+            params.credit_outstanding == debt.amount,
+            CapitalCallError::CreditOutstandingMismatch
+        );
+    }
+    require!(
+        config.max_concurrent_calls == 0 || config.open_calls < config.max_concurrent_calls,
+        CapitalCallError::TooManyOpenCalls
+    );
+
+    Ok(())
+}
+
+/// Create account at program derived address with `seeds`, rent is paid by `payer`
+fn create_pda_account<'info>(
+    payer: &Signer<'info>,
+    account: &AccountInfo<'info>,
+    system_program: &Program<'info, System>,
+    rent: &Rent,
+    space: usize,
+    owner: &Pubkey,
+    seeds: &[&[u8]],
+) -> Result<()> {
+    system_program::create_account(
+        CpiContext::new_with_signer(
+            system_program.to_account_info(),
+            system_program::CreateAccount {
+                from: payer.to_account_info(),
+                to: account.clone(),
+            },
+            &[seeds],
+        ),
+        rent.minimum_balance(space),
+        space as u64,
+        owner,
+    )
+}
+
+/// Remaining accounts of `deposit` grouped in order they are passed, group of disabled feature
+/// is empty
+struct DepositAccounts<'a, 'info> {
+    // Registered Referral account of `referrer`, if set
+    referral: &'a [AccountInfo<'info>],
+    // Credit token mint and credit token account of depositor, if capital call has
+    // `credit_token_mint`. Deposited amount of credit tokens is minted.
+    credit: &'a [AccountInfo<'info>],
+    // Match reserve, if capital call has `match_reserve`. Matched amount is limited by reserve
+    // balance and delegated amount, so exhausted reserve matches nothing.
+    match_reserve: &'a [AccountInfo<'info>],
+    // Stake token account of depositor, if capital call has `stake_mint`. Deposited amount is
+    // capped at multiple of its balance.
+    stake: &'a [AccountInfo<'info>],
+    // Tier record of depositor, if config has `tier_oracle`. Its tier is stored on voucher.
+    tier: &'a [AccountInfo<'info>],
+    // Participation mint, participation token account of depositor and LP mint authority, if
+    // config has `participation_mint`. Depositor without badge gets one.
+    participation: &'a [AccountInfo<'info>],
+    // Instructions sysvar, if capital call has `allowed_depositor_program` or `gatekeeper`
+    instructions: &'a [AccountInfo<'info>],
+    // Liquidity pool of config, if config has `min_pool_balance`
+    liquidity_pool: &'a [AccountInfo<'info>],
+}
+
+/// Split `remaining_accounts` of `deposit` into groups of enabled features, missing accounts
+/// leave groups short and are rejected by their loaders
+fn split_deposit_accounts<'a, 'info>(
+    config: &Config,
+    capital_call: &CapitalCall,
+    has_referrer: bool,
+    remaining_accounts: &'a [AccountInfo<'info>],
+) -> DepositAccounts<'a, 'info> {
+    let mut rest = remaining_accounts;
+    let mut take = |enabled: bool, count: usize| -> &'a [AccountInfo<'info>] {
+        let count = if enabled { count.min(rest.len()) } else { 0 };
+        let (group, tail) = rest.split_at(count);
+        rest = tail;
+        group
+    };
+
+    DepositAccounts {
+        referral: take(has_referrer, 1),
+        credit: take(capital_call.credit_token_mint != Pubkey::default(), 2),
+        match_reserve: take(capital_call.is_match_enabled(), 1),
+        stake: take(capital_call.stake_mint != Pubkey::default(), 1),
+        tier: take(config.tier_oracle != Pubkey::default(), 1),
+        participation: take(config.participation_mint != Pubkey::default(), 3),
+        instructions: take(
+            capital_call.allowed_depositor_program != Pubkey::default()
+                || capital_call.gatekeeper != Pubkey::default(),
+            1,
+        ),
+        liquidity_pool: take(config.min_pool_balance > 0, 1),
+    }
+}
+
+/// Split yield strategy accounts off the end of `remaining_accounts`, none if capital call
+/// doesn't have `yield_program`
+fn split_yield_accounts<'a, 'info>(
+    capital_call: &CapitalCall,
+    remaining_accounts: &'a [AccountInfo<'info>],
+) -> Result<(&'a [AccountInfo<'info>], &'a [AccountInfo<'info>])> {
+    if capital_call.yield_program == Pubkey::default() {
+        return Ok((remaining_accounts, &[]));
+    }
+    let split = remaining_accounts
+        .len()
+        .checked_sub(YIELD_STRATEGY_ACCOUNTS)
+        .ok_or_else(|| error!(CapitalCallError::InvalidYieldProgram))?;
+    let (accounts, yield_accounts) = remaining_accounts.split_at(split);
+    require!(
+        yield_accounts[0].key() == capital_call.yield_program,
+        CapitalCallError::InvalidYieldProgram
+    );
+
+    Ok((accounts, yield_accounts))
+}
+
+/// Invoke `yield_program` of capital call with capital call signing for vault.
+/// Instruction accounts are capital call, vault and token program followed by money market
+/// accounts.
+fn invoke_yield_program<'info>(
+    capital_call: &Account<'info, CapitalCall>,
+    vault: &Account<'info, TokenAccount>,
+    token_program: &Program<'info, Token>,
+    yield_accounts: &[AccountInfo<'info>],
+    data: Vec<u8>,
+) -> Result<()> {
+    let (program_info, market_accounts) = yield_accounts
+        .split_first()
+        .ok_or_else(|| error!(CapitalCallError::InvalidYieldProgram))?;
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(capital_call.key(), true),
+        AccountMeta::new(vault.key(), false),
+        AccountMeta::new_readonly(token_program.key(), false),
+    ];
+    accounts.extend(market_accounts.iter().map(|account| {
+        if account.is_writable {
+            AccountMeta::new(account.key(), account.is_signer)
+        } else {
+            AccountMeta::new_readonly(account.key(), account.is_signer)
+        }
+    }));
+
+    let mut account_infos = vec![
+        capital_call.to_account_info(),
+        vault.to_account_info(),
+        token_program.to_account_info(),
+    ];
+    account_infos.extend_from_slice(market_accounts);
+    account_infos.push(program_info.clone());
+
+    let signer_seeds = capital_call.signer_seeds();
+    let seeds = signer_seeds.as_slices();
+
+    invoke_signed(
+        &Instruction {
+            program_id: capital_call.yield_program,
+            accounts,
+            data,
+        },
+        &account_infos,
+        &[&seeds],
+    )?;
+
+    Ok(())
+}
+
+/// Move `amount` of deposited tokens from vault to `yield_program` of capital call
+fn deposit_yield<'info>(
+    capital_call: &mut Account<'info, CapitalCall>,
+    vault: &Account<'info, TokenAccount>,
+    token_program: &Program<'info, Token>,
+    yield_accounts: &[AccountInfo<'info>],
+    amount: u64,
+) -> Result<()> {
+    let mut data = hash(YIELD_DEPOSIT_IX_PREIMAGE).to_bytes()[..8].to_vec();
+    data.extend_from_slice(&amount.to_le_bytes());
+    invoke_yield_program(capital_call, vault, token_program, yield_accounts, data)?;
+
+    capital_call.yield_principal = capital_call
+        .yield_principal
+        .checked_add(amount)
+        .ok_or_else(|| error!(CapitalCallError::CalculationError))?;
+
+    Ok(())
+}
+
+/// Withdraw all tokens of capital call from `yield_program` to vault, yield program has to
+/// return at least the principal
+fn withdraw_yield<'info>(
+    capital_call: &mut Account<'info, CapitalCall>,
+    vault: &mut Account<'info, TokenAccount>,
+    token_program: &Program<'info, Token>,
+    yield_accounts: &[AccountInfo<'info>],
+) -> Result<()> {
+    vault.reload()?;
+    let balance = vault.amount;
+
+    let data = hash(YIELD_WITHDRAW_IX_PREIMAGE).to_bytes()[..8].to_vec();
+    invoke_yield_program(capital_call, vault, token_program, yield_accounts, data)?;
+
+    vault.reload()?;
+    let principal = capital_call.yield_principal;
+    let earned = vault
+        .amount
+        .checked_sub(balance)
+        .and_then(|returned| returned.checked_sub(principal))
+        .ok_or_else(|| error!(CapitalCallError::YieldPrincipalNotReturned))?;
+
+    capital_call.yield_principal = 0;
+    capital_call.yield_earned = capital_call
+        .yield_earned
+        .checked_add(earned)
+        .ok_or_else(|| error!(CapitalCallError::CalculationError))?;
+
+    emit!(YieldWithdrawnEvent {
+        program_version: PROGRAM_VERSION,
+        config: capital_call.config,
+        capital_call: capital_call.key(),
+        principal,
+        earned,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct Initialize<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = Config::SPACE,
+    )]
+    pub config: Account<'info, Config>,
+
+    /// CHECK: Only for key
+    pub authority: UncheckedAccount<'info>,
+
+    /// CHECK: Only for bump calculation
+    #[account(
+        seeds = [
+            SEED_LP_MINT_AUTHORITY.as_ref(),
+            config.key().as_ref(),
+        ], bump
+    )]
+    pub lp_mint_authority: UncheckedAccount<'info>,
+
+    pub lp_mint: Account<'info, Mint>,
+
+    #[account(
+        constraint = liquidity_pool.mint == MINT_PUBKEY.parse::<Pubkey>().unwrap()
+    )]
+    pub liquidity_pool: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    #[account(mut, has_one = authority)]
+    pub config: Account<'info, Config>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetKilled<'info> {
+    #[account(mut)]
+    pub config: Account<'info, Config>,
+
+    // Config authority or guardian
+    #[account(
+        constraint = authority.key() == config.authority
+            || authority.key() == config.guardian @ CapitalCallError::InvalidAuthority,
+    )]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseConfig<'info> {
+    #[account(mut, has_one = authority, close = receiver)]
+    pub config: Account<'info, Config>,
+
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub receiver: SystemAccount<'info>,
+}
+
+/// Arguments of `create_capital_call` for each capital call of `create_capital_calls_batch`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct CreateParams {
+    pub start_time: u64,
+    pub duration: u64,
+    pub capacity: u64,
+    pub credit_outstanding: u64,
+    pub idempotency_key: [u8; 16],
+    pub manager: Pubkey,
+    pub refund_lockup: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct ConfigParams {
+    pub finalize_callback_program: Option<Pubkey>,
+    pub claim_fee_bps: Option<u16>,
+    pub fee_lp_account: Option<Pubkey>,
+    pub min_credit_outstanding: Option<u64>,
+    pub min_pool_balance: Option<u64>,
+    pub min_claim: Option<u64>,
+    pub max_concurrent_calls: Option<u32>,
+    pub authority_bypass_whitelist: Option<bool>,
+    pub ata_destinations: Option<bool>,
+    pub min_lead_time: Option<u64>,
+    pub max_finalize_transfer: Option<u64>,
+    pub round_up_claims: Option<bool>,
+    pub debt_account: Option<Pubkey>,
+    pub yield_program: Option<Pubkey>,
+    pub max_partial_claims: Option<u8>,
+    pub min_open_before_cancel: Option<u64>,
+    pub min_window_slots: Option<u64>,
+    pub min_capacity: Option<u64>,
+    pub max_capacity: Option<u64>,
+    pub tier_oracle: Option<Pubkey>,
+    pub rent_to_payer: Option<bool>,
+    pub reject_delegated_source: Option<bool>,
+    pub participation_mint: Option<Pubkey>,
+    pub surplus_treasury: Option<Pubkey>,
+    pub min_fill_to_extend_bps: Option<u16>,
+    pub strict_claim_destination: Option<bool>,
+    pub void_treasury: Option<Pubkey>,
+}
+
+#[derive(Accounts)]
+#[instruction(
+    start_time: u64,
+    duration: u64,
+    capacity: u64,
+    credit_outstanding: u64,
+    idempotency_key: [u8; 16],
+    manager: Pubkey,
+    refund_lockup: u64,
+)]
+pub struct CreateCapitalCall<'info> {
+    #[account(
+        mut,
+        has_one = authority,
+        has_one = lp_mint,
+    )]
+    pub config: Box<Account<'info, Config>>,
+
+    // Retried creation finds capital call initialized, before vault and LP token pool `init`
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = CapitalCall::SPACE,
+        seeds = [
+            SEED_CAPITAL_CALL.as_ref(),
+            config.key().as_ref(),
+            start_time.to_le_bytes().as_ref(),
+            capacity.to_le_bytes().as_ref(),
+            idempotency_key.as_ref(),
+        ],
+        bump,
+        constraint = capital_call.config == Pubkey::default()
+            @ CapitalCallError::DuplicateCapitalCall,
+    )]
+    pub capital_call: Box<Account<'info, CapitalCall>>,
+
+    #[account(
+        init,
+        payer = payer,
+        token::mint = mint,
+        token::authority = capital_call,
+        seeds = [
+            SEED_VAULT.as_ref(),
+            capital_call.key().as_ref(),
+        ],
+        bump
+    )]
+    pub vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(address = MINT_PUBKEY.parse::<Pubkey>().unwrap())]
+    pub mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        init,
+        payer = payer,
+        token::mint = lp_mint,
+        token::authority = capital_call,
+        seeds = [
+            SEED_LP_TOKEN_POOL.as_ref(),
+            capital_call.key().as_ref(),
+        ], bump
+    )]
+    pub lp_token_pool: Box<Account<'info, TokenAccount>>,
+    pub lp_mint: Box<Account<'info, Mint>>,
+
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct CreateCapitalCallsBatch<'info> {
+    #[account(
+        mut,
+        has_one = authority,
+        has_one = lp_mint,
+    )]
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(address = MINT_PUBKEY.parse::<Pubkey>().unwrap())]
+    pub mint: Box<Account<'info, Mint>>,
+    pub lp_mint: Box<Account<'info, Mint>>,
+
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateCapitalCall<'info> {
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_CAPITAL_CALL.as_ref(),
+            capital_call.config.as_ref(),
+            capital_call.start_time.to_le_bytes().as_ref(),
+            capital_call.capacity.to_le_bytes().as_ref(),
+            capital_call.idempotency_key.as_ref(),
+        ],
+        bump = capital_call.bump,
+        has_one = config,
+    )]
+    pub capital_call: Account<'info, CapitalCall>,
+
+    // Config authority or manager of capital call
+    #[account(
+        constraint = authority.key() == config.authority
+            || authority.key() == capital_call.manager @ CapitalCallError::InvalidAuthority,
+    )]
+    pub authority: Signer<'info>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct BonusTier {
+    pub min_amount: u64,
+    pub bonus_bps: u16,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct UnlockPoint {
+    pub timestamp: u64,
+    pub bps: u16,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct CapitalCallParams {
+    pub start_slot: Option<u64>,
+    pub refund_cooldown: Option<u64>,
+    pub referral_bps: Option<u16>,
+    pub progress_event_step_bps: Option<u16>,
+    pub vest_start: Option<u64>,
+    pub vest_cliff: Option<u64>,
+    pub vest_duration: Option<u64>,
+    pub credit_token_mint: Option<Pubkey>,
+    pub max_fill_bps_per_tx: Option<u16>,
+    pub allow_clamp: Option<bool>,
+    pub min_rate: Option<u64>,
+    pub max_rate: Option<u64>,
+    pub custodial_vault: Option<bool>,
+    pub custodian: Option<Pubkey>,
+    pub min_deposit: Option<u64>,
+    pub stake_mint: Option<Pubkey>,
+    pub stake_cap_multiplier: Option<u64>,
+    pub bonus_tiers: Option<Vec<BonusTier>>,
+    pub finalize_window: Option<u64>,
+    pub allowed_depositor_program: Option<Pubkey>,
+    pub gatekeeper: Option<Pubkey>,
+    pub auto_extend_threshold_bps: Option<u16>,
+    pub auto_extend_duration: Option<u64>,
+    pub refund_to_source: Option<bool>,
+    pub refund_to_source_owner: Option<bool>,
+    pub match_reserve: Option<Pubkey>,
+    pub match_bps: Option<u16>,
+    pub voucher_expiry: Option<u64>,
+    pub confirmation_slots: Option<u64>,
+    pub reserve_bps: Option<u16>,
+    pub claim_delay: Option<u64>,
+    pub finalize_cooldown: Option<u64>,
+    pub twap_release_duration: Option<u64>,
+    pub yield_strategy: Option<bool>,
+    pub fast_fill_threshold: Option<u64>,
+    pub fast_fill_fee_bps: Option<u16>,
+    pub slow_fill_fee_bps: Option<u16>,
+    pub max_valuation_age: Option<u64>,
+    pub lp_lockup: Option<u64>,
+    pub unlock_points: Option<Vec<UnlockPoint>>,
+    pub finalize_chunk: Option<u64>,
+    pub strict_vault_balance: Option<bool>,
+    pub use_oracle_conversion: Option<bool>,
+    pub oracle: Option<Pubkey>,
+    pub oracle_max_age: Option<u64>,
+    pub oracle_max_conf_bps: Option<u16>,
+}
+
+#[derive(Accounts)]
+pub struct SetCallFrozen<'info> {
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_CAPITAL_CALL.as_ref(),
+            capital_call.config.as_ref(),
+            capital_call.start_time.to_le_bytes().as_ref(),
+            capital_call.capacity.to_le_bytes().as_ref(),
+            capital_call.idempotency_key.as_ref(),
+        ],
+        bump = capital_call.bump,
+        has_one = config,
+    )]
+    pub capital_call: Account<'info, CapitalCall>,
+
+    // Config authority, guardian or manager of capital call
+    #[account(
+        constraint = authority.key() == config.authority
+            || authority.key() == config.guardian
+            || authority.key() == capital_call.manager @ CapitalCallError::InvalidAuthority,
+    )]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetFinalizationPaused<'info> {
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_CAPITAL_CALL.as_ref(),
+            capital_call.config.as_ref(),
+            capital_call.start_time.to_le_bytes().as_ref(),
+            capital_call.capacity.to_le_bytes().as_ref(),
+            capital_call.idempotency_key.as_ref(),
+        ],
+        bump = capital_call.bump,
+        has_one = config,
+    )]
+    pub capital_call: Account<'info, CapitalCall>,
+
+    // Config authority, guardian or manager of capital call
+    #[account(
+        constraint = authority.key() == config.authority
+            || authority.key() == config.guardian
+            || authority.key() == capital_call.manager @ CapitalCallError::InvalidAuthority,
+    )]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateValuation<'info> {
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_CAPITAL_CALL.as_ref(),
+            capital_call.config.as_ref(),
+            capital_call.start_time.to_le_bytes().as_ref(),
+            capital_call.capacity.to_le_bytes().as_ref(),
+            capital_call.idempotency_key.as_ref(),
+        ],
+        bump = capital_call.bump,
+        has_one = config,
+    )]
+    pub capital_call: Account<'info, CapitalCall>,
+
+    // Config authority or manager of capital call
+    #[account(
+        constraint = authority.key() == config.authority
+            || authority.key() == capital_call.manager @ CapitalCallError::InvalidAuthority,
+    )]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CancelCapitalCall<'info> {
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_CAPITAL_CALL.as_ref(),
+            capital_call.config.as_ref(),
+            capital_call.start_time.to_le_bytes().as_ref(),
+            capital_call.capacity.to_le_bytes().as_ref(),
+            capital_call.idempotency_key.as_ref(),
+        ],
+        bump = capital_call.bump,
+        has_one = config,
+    )]
+    pub capital_call: Account<'info, CapitalCall>,
+
+    // Config authority, guardian or manager of capital call
+    #[account(
+        constraint = authority.key() == config.authority
+            || authority.key() == config.guardian
+            || authority.key() == capital_call.manager @ CapitalCallError::InvalidAuthority,
+    )]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CancelAndRefund<'info> {
+    #[account(mut)]
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_CAPITAL_CALL.as_ref(),
+            capital_call.config.as_ref(),
+            capital_call.start_time.to_le_bytes().as_ref(),
+            capital_call.capacity.to_le_bytes().as_ref(),
+            capital_call.idempotency_key.as_ref(),
+        ],
+        bump = capital_call.bump,
+        has_one = config,
+    )]
+    pub capital_call: Account<'info, CapitalCall>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_VAULT.as_ref(),
+            capital_call.key().as_ref(),
+        ],
+        bump = capital_call.vault_bump,
+    )]
+    pub vault: Account<'info, TokenAccount>,
+
+    // Config authority or manager of capital call, guardian doesn't move funds
+    #[account(
+        constraint = authority.key() == config.authority
+            || authority.key() == capital_call.manager @ CapitalCallError::InvalidAuthority,
+    )]
+    pub authority: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct SetRefundHaircut<'info> {
+    #[account(has_one = authority)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_CAPITAL_CALL.as_ref(),
+            capital_call.config.as_ref(),
+            capital_call.start_time.to_le_bytes().as_ref(),
+            capital_call.capacity.to_le_bytes().as_ref(),
+            capital_call.idempotency_key.as_ref(),
+        ],
+        bump = capital_call.bump,
+        has_one = config,
+    )]
+    pub capital_call: Account<'info, CapitalCall>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ShortenDuration<'info> {
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_CAPITAL_CALL.as_ref(),
+            capital_call.config.as_ref(),
+            capital_call.start_time.to_le_bytes().as_ref(),
+            capital_call.capacity.to_le_bytes().as_ref(),
+            capital_call.idempotency_key.as_ref(),
+        ],
+        bump = capital_call.bump,
+        has_one = config,
+    )]
+    pub capital_call: Account<'info, CapitalCall>,
+
+    // Config authority or manager of capital call
+    #[account(
+        constraint = authority.key() == config.authority
+            || authority.key() == capital_call.manager @ CapitalCallError::InvalidAuthority,
+    )]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct TryAutoExtend<'info> {
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_CAPITAL_CALL.as_ref(),
+            capital_call.config.as_ref(),
+            capital_call.start_time.to_le_bytes().as_ref(),
+            capital_call.capacity.to_le_bytes().as_ref(),
+            capital_call.idempotency_key.as_ref(),
+        ],
+        bump = capital_call.bump,
+        has_one = config,
+    )]
+    pub capital_call: Account<'info, CapitalCall>,
+}
+
+#[derive(Accounts)]
+pub struct Deposit<'info> {
+    #[account(mut)]
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_CAPITAL_CALL.as_ref(),
+            capital_call.config.as_ref(),
+            capital_call.start_time.to_le_bytes().as_ref(),
+            capital_call.capacity.to_le_bytes().as_ref(),
+            capital_call.idempotency_key.as_ref(),
+        ],
+        bump = capital_call.bump,
+        has_one = config,
+    )]
+    pub capital_call: Account<'info, CapitalCall>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = Voucher::SPACE,
+        seeds = [
+            SEED_VOUCHER.as_ref(),
+            capital_call.key().as_ref(),
+            authority.key().as_ref(),
+        ],
+        bump
+    )]
+    pub voucher: Account<'info, Voucher>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_VAULT.as_ref(),
+            capital_call.key().as_ref(),
+        ],
+        bump = capital_call.vault_bump,
+    )]
+    pub vault: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub source: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'a, 'b, 'c, 'info> From<&mut Deposit<'info>>
+    for CpiContext<'a, 'b, 'c, 'info, Transfer<'info>>
+{
+    fn from(accounts: &mut Deposit<'info>) -> CpiContext<'a, 'b, 'c, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: accounts.source.to_account_info(),
+            to: accounts.vault.to_account_info(),
+            authority: accounts.authority.to_account_info(),
+        };
+        let cpi_program = accounts.token_program.to_account_info();
+        CpiContext::new(cpi_program, cpi_accounts)
+    }
+}
+
+#[derive(Accounts)]
+pub struct InitSecondaryVault<'info> {
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_CAPITAL_CALL.as_ref(),
+            capital_call.config.as_ref(),
+            capital_call.start_time.to_le_bytes().as_ref(),
+            capital_call.capacity.to_le_bytes().as_ref(),
+            capital_call.idempotency_key.as_ref(),
+        ],
+        bump = capital_call.bump,
+        has_one = config,
+        has_one = vault,
+    )]
+    pub capital_call: Box<Account<'info, CapitalCall>>,
+
+    #[account(has_one = mint)]
+    pub vault: Box<Account<'info, TokenAccount>>,
+    pub mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        init,
+        payer = payer,
+        token::mint = secondary_mint,
+        token::authority = capital_call,
+        seeds = [
+            SEED_SECONDARY_VAULT.as_ref(),
+            capital_call.key().as_ref(),
+        ],
+        bump
+    )]
+    pub secondary_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        constraint = secondary_mint.key() != mint.key() @ CapitalCallError::InvalidSecondaryVault,
+    )]
+    pub secondary_mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        constraint = secondary_liquidity_pool.mint == secondary_mint.key()
+            @ CapitalCallError::InvalidSecondaryVault,
+    )]
+    pub secondary_liquidity_pool: Box<Account<'info, TokenAccount>>,
+
+    // Config authority or manager of capital call
+    #[account(
+        constraint = authority.key() == config.authority
+            || authority.key() == capital_call.manager @ CapitalCallError::InvalidAuthority,
+    )]
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct DepositSecondary<'info> {
+    #[account(mut)]
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_CAPITAL_CALL.as_ref(),
+            capital_call.config.as_ref(),
+            capital_call.start_time.to_le_bytes().as_ref(),
+            capital_call.capacity.to_le_bytes().as_ref(),
+            capital_call.idempotency_key.as_ref(),
+        ],
+        bump = capital_call.bump,
+        has_one = config,
+        has_one = secondary_vault,
+    )]
+    pub capital_call: Account<'info, CapitalCall>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = Voucher::SPACE,
+        seeds = [
+            SEED_VOUCHER.as_ref(),
+            capital_call.key().as_ref(),
+            authority.key().as_ref(),
+        ],
+        bump
+    )]
+    pub voucher: Account<'info, Voucher>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_SECONDARY_VAULT.as_ref(),
+            capital_call.key().as_ref(),
+        ],
+        bump = capital_call.secondary_vault_bump,
+    )]
+    pub secondary_vault: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub source: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ConfirmDeposit<'info> {
+    #[account(mut)]
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_CAPITAL_CALL.as_ref(),
+            capital_call.config.as_ref(),
+            capital_call.start_time.to_le_bytes().as_ref(),
+            capital_call.capacity.to_le_bytes().as_ref(),
+            capital_call.idempotency_key.as_ref(),
+        ],
+        bump = capital_call.bump,
+        has_one = config,
+    )]
+    pub capital_call: Account<'info, CapitalCall>,
+
+    #[account(mut, has_one = capital_call)]
+    pub voucher: Account<'info, Voucher>,
+}
+
+#[derive(Accounts)]
+pub struct Refund<'info> {
+    #[account(mut)]
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_CAPITAL_CALL.as_ref(),
+            capital_call.config.as_ref(),
+            capital_call.start_time.to_le_bytes().as_ref(),
+            capital_call.capacity.to_le_bytes().as_ref(),
+            capital_call.idempotency_key.as_ref(),
+        ],
+        bump = capital_call.bump,
+        has_one = config,
+    )]
+    pub capital_call: Account<'info, CapitalCall>,
+
+    #[account(
+        mut,
+        close = authority,
+        seeds = [
+            SEED_VOUCHER.as_ref(),
+            capital_call.key().as_ref(),
+            authority.key().as_ref(),
+        ],
+        bump = voucher.bump,
+        has_one = authority,
+        has_one = capital_call,
+    )]
+    pub voucher: Account<'info, Voucher>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_VAULT.as_ref(),
+            capital_call.key().as_ref(),
+        ],
+        bump = capital_call.vault_bump,
+    )]
+    pub vault: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = !config.ata_destinations
+            || destination.key() == get_associated_token_address(&authority.key(), &vault.mint)
+            @ CapitalCallError::InvalidDestination,
+    )]
+    pub destination: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+impl<'a, 'b, 'c, 'info> From<&mut Refund<'info>>
+    for CpiContext<'a, 'b, 'c, 'info, Transfer<'info>>
+{
+    fn from(accounts: &mut Refund<'info>) -> CpiContext<'a, 'b, 'c, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: accounts.vault.to_account_info(),
+            to: accounts.destination.to_account_info(),
+            authority: accounts.capital_call.to_account_info(),
+        };
+        let cpi_program = accounts.token_program.to_account_info();
+        CpiContext::new(cpi_program, cpi_accounts)
+    }
+}
+
+// Unlike `Refund` voucher is not closed
+#[derive(Accounts)]
+pub struct PreviewRefund<'info> {
+    #[account(
+        seeds = [
+            SEED_CAPITAL_CALL.as_ref(),
+            capital_call.config.as_ref(),
+            capital_call.start_time.to_le_bytes().as_ref(),
+            capital_call.capacity.to_le_bytes().as_ref(),
+            capital_call.idempotency_key.as_ref(),
+        ],
+        bump = capital_call.bump,
+    )]
+    pub capital_call: Account<'info, CapitalCall>,
+
+    #[account(has_one = capital_call)]
+    pub voucher: Account<'info, Voucher>,
+}
+
+#[derive(Accounts)]
+pub struct Withdraw<'info> {
+    #[account(mut)]
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_CAPITAL_CALL.as_ref(),
+            capital_call.config.as_ref(),
+            capital_call.start_time.to_le_bytes().as_ref(),
+            capital_call.capacity.to_le_bytes().as_ref(),
+            capital_call.idempotency_key.as_ref(),
+        ],
+        bump = capital_call.bump,
+        has_one = config,
+    )]
+    pub capital_call: Account<'info, CapitalCall>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_VOUCHER.as_ref(),
+            capital_call.key().as_ref(),
+            authority.key().as_ref(),
+        ],
+        bump = voucher.bump,
+        has_one = authority,
+        has_one = capital_call,
+    )]
+    pub voucher: Account<'info, Voucher>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_VAULT.as_ref(),
+            capital_call.key().as_ref(),
+        ],
+        bump = capital_call.vault_bump,
+    )]
+    pub vault: Account<'info, TokenAccount>,
+
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub destination: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct MintLpTokens<'info> {
+    #[account(
+        has_one = lp_mint,
+        has_one = lp_mint_authority,
+        has_one = liquidity_pool,
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_CAPITAL_CALL.as_ref(),
+            capital_call.config.as_ref(),
+            capital_call.start_time.to_le_bytes().as_ref(),
+            capital_call.capacity.to_le_bytes().as_ref(),
+            capital_call.idempotency_key.as_ref(),
+        ],
+        bump = capital_call.bump,
+        has_one = config,
+        has_one = lp_token_pool,
+    )]
+    pub capital_call: Account<'info, CapitalCall>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_VAULT.as_ref(),
+            capital_call.key().as_ref(),
+        ],
+        bump = capital_call.vault_bump,
+    )]
+    pub vault: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub liquidity_pool: Account<'info, TokenAccount>,
+
+    /// CHECK: Only for bump calculation
+    #[account(
+        seeds = [
+            SEED_LP_MINT_AUTHORITY.as_ref(),
+            config.key().as_ref(),
+        ], bump = config.bump
+    )]
+    pub lp_mint_authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_LP_TOKEN_POOL.as_ref(),
+            capital_call.key().as_ref(),
+        ],
+        bump = capital_call.lp_token_pool_bump
+    )]
+    pub lp_token_pool: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub lp_mint: Account<'info, Mint>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct Unwind<'info> {
+    #[account(
+        has_one = authority,
+        has_one = lp_mint,
+        has_one = liquidity_pool,
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_CAPITAL_CALL.as_ref(),
+            capital_call.config.as_ref(),
+            capital_call.start_time.to_le_bytes().as_ref(),
+            capital_call.capacity.to_le_bytes().as_ref(),
+            capital_call.idempotency_key.as_ref(),
+        ],
+        bump = capital_call.bump,
+        has_one = config,
+        has_one = vault,
+        has_one = lp_token_pool,
+    )]
+    pub capital_call: Account<'info, CapitalCall>,
+
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub liquidity_pool: Account<'info, TokenAccount>,
+
+    #[account(
+        constraint = pool_authority.key() == liquidity_pool.owner @ CapitalCallError::InvalidAuthority,
+    )]
+    pub pool_authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_VAULT.as_ref(),
+            capital_call.key().as_ref(),
+        ],
+        bump = capital_call.vault_bump,
+    )]
+    pub vault: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_LP_TOKEN_POOL.as_ref(),
+            capital_call.key().as_ref(),
+        ],
+        bump = capital_call.lp_token_pool_bump
+    )]
+    pub lp_token_pool: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub lp_mint: Account<'info, Mint>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct FinalizeAndDistribute<'info> {
+    #[account(
+        mut,
+        has_one = authority,
+        has_one = lp_mint,
+        has_one = lp_mint_authority,
+        has_one = liquidity_pool,
+    )]
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_CAPITAL_CALL.as_ref(),
+            capital_call.config.as_ref(),
+            capital_call.start_time.to_le_bytes().as_ref(),
+            capital_call.capacity.to_le_bytes().as_ref(),
+            capital_call.idempotency_key.as_ref(),
+        ],
+        bump = capital_call.bump,
+        has_one = config,
+        has_one = vault,
+        has_one = lp_token_pool,
+    )]
+    pub capital_call: Box<Account<'info, CapitalCall>>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_VAULT.as_ref(),
+            capital_call.key().as_ref(),
+        ],
+        bump = capital_call.vault_bump,
+    )]
+    pub vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub liquidity_pool: Box<Account<'info, TokenAccount>>,
+
+    /// CHECK: Only for bump calculation
+    #[account(
+        seeds = [
+            SEED_LP_MINT_AUTHORITY.as_ref(),
+            config.key().as_ref(),
+        ], bump = config.bump
+    )]
+    pub lp_mint_authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_LP_TOKEN_POOL.as_ref(),
+            capital_call.key().as_ref(),
+        ],
+        bump = capital_call.lp_token_pool_bump
+    )]
+    pub lp_token_pool: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub lp_mint: Box<Account<'info, Mint>>,
+
+    // Not checked if claim fee is disabled
+    #[account(
+        mut,
+        constraint = config.claim_fee_bps == 0
+            || fee_lp_account.key() == config.fee_lp_account @ CapitalCallError::InvalidFeeLpAccount,
+    )]
+    pub fee_lp_account: Box<Account<'info, TokenAccount>>,
+
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub receiver: SystemAccount<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct FinalizeChunked<'info> {
+    #[account(
+        has_one = lp_mint,
+        has_one = lp_mint_authority,
+        has_one = liquidity_pool,
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_CAPITAL_CALL.as_ref(),
+            capital_call.config.as_ref(),
+            capital_call.start_time.to_le_bytes().as_ref(),
+            capital_call.capacity.to_le_bytes().as_ref(),
+            capital_call.idempotency_key.as_ref(),
+        ],
+        bump = capital_call.bump,
+        has_one = config,
+        has_one = lp_token_pool,
+    )]
+    pub capital_call: Account<'info, CapitalCall>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_VAULT.as_ref(),
+            capital_call.key().as_ref(),
+        ],
+        bump = capital_call.vault_bump,
+    )]
+    pub vault: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub liquidity_pool: Account<'info, TokenAccount>,
+
+    /// CHECK: Only for bump calculation
+    #[account(
+        seeds = [
+            SEED_LP_MINT_AUTHORITY.as_ref(),
+            config.key().as_ref(),
+        ], bump = config.bump
+    )]
+    pub lp_mint_authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_LP_TOKEN_POOL.as_ref(),
+            capital_call.key().as_ref(),
+        ],
+        bump = capital_call.lp_token_pool_bump
+    )]
+    pub lp_token_pool: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub lp_mint: Account<'info, Mint>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct FinalizeSplit<'info> {
+    #[account(
+        has_one = authority,
+        has_one = lp_mint,
+        has_one = lp_mint_authority,
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_CAPITAL_CALL.as_ref(),
+            capital_call.config.as_ref(),
+            capital_call.start_time.to_le_bytes().as_ref(),
+            capital_call.capacity.to_le_bytes().as_ref(),
+            capital_call.idempotency_key.as_ref(),
+        ],
+        bump = capital_call.bump,
+        has_one = config,
+        has_one = lp_token_pool,
+    )]
+    pub capital_call: Account<'info, CapitalCall>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_VAULT.as_ref(),
+            capital_call.key().as_ref(),
+        ],
+        bump = capital_call.vault_bump,
+    )]
+    pub vault: Account<'info, TokenAccount>,
+
+    /// CHECK: Only for bump calculation
+    #[account(
+        seeds = [
+            SEED_LP_MINT_AUTHORITY.as_ref(),
+            config.key().as_ref(),
+        ], bump = config.bump
+    )]
+    pub lp_mint_authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_LP_TOKEN_POOL.as_ref(),
+            capital_call.key().as_ref(),
+        ],
+        bump = capital_call.lp_token_pool_bump
+    )]
+    pub lp_token_pool: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub lp_mint: Account<'info, Mint>,
+
+    pub authority: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct Claim<'info> {
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_CAPITAL_CALL.as_ref(),
+            capital_call.config.as_ref(),
+            capital_call.start_time.to_le_bytes().as_ref(),
+            capital_call.capacity.to_le_bytes().as_ref(),
+            capital_call.idempotency_key.as_ref(),
+        ],
+        bump = capital_call.bump,
+        has_one = config,
+        has_one = lp_token_pool,
+    )]
+    pub capital_call: Account<'info, CapitalCall>,
+
+    // Not checked if claim fee is disabled
+    #[account(
+        mut,
+        constraint = config.claim_fee_bps == 0
+            || fee_lp_account.key() == config.fee_lp_account @ CapitalCallError::InvalidFeeLpAccount,
+    )]
+    pub fee_lp_account: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_LP_TOKEN_POOL.as_ref(),
+            capital_call.key().as_ref()],
+        bump = capital_call.lp_token_pool_bump,
+        constraint = lp_token_pool.mint == config.lp_mint @ CapitalCallError::InvalidLpTokenPoolMint,
+    )]
+    pub lp_token_pool: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        close = authority,
+        seeds = [
+            SEED_VOUCHER.as_ref(),
+            capital_call.key().as_ref(),
+            authority.key().as_ref()],
+        bump = voucher.bump,
+        has_one = authority,
+        has_one = capital_call,
+    )]
+    pub voucher: Account<'info, Voucher>,
+
+    #[account(
+        mut,
+        constraint = destination.mint == config.lp_mint @ CapitalCallError::InvalidDestinationMint,
+        // LP escrow of depositor if LP tokens are locked up
+        constraint = !config.ata_destinations
+            || capital_call.lp_lockup > 0
+            || destination.key() == get_associated_token_address(&authority.key(), &config.lp_mint)
+            @ CapitalCallError::InvalidDestination,
+        constraint = !config.strict_claim_destination
+            || capital_call.lp_lockup > 0
+            || destination.owner == voucher.authority @ CapitalCallError::InvalidDestination,
+    )]
+    pub destination: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimVested<'info> {
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_CAPITAL_CALL.as_ref(),
+            capital_call.config.as_ref(),
+            capital_call.start_time.to_le_bytes().as_ref(),
+            capital_call.capacity.to_le_bytes().as_ref(),
+            capital_call.idempotency_key.as_ref(),
+        ],
+        bump = capital_call.bump,
+        has_one = config,
+        has_one = lp_token_pool,
+    )]
+    pub capital_call: Account<'info, CapitalCall>,
+
+    // Not checked if claim fee is disabled
+    #[account(
+        mut,
+        constraint = config.claim_fee_bps == 0
+            || fee_lp_account.key() == config.fee_lp_account @ CapitalCallError::InvalidFeeLpAccount,
+    )]
+    pub fee_lp_account: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_LP_TOKEN_POOL.as_ref(),
+            capital_call.key().as_ref()],
+        bump = capital_call.lp_token_pool_bump,
+        constraint = lp_token_pool.mint == config.lp_mint @ CapitalCallError::InvalidLpTokenPoolMint,
+    )]
+    pub lp_token_pool: Account<'info, TokenAccount>,
+
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_VOUCHER.as_ref(),
+            capital_call.key().as_ref(),
+            authority.key().as_ref()],
+        bump = voucher.bump,
+        has_one = authority,
+        has_one = capital_call,
+    )]
+    pub voucher: Account<'info, Voucher>,
+
+    #[account(
+        mut,
+        constraint = destination.mint == config.lp_mint @ CapitalCallError::InvalidDestinationMint,
+        // LP escrow of depositor if LP tokens are locked up
+        constraint = !config.ata_destinations
+            || capital_call.lp_lockup > 0
+            || destination.key() == get_associated_token_address(&authority.key(), &config.lp_mint)
+            @ CapitalCallError::InvalidDestination,
+        constraint = !config.strict_claim_destination
+            || capital_call.lp_lockup > 0
+            || destination.owner == voucher.authority @ CapitalCallError::InvalidDestination,
+    )]
+    pub destination: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct InitLpLockup<'info> {
+    #[account(has_one = lp_mint)]
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(
+        seeds = [
+            SEED_CAPITAL_CALL.as_ref(),
+            capital_call.config.as_ref(),
+            capital_call.start_time.to_le_bytes().as_ref(),
+            capital_call.capacity.to_le_bytes().as_ref(),
+            capital_call.idempotency_key.as_ref(),
+        ],
+        bump = capital_call.bump,
+        has_one = config,
+    )]
+    pub capital_call: Box<Account<'info, CapitalCall>>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = LpLockup::SPACE,
+        seeds = [
+            SEED_LP_LOCKUP.as_ref(),
+            capital_call.key().as_ref(),
+            authority.key().as_ref(),
+        ],
+        bump
+    )]
+    pub lp_lockup: Box<Account<'info, LpLockup>>,
+
+    #[account(
+        init,
+        payer = authority,
+        token::mint = lp_mint,
+        token::authority = capital_call,
+        seeds = [
+            SEED_LP_ESCROW.as_ref(),
+            capital_call.key().as_ref(),
+            authority.key().as_ref(),
+        ],
+        bump
+    )]
+    pub escrow: Box<Account<'info, TokenAccount>>,
+
+    pub lp_mint: Box<Account<'info, Mint>>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct ReleaseLp<'info> {
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_CAPITAL_CALL.as_ref(),
+            capital_call.config.as_ref(),
+            capital_call.start_time.to_le_bytes().as_ref(),
+            capital_call.capacity.to_le_bytes().as_ref(),
+            capital_call.idempotency_key.as_ref(),
+        ],
+        bump = capital_call.bump,
+        has_one = config,
+    )]
+    pub capital_call: Account<'info, CapitalCall>,
+
+    #[account(
+        mut,
+        close = authority,
+        seeds = [
+            SEED_LP_LOCKUP.as_ref(),
+            capital_call.key().as_ref(),
+            authority.key().as_ref(),
+        ],
+        bump = lp_lockup.bump,
+        has_one = authority,
+        has_one = capital_call,
+        has_one = escrow,
+    )]
+    pub lp_lockup: Account<'info, LpLockup>,
+
+    #[account(mut)]
+    pub escrow: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = destination.mint == config.lp_mint @ CapitalCallError::InvalidDestinationMint,
+        constraint = !config.ata_destinations
+            || destination.key() == get_associated_token_address(&authority.key(), &config.lp_mint)
+            @ CapitalCallError::InvalidDestination,
+    )]
+    pub destination: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct SetVestingOverride<'info> {
+    pub config: Account<'info, Config>,
+
+    #[account(
+        seeds = [
+            SEED_CAPITAL_CALL.as_ref(),
+            capital_call.config.as_ref(),
+            capital_call.start_time.to_le_bytes().as_ref(),
+            capital_call.capacity.to_le_bytes().as_ref(),
+            capital_call.idempotency_key.as_ref(),
+        ],
+        bump = capital_call.bump,
+        has_one = config,
+    )]
+    pub capital_call: Account<'info, CapitalCall>,
+
+    #[account(mut, has_one = capital_call)]
+    pub voucher: Account<'info, Voucher>,
+
+    // Config authority or manager of capital call
+    #[account(
+        constraint = authority.key() == config.authority
+            || authority.key() == capital_call.manager @ CapitalCallError::InvalidAuthority,
+    )]
+    pub authority: Signer<'info>,
+}
+
+impl<'a, 'b, 'c, 'info> From<&mut Claim<'info>> for CpiContext<'a, 'b, 'c, 'info, Transfer<'info>> {
+    fn from(accounts: &mut Claim<'info>) -> CpiContext<'a, 'b, 'c, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: accounts.lp_token_pool.to_account_info(),
+            to: accounts.destination.to_account_info(),
+            authority: accounts.capital_call.to_account_info(),
+        };
+        let cpi_program = accounts.token_program.to_account_info();
+        CpiContext::new(cpi_program, cpi_accounts)
+    }
+}
+
+#[derive(Accounts)]
+pub struct VoidVoucher<'info> {
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_CAPITAL_CALL.as_ref(),
+            capital_call.config.as_ref(),
+            capital_call.start_time.to_le_bytes().as_ref(),
+            capital_call.capacity.to_le_bytes().as_ref(),
+            capital_call.idempotency_key.as_ref(),
+        ],
+        bump = capital_call.bump,
+        has_one = config,
+        has_one = lp_token_pool,
+    )]
+    pub capital_call: Account<'info, CapitalCall>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_LP_TOKEN_POOL.as_ref(),
+            capital_call.key().as_ref()],
+        bump = capital_call.lp_token_pool_bump
+    )]
+    pub lp_token_pool: Account<'info, TokenAccount>,
+
+    // Rent of voucher is returned to depositor
+    #[account(
+        mut,
+        close = depositor,
+        has_one = capital_call,
+        constraint = voucher.authority == depositor.key() @ CapitalCallError::InvalidVoucher,
+    )]
+    pub voucher: Account<'info, Voucher>,
+
+    /// CHECK: Only for rent of voucher
+    #[account(mut)]
+    pub depositor: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        constraint = treasury.key() == config.void_treasury @ CapitalCallError::InvalidVoidTreasury,
+    )]
+    pub treasury: Account<'info, TokenAccount>,
+
+    // Config authority or manager of capital call
+    #[account(
+        constraint = authority.key() == config.authority
+            || authority.key() == capital_call.manager @ CapitalCallError::InvalidAuthority,
+    )]
+    pub authority: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(start_time: u64, capacity: u64, idempotency_key: [u8; 16])]
+pub struct RecoverOrphanVoucher<'info> {
+    #[account(has_one = authority)]
+    pub config: Account<'info, Config>,
+
+    pub authority: Signer<'info>,
+
+    /// CHECK: Closed capital call of voucher, checked in instruction
+    #[account(
+        seeds = [
+            SEED_CAPITAL_CALL.as_ref(),
+            config.key().as_ref(),
+            start_time.to_le_bytes().as_ref(),
+            capacity.to_le_bytes().as_ref(),
+            idempotency_key.as_ref(),
+        ],
+        bump,
+        constraint = capital_call.key() == voucher.capital_call @ CapitalCallError::InvalidVoucher,
+    )]
+    pub capital_call: UncheckedAccount<'info>,
+
+    // Rent of voucher is returned to depositor
+    #[account(
+        mut,
+        close = depositor,
+        seeds = [
+            SEED_VOUCHER.as_ref(),
+            capital_call.key().as_ref(),
+            depositor.key().as_ref(),
+        ],
+        bump = voucher.bump,
+        constraint = voucher.authority == depositor.key() @ CapitalCallError::InvalidVoucher,
+    )]
+    pub voucher: Account<'info, Voucher>,
+
+    /// CHECK: Only for rent of voucher
+    #[account(mut)]
+    pub depositor: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterReferral<'info> {
+    pub config: Account<'info, Config>,
+
+    #[account(
+        seeds = [
+            SEED_CAPITAL_CALL.as_ref(),
+            capital_call.config.as_ref(),
+            capital_call.start_time.to_le_bytes().as_ref(),
+            capital_call.capacity.to_le_bytes().as_ref(),
+            capital_call.idempotency_key.as_ref(),
+        ],
+        bump = capital_call.bump,
+        has_one = config,
+    )]
+    pub capital_call: Account<'info, CapitalCall>,
+
+    #[account(
+        init,
+        payer = referrer,
+        space = Referral::SPACE,
+        seeds = [
+            SEED_REFERRAL.as_ref(),
+            capital_call.key().as_ref(),
+            referrer.key().as_ref(),
+        ],
+        bump
+    )]
+    pub referral: Account<'info, Referral>,
+
+    #[account(mut)]
+    pub referrer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimReferral<'info> {
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_CAPITAL_CALL.as_ref(),
+            capital_call.config.as_ref(),
+            capital_call.start_time.to_le_bytes().as_ref(),
+            capital_call.capacity.to_le_bytes().as_ref(),
+            capital_call.idempotency_key.as_ref(),
+        ],
+        bump = capital_call.bump,
+        has_one = config,
+        has_one = lp_token_pool,
+    )]
+    pub capital_call: Account<'info, CapitalCall>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_LP_TOKEN_POOL.as_ref(),
+            capital_call.key().as_ref()],
+        bump = capital_call.lp_token_pool_bump
+    )]
+    pub lp_token_pool: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_REFERRAL.as_ref(),
+            capital_call.key().as_ref(),
+            referrer.key().as_ref(),
+        ],
+        bump = referral.bump,
+        has_one = referrer,
+        has_one = capital_call,
+    )]
+    pub referral: Account<'info, Referral>,
+
+    pub referrer: Signer<'info>,
+
+    #[account(mut)]
+    pub destination: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct CloseCapitalCall<'info> {
+    #[account(
+        mut,
+        has_one = authority,
+        has_one = lp_mint,
+    )]
+    pub config: Box<Account<'info, Config>>,
+
+    // Closed by handler unless vault retains balance
+    #[account(
+        mut,
+        seeds = [
+            SEED_CAPITAL_CALL.as_ref(),
+            capital_call.config.as_ref(),
+            capital_call.start_time.to_le_bytes().as_ref(),
+            capital_call.capacity.to_le_bytes().as_ref(),
+            capital_call.idempotency_key.as_ref(),
+        ],
+        bump = capital_call.bump,
+        has_one = config,
+        has_one = vault,
+        has_one = lp_token_pool,
+    )]
+    pub capital_call: Box<Account<'info, CapitalCall>>,
+
+    pub authority: Signer<'info>,
+
+    // Payer of capital call if config returns rent to payer
+    #[account(
+        mut,
+        constraint = !config.rent_to_payer || receiver.key() == capital_call.payer
+            @ CapitalCallError::ReceiverNotPayer,
+    )]
+    pub receiver: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_LP_TOKEN_POOL.as_ref(),
+            capital_call.key().as_ref()],
+        bump = capital_call.lp_token_pool_bump
+    )]
+    pub lp_token_pool: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_VAULT.as_ref(),
+            capital_call.key().as_ref()],
+        bump = capital_call.vault_bump,
+    )]
+    pub vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub lp_mint: Box<Account<'info, Mint>>,
+
+    #[account(mut)]
+    pub destination: Box<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct SweepRetained<'info> {
+    #[account(mut, has_one = authority)]
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_CAPITAL_CALL.as_ref(),
+            capital_call.config.as_ref(),
+            capital_call.start_time.to_le_bytes().as_ref(),
+            capital_call.capacity.to_le_bytes().as_ref(),
+            capital_call.idempotency_key.as_ref(),
+        ],
+        bump = capital_call.bump,
+        has_one = config,
+        has_one = vault,
+    )]
+    pub capital_call: Box<Account<'info, CapitalCall>>,
+
+    pub authority: Signer<'info>,
+
+    // Payer of capital call if config returns rent to payer
+    #[account(
+        mut,
+        constraint = !config.rent_to_payer || receiver.key() == capital_call.payer
+            @ CapitalCallError::ReceiverNotPayer,
+    )]
+    pub receiver: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_VAULT.as_ref(),
+            capital_call.key().as_ref()],
+        bump = capital_call.vault_bump,
+    )]
+    pub vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub destination: Box<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct CloseCancelled<'info> {
+    #[account(mut, has_one = authority)]
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(
+        mut,
+        close = receiver,
+        seeds = [
+            SEED_CAPITAL_CALL.as_ref(),
+            capital_call.config.as_ref(),
+            capital_call.start_time.to_le_bytes().as_ref(),
+            capital_call.capacity.to_le_bytes().as_ref(),
+            capital_call.idempotency_key.as_ref(),
+        ],
+        bump = capital_call.bump,
+        has_one = config,
+        has_one = vault,
+        has_one = lp_token_pool,
+    )]
+    pub capital_call: Box<Account<'info, CapitalCall>>,
+
+    pub authority: Signer<'info>,
+
+    // Payer of capital call if config returns rent to payer
+    #[account(
+        mut,
+        constraint = !config.rent_to_payer || receiver.key() == capital_call.payer
+            @ CapitalCallError::ReceiverNotPayer,
+    )]
+    pub receiver: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_LP_TOKEN_POOL.as_ref(),
+            capital_call.key().as_ref()],
+        bump = capital_call.lp_token_pool_bump
+    )]
+    pub lp_token_pool: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_VAULT.as_ref(),
+            capital_call.key().as_ref()],
+        bump = capital_call.vault_bump,
+    )]
+    pub vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub destination: Box<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct Redeem<'info> {
+    #[account(has_one = lp_mint)]
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_CAPITAL_CALL.as_ref(),
+            capital_call.config.as_ref(),
+            capital_call.start_time.to_le_bytes().as_ref(),
+            capital_call.capacity.to_le_bytes().as_ref(),
+            capital_call.idempotency_key.as_ref(),
+        ],
+        bump = capital_call.bump,
+        has_one = config,
+    )]
+    pub capital_call: Account<'info, CapitalCall>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_VAULT.as_ref(),
+            capital_call.key().as_ref(),
+        ],
+        bump = capital_call.vault_bump,
+    )]
+    pub vault: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub lp_mint: Account<'info, Mint>,
+
+    pub authority: Signer<'info>,
+
+    // LP tokens of authority
+    #[account(mut)]
+    pub source: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub destination: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct RecoverStrandedLp<'info> {
+    #[account(has_one = authority)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        seeds = [
+            SEED_CAPITAL_CALL.as_ref(),
+            capital_call.config.as_ref(),
+            capital_call.start_time.to_le_bytes().as_ref(),
+            capital_call.capacity.to_le_bytes().as_ref(),
+            capital_call.idempotency_key.as_ref(),
+        ],
+        bump = capital_call.bump,
+        has_one = config,
+        has_one = lp_token_pool,
+    )]
+    pub capital_call: Account<'info, CapitalCall>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_LP_TOKEN_POOL.as_ref(),
+            capital_call.key().as_ref()],
+        bump = capital_call.lp_token_pool_bump
+    )]
+    pub lp_token_pool: Account<'info, TokenAccount>,
+
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub destination: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct TakeSnapshot<'info> {
+    #[account(has_one = authority)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        seeds = [
+            SEED_CAPITAL_CALL.as_ref(),
+            capital_call.config.as_ref(),
+            capital_call.start_time.to_le_bytes().as_ref(),
+            capital_call.capacity.to_le_bytes().as_ref(),
+            capital_call.idempotency_key.as_ref(),
+        ],
+        bump = capital_call.bump,
+        has_one = config,
+    )]
+    pub capital_call: Account<'info, CapitalCall>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = Snapshot::SPACE,
+        seeds = [
+            SEED_SNAPSHOT.as_ref(),
+            capital_call.key().as_ref(),
+        ],
+        bump
+    )]
+    pub snapshot: Account<'info, Snapshot>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SnapshotVouchers<'info> {
+    #[account(has_one = authority)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        seeds = [
+            SEED_CAPITAL_CALL.as_ref(),
+            capital_call.config.as_ref(),
+            capital_call.start_time.to_le_bytes().as_ref(),
+            capital_call.capacity.to_le_bytes().as_ref(),
+            capital_call.idempotency_key.as_ref(),
+        ],
+        bump = capital_call.bump,
+        has_one = config,
+    )]
+    pub capital_call: Account<'info, CapitalCall>,
+
+    #[account(
+        mut,
+        seeds = [
+            SEED_SNAPSHOT.as_ref(),
+            capital_call.key().as_ref(),
+        ],
+        bump = snapshot.bump,
+        has_one = capital_call,
+    )]
+    pub snapshot: Account<'info, Snapshot>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct LogAllocations<'info> {
+    #[account(
+        seeds = [
+            SEED_CAPITAL_CALL.as_ref(),
+            capital_call.config.as_ref(),
+            capital_call.start_time.to_le_bytes().as_ref(),
+            capital_call.capacity.to_le_bytes().as_ref(),
+            capital_call.idempotency_key.as_ref(),
+        ],
+        bump = capital_call.bump,
+    )]
+    pub capital_call: Account<'info, CapitalCall>,
+}
+
+#[derive(Accounts)]
+pub struct VoucherStatus<'info> {
+    #[account(
+        seeds = [
+            SEED_CAPITAL_CALL.as_ref(),
+            capital_call.config.as_ref(),
+            capital_call.start_time.to_le_bytes().as_ref(),
+            capital_call.capacity.to_le_bytes().as_ref(),
+            capital_call.idempotency_key.as_ref(),
+        ],
+        bump = capital_call.bump,
+    )]
+    pub capital_call: Account<'info, CapitalCall>,
+
+    #[account(has_one = capital_call)]
+    pub voucher: Account<'info, Voucher>,
+}
+
+#[derive(Accounts)]
+pub struct VerifyVault<'info> {
+    #[account(
+        seeds = [
+            SEED_CAPITAL_CALL.as_ref(),
+            capital_call.config.as_ref(),
+            capital_call.start_time.to_le_bytes().as_ref(),
+            capital_call.capacity.to_le_bytes().as_ref(),
+            capital_call.idempotency_key.as_ref(),
+        ],
+        bump = capital_call.bump,
+        has_one = vault,
+    )]
+    pub capital_call: Account<'info, CapitalCall>,
+
+    pub vault: Account<'info, TokenAccount>,
+}
+
+#[derive(Accounts)]
+pub struct ConfigStats<'info> {
+    pub config: Account<'info, Config>,
+}
+
+#[account]
+pub struct Config {
+    pub authority: Pubkey,
+    pub liquidity_pool: Pubkey,
+    pub lp_mint: Pubkey,
+    pub lp_mint_authority: Pubkey,
+    pub lp_decimals: u8,
+
+    // Sum of capacity of all active capital calls
+    pub total_capacity: u64,
+
+    // Sum of allocated and not refunded amount of all active capital calls
+    pub total_allocated: u64,
+
+    // Program notified on finalization of capital call, default pubkey if not set
+    pub finalize_callback_program: Pubkey,
+
+    // Share of claimed LP tokens transferred to `fee_lp_account`, zero if disabled
+    pub claim_fee_bps: u16,
+    pub fee_lp_account: Pubkey,
+
+    // Minimal credit outstanding of new capital calls
+    pub min_credit_outstanding: u64,
+
+    // Minimal balance of liquidity pool to accept deposits, zero if not checked
+    pub min_pool_balance: u64,
+
+    // Minimal underlying amount of partial claim, final claim of voucher is always allowed
+    pub min_claim: u64,
+
+    // Number of created and not yet closed capital calls, capped by max concurrent calls
+    // unless zero
+    pub open_calls: u32,
+    pub max_concurrent_calls: u32,
+
+    // Vaults left open by close of capital calls with non-zero close retain
+    pub retained_vaults: u32,
+
+    // Id assigned to the next created capital call, incremented by every creation
+    pub next_call_id: u64,
+
+    // Config authority deposits without gatekeeper allowance
+    pub authority_bypass_whitelist: bool,
+
+    // Claim and refund destinations have to be associated token accounts of depositor,
+    // clients create them in the same transaction if needed
+    pub ata_destinations: bool,
+
+    // Minimal time between creation and start of capital call, zero if not checked
+    pub min_lead_time: u64,
+
+    // Maximal amount transferred from vault to liquidity pool by single finalize instruction,
+    // zero if not checked
+    pub max_finalize_transfer: u64,
+
+    // Claimed LP tokens are rounded up in favor of depositors instead of down in favor of
+    // pool, applies to capital calls created afterwards
+    pub round_up_claims: bool,
+
+    // Token account which balance is outstanding debt, credit outstanding of created capital
+    // calls has to match it, default pubkey if not checked
+    pub debt_account: Pubkey,
+
+    // Key allowed only emergency actions: freezing, pausing finalization and cancelling
+    pub guardian: Pubkey,
+
+    // Money market program capital calls can opt in to, default pubkey if disabled
+    pub yield_program: Pubkey,
+
+    // Maximal number of claim_vested of voucher before LP tokens are fully vested, zero if not
+    // checked
+    pub max_partial_claims: u8,
+
+    // Minimal time after start before capital call can be cancelled unless it's frozen, zero if
+    // not checked
+    pub min_open_before_cancel: u64,
+
+    // Minimal number of slots duration of created capital call has to span at default slot
+    // time, zero if not checked
+    pub min_window_slots: u64,
+
+    // Bounds of capacity of created capital call, zero bound is disabled
+    pub min_capacity: u64,
+    pub max_capacity: u64,
+
+    // Program holding tier records of wallets read by deposit, default pubkey if disabled
+    pub tier_oracle: Pubkey,
+
+    // Rent of closed capital calls returns to their payers, otherwise authority picks receiver
+    pub rent_to_payer: bool,
+
+    // Deposits from token accounts with active delegate are rejected
+    pub reject_delegated_source: bool,
+
+    // Mint of participation badges, depositor gets one frozen token of it so it can't be
+    // transferred. Mint and freeze authority is LP mint authority, default pubkey if disabled.
+    pub participation_mint: Pubkey,
+
+    // Token account receiving tokens transferred directly to vault when LP tokens are minted,
+    // default pubkey if surplus is left in vault for close
+    pub surplus_treasury: Pubkey,
+
+    // Minimal share of capacity allocated for capital call to be extended, applies on top of
+    // auto extend threshold of capital call, zero if not checked
+    pub min_fill_to_extend_bps: u16,
+
+    // Claim destinations have to be owned by depositor, otherwise any LP token account is
+    // accepted for custody flows
+    pub strict_claim_destination: bool,
+
+    // LP token account receiving LP tokens of voided vouchers, default pubkey if vouchers can't
+    // be voided
+    pub void_treasury: Pubkey,
+
+    // Kill switch halting deposits, refunds, claims, LP tokens minting, creation and closing of
+    // all capital calls
+    pub killed: bool,
+
+    pub bump: u8,
+}
+
+impl Config {
+    pub const SPACE: usize = 8 + std::mem::size_of::<Config>();
+
+    pub fn claim_fee(&self, lp_amount: u64) -> Result<u64> {
+        u64::try_from(lp_amount as u128 * self.claim_fee_bps as u128 / BPS_DENOMINATOR as u128)
+            .map_err(|_| error!(CapitalCallError::CalculationError))
+    }
+
+    /// Count created capital call as open and assign it the next call id
+    pub fn add_capital_call(&mut self, capacity: u64) -> Result<u64> {
+        self.total_capacity = self
+            .total_capacity
+            .checked_add(capacity)
+            .ok_or_else(|| error!(CapitalCallError::CalculationError))?;
+        self.open_calls = self
+            .open_calls
+            .checked_add(1)
+            .ok_or_else(|| error!(CapitalCallError::CalculationError))?;
+
+        let call_id = self.next_call_id;
+        self.next_call_id = self
+            .next_call_id
+            .checked_add(1)
+            .ok_or_else(|| error!(CapitalCallError::CalculationError))?;
+
+        Ok(call_id)
+    }
+}
+
+#[account]
+#[derive(Default)]
+pub struct CapitalCall {
+    pub config: Pubkey,
+    pub vault: Pubkey,
+    pub lp_token_pool: Pubkey,
+
+    // Client provided key, part of seeds to make creation retries idempotent
+    pub idempotency_key: [u8; 16],
+
+    // Sequential id within config assigned at creation
+    pub call_id: u64,
+
+    // Delegate of config authority for actions scoped to this capital call, default pubkey if
+    // not set
+    pub manager: Pubkey,
+
+    // Funded rent of capital call at creation
+    pub payer: Pubkey,
+
+    // Start time of capital call
+    pub start_time: u64,
+
+    // Slot from which deposits are accepted in addition to start time, zero if not set
+    pub start_slot: u64,
+
+    // End time of capital call
+    pub end_time: u64,
+
+    // Seconds after end time before refunds are allowed
+    pub refund_lockup: u64,
+
+    // Refunds are transferred only to token account deposit came from, not usable with
+    // deposit_sol as its source account is closed
+    pub refund_to_source: bool,
+
+    // Refunds are transferred only to token accounts owned by owner of deposit source
+    pub refund_to_source_owner: bool,
+
+    // Signer of off-chain deposit allowances verified with ed25519 program, default pubkey if
+    // deposits aren't gated
+    pub gatekeeper: Pubkey,
+
+    // Program that has to invoke deposit with CPI, default pubkey if direct deposits allowed
+    pub allowed_depositor_program: Pubkey,
+
+    // Seconds after withdraw before depositor can deposit again, zero if disabled
+    pub refund_cooldown: u64,
+
+    // Seconds after end time to mint LP tokens, afterwards fully funded capital call can be
+    // refunded and no longer finalized, zero if disabled
+    pub finalize_window: u64,
+
+    // Share of remaining capacity a single deposit can take, larger deposits are reduced,
+    // zero if disabled
+    pub max_fill_bps_per_tx: u16,
+
+    // Deposit over remaining capacity is reduced to fill it, otherwise it's rejected
+    pub allow_clamp: bool,
+
+    // Deposit is rejected if vault holds more than accounted, not only less
+    pub strict_vault_balance: bool,
+
+    // Capital is valued in base units of liquidity pool by price of `oracle` at finalization,
+    // price at most `oracle_max_age` seconds old with confidence within `oracle_max_conf_bps`
+    pub use_oracle_conversion: bool,
+    pub oracle: Pubkey,
+    pub oracle_max_age: u64,
+    pub oracle_max_conf_bps: u16,
+    // Pool base units per raised token scaled by `RATE_PRECISION`, stamped at finalization
+    pub oracle_rate: u64,
+
+    // Minimal deposit, a deposit leaving less than it of remaining capacity is reduced to leave
+    // exactly it, zero if disabled
+    pub min_deposit: u64,
+
+    // Deposit of depositor is capped at multiplier of balance of their stake token account,
+    // default pubkey if not required
+    pub stake_mint: Pubkey,
+    pub stake_cap_multiplier: u64,
+
+    // LP tokens bonus by deposited amount of voucher, tiers are sorted by min amount and unused
+    // tiers are zero. LP tokens for bonuses of allocated vouchers are minted at finalization.
+    pub bonus_tiers: [BonusTier; MAX_BONUS_TIERS],
+    pub bonus_allocated: u64,
+
+    // Step vesting of LP tokens used instead of linear vesting if set, share of LP tokens unlocks
+    // at each point. Points are sorted by timestamp, their shares sum to 100% and unused points
+    // are zero.
+    pub unlock_points: [UnlockPoint; MAX_UNLOCK_POINTS],
+
+    // End time is extended once by duration if capital call ends with at least threshold of
+    // capacity allocated, zero threshold if disabled
+    pub auto_extend_threshold_bps: u16,
+    pub auto_extend_duration: u64,
+    pub auto_extended: bool,
+
+    // Slots deposits are pending before confirm_deposit counts them in allocated, zero if
+    // deposits are allocated immediately
+    pub confirmation_slots: u64,
+
+    // Expected amount
+    pub capacity: u64,
+
+    // Allocated amount
+    pub allocated: u64,
+
+    // Deposited amount not confirmed yet, it takes capacity but isn't allocated
+    pub pending_amount: u64,
+
+    // Redeemed or return tokens
+    pub redeemed: u64,
+
+    // Linear vesting of LP tokens from start, nothing is vested before cliff (seconds after
+    // start), zero duration if LP tokens are not vested
+    pub vest_start: u64,
+    pub vest_cliff: u64,
+    pub vest_duration: u64,
+
+    // Time after which unclaimed vouchers can be voided, zero if vouchers don't expire
+    pub voucher_expiry: u64,
+
+    // Time capital call first got fully funded, LP tokens aren't minted within finalize
+    // cooldown after it while deposits can be withdrawn
+    pub filled_at: u64,
+    pub finalize_cooldown: u64,
+
+    // Time LP tokens were minted, claims open claim delay seconds after it
+    pub finalized_at: u64,
+    pub claim_delay: u64,
+
+    // LP tokens are released to all vouchers linearly over duration since finalization on top
+    // of vesting, zero releases everything at finalization
+    pub twap_release_duration: u64,
+
+    // FundingProgressEvent is emitted when fill crosses multiple of step, zero to emit on every deposit
+    pub progress_event_step_bps: u16,
+    pub last_emitted_bps: u16,
+
+    pub token_liquidity: u64,
+    pub lp_supply: u64,
+    pub credit_outstanding: u64,
+
+    // Bounds of LP tokens per underlying token rate at finalization scaled by RATE_PRECISION,
+    // zero bound is disabled
+    pub min_rate: u64,
+    pub max_rate: u64,
+
+    // Credit tokens minted to depositor at deposit and burned on refund, withdraw and claim,
+    // default pubkey if disabled
+    pub credit_token_mint: Pubkey,
+
+    // Token accepted 1:1 to mint of vault into secondary vault, deployed to secondary liquidity
+    // pool at finalization, default pubkey if disabled
+    pub secondary_mint: Pubkey,
+    pub secondary_vault: Pubkey,
+    pub secondary_liquidity_pool: Pubkey,
+
+    // Part of allocated amount held in secondary vault
+    pub secondary_allocated: u64,
+
+    // Share of each deposit matched from reserve token account, which delegates matched
+    // tokens to capital call. Matched tokens are returned to reserve on refund and withdraw.
+    // Default pubkey if disabled
+    pub match_reserve: Pubkey,
+    pub match_bps: u16,
+
+    // Share of referred depositor's LP tokens paid to referrer
+    pub referral_bps: u16,
+
+    // Referral fees withheld from claims and not yet claimed by referrers
+    pub referral_lp_outstanding: u64,
+
+    // LP tokens minted at finalization and transferred out of lp token pool since, transferred
+    // never exceed minted
+    pub lp_minted: u64,
+    pub lp_distributed: u64,
+
+    // Rounding of claimed LP tokens taken from config at creation, rounding up is covered by
+    // reserve minted for every deposit, unused reserve is burned at close
+    pub round_up_claims: bool,
+    pub deposit_count: u32,
+
+    // Share of capital retained in vault by mint_lp_tokens, LP tokens are redeemed against
+    // retained amount with redeem
+    pub reserve_bps: u16,
+    pub vault_reserve: u64,
+
+    pub is_lp_minted: bool,
+
+    // All user actions and finalization are blocked while frozen
+    pub frozen: bool,
+
+    // Only finalization is blocked while paused
+    pub finalization_paused: bool,
+
+    // Custodian has to co-sign transfer of raised funds from vault to liquidity pool, which is
+    // possible only with mint_lp_tokens
+    pub custodial_vault: bool,
+    pub custodian: Pubkey,
+
+    // Cancelled capital call accepts no deposits and isn't finalized, deposits are refundable
+    // right away
+    pub is_cancelled: bool,
+
+    // Closed with non-zero close retain, vault keeps the balance until sweep_retained
+    pub retained: bool,
+
+    // Share of refunds retained in vault for creditors in wind-down of cancelled capital call,
+    // and tokens retained by refunds so far
+    pub refund_haircut_bps: u16,
+    pub refund_shortfall: u64,
+
+    // Money market program holding raised tokens until finalization or first refund, default
+    // pubkey if tokens stay in vault. Program of config is fixed at opt in.
+    pub yield_program: Pubkey,
+    // Tokens deposited from vault to yield program
+    pub yield_principal: u64,
+    // Tokens returned from yield program above principal, they stay in vault until `close`
+    pub yield_earned: u64,
+
+    // Share of minted LP tokens routed to `fee_lp_account` of config by `mint_lp_tokens`,
+    // fast fill fee applies if capital call got filled within threshold seconds after start.
+    // Fee charged at finalization and LP tokens it took.
+    pub fast_fill_threshold: u64,
+    pub fast_fill_fee_bps: u16,
+    pub slow_fill_fee_bps: u16,
+    pub fill_fee_bps: u16,
+    pub fill_fee_lp: u64,
+
+    // Liquidity stamped by update_valuation and time of it, finalization has to snapshot the
+    // stamped liquidity within max valuation age, zero age if not checked
+    pub max_valuation_age: u64,
+    pub valuation_timestamp: u64,
+    pub valuation_liquidity: u64,
+
+    // Claimed LP tokens are escrowed for lockup seconds after the latest claim of depositor,
+    // zero if claims go to depositors directly. LP tokens in escrows of depositors.
+    pub lp_lockup: u64,
+    pub lp_locked: u64,
+
+    // Maximal capital transferred to liquidity pool by each finalize_chunked, zero if capital
+    // call is finalized at once. Capital transferred so far by finalize_chunked.
+    pub finalize_chunk: u64,
+    pub finalize_cursor: u64,
+
+    // Capital was split between several liquidity pools by finalize_split
+    pub split_finalized: bool,
+
+    pub bump: u8,
+    pub vault_bump: u8,
+    pub lp_token_pool_bump: u8,
+    pub secondary_vault_bump: u8,
+}
+
+/// Owned seeds of capital call PDA, see `CapitalCall::signer_seeds`
+pub struct CapitalCallSeeds {
+    config: Pubkey,
+    start_time: [u8; 8],
+    capacity: [u8; 8],
+    idempotency_key: [u8; 16],
+    bump: [u8; 1],
+}
+
+impl CapitalCallSeeds {
+    pub fn as_slices(&self) -> [&[u8]; 6] {
+        [
+            &SEED_CAPITAL_CALL,
+            self.config.as_ref(),
+            &self.start_time,
+            &self.capacity,
+            &self.idempotency_key,
+            &self.bump,
+        ]
+    }
+}
+
+impl CapitalCall {
+    pub const SPACE: usize = 8 + std::mem::size_of::<CapitalCall>();
+
+    /// Set terms of created capital call, bumps are set by caller
+    pub fn init(
+        &mut self,
+        config: Pubkey,
+        vault: Pubkey,
+        lp_token_pool: Pubkey,
+        params: &CreateParams,
+        round_up_claims: bool,
+    ) {
+        self.config = config;
+        self.vault = vault;
+        self.lp_token_pool = lp_token_pool;
+        self.idempotency_key = params.idempotency_key;
+        self.manager = params.manager;
+
+        self.start_time = params.start_time;
+        self.end_time = params.start_time + params.duration;
+        self.refund_lockup = params.refund_lockup;
+        self.round_up_claims = round_up_claims;
+        self.allow_clamp = true;
+        self.capacity = params.capacity;
+        self.redeemed = 0;
+        self.allocated = 0;
+        self.is_lp_minted = false;
+
+        self.token_liquidity = 0;
+        self.lp_supply = 0;
+        self.credit_outstanding = params.credit_outstanding;
+    }
+
+    /// Seeds of capital call PDA to sign for its vault and LP token pool
+    pub fn signer_seeds(&self) -> CapitalCallSeeds {
+        CapitalCallSeeds {
+            config: self.config,
+            start_time: self.start_time.to_le_bytes(),
+            capacity: self.capacity.to_le_bytes(),
+            idempotency_key: self.idempotency_key,
+            bump: [self.bump],
+        }
+    }
+
+    /// Underfunded capital call that reached auto extend threshold and wasn't extended or
+    /// refunded yet
+    pub fn is_auto_extendable(&self) -> Result<bool> {
+        if self.auto_extend_threshold_bps == 0 || self.auto_extend_duration == 0 {
+            return Ok(false);
+        }
+        let threshold = u64::try_from(
+            self.capacity as u128 * self.auto_extend_threshold_bps as u128
+                / BPS_DENOMINATOR as u128,
+        )
+        .map_err(|_| error!(CapitalCallError::CalculationError))?;
+
+        Ok(!self.auto_extended
+            && !self.is_lp_minted
+            && self.redeemed == 0
+            && self.allocated < self.capacity
+            && self.allocated >= threshold)
+    }
+
+    pub fn to_lp_token(&self, amount: u64) -> Result<u64> {
+        require!(self.capacity > 0, CapitalCallError::CapacityNonZero);
+        require!(self.lp_supply > 0, CapitalCallError::CalculationError);
+        let amount = self.to_pool_units(amount)?;
+
+        let numerator =
+            amount as u128 * (self.token_liquidity as u128 + self.credit_outstanding as u128);
+        let lp_supply = self.lp_supply as u128;
+        let lp_amount = if self.round_up_claims {
+            (numerator + lp_supply - 1) / lp_supply
+        } else {
+            numerator / lp_supply
+        };
+        u64::try_from(lp_amount).map_err(|_| error!(CapitalCallError::CalculationError))
+    }
+
+    /// Raised `amount` in base units of liquidity pool by oracle rate stamped at finalization
+    pub fn to_pool_units(&self, amount: u64) -> Result<u64> {
+        if !self.use_oracle_conversion {
+            return Ok(amount);
+        }
+        u64::try_from(amount as u128 * self.oracle_rate as u128 / RATE_PRECISION as u128)
+            .map_err(|_| error!(CapitalCallError::CalculationError))
+    }
+
+    /// Base units of liquidity pool `amount` in raised tokens, inverse of `to_pool_units`
+    pub fn from_pool_units(&self, amount: u64) -> Result<u64> {
+        if !self.use_oracle_conversion {
+            return Ok(amount);
+        }
+        require!(self.oracle_rate > 0, CapitalCallError::CalculationError);
+        u64::try_from(amount as u128 * RATE_PRECISION as u128 / self.oracle_rate as u128)
+            .map_err(|_| error!(CapitalCallError::CalculationError))
+    }
+
+    /// Pool base units per raised token scaled by `RATE_PRECISION` from oracle `price`, which
+    /// has to be fresh and confident enough
+    pub fn oracle_rate(&self, price: &OraclePrice, now: u64) -> Result<u64> {
+        require!(price.price > 0, CapitalCallError::InvalidOraclePrice);
+        require!(
+            price.publish_time >= 0
+                && now.saturating_sub(price.publish_time as u64) <= self.oracle_max_age,
+            CapitalCallError::OraclePriceStale
+        );
+        let value = price.price as u128;
+        require!(
+            price.conf as u128 * BPS_DENOMINATOR as u128
+                <= value * self.oracle_max_conf_bps as u128,
+            CapitalCallError::OraclePriceUncertain
+        );
+
+        let scale = 10u128
+            .checked_pow(price.expo.unsigned_abs())
+            .ok_or_else(|| error!(CapitalCallError::CalculationError))?;
+        let rate = if price.expo < 0 {
+            value * RATE_PRECISION as u128 / scale
+        } else {
+            value
+                .checked_mul(scale)
+                .and_then(|value| value.checked_mul(RATE_PRECISION as u128))
+                .ok_or_else(|| error!(CapitalCallError::CalculationError))?
+        };
+        require!(rate > 0, CapitalCallError::InvalidOraclePrice);
+        u64::try_from(rate).map_err(|_| error!(CapitalCallError::CalculationError))
+    }
+
+    /// LP tokens per underlying token scaled by `RATE_PRECISION`
+    pub fn lp_rate(&self) -> Result<u64> {
+        require!(self.lp_supply > 0, CapitalCallError::CalculationError);
+        u64::try_from(
+            (self.token_liquidity as u128 + self.credit_outstanding as u128)
+                * RATE_PRECISION as u128
+                / self.lp_supply as u128,
+        )
+        .map_err(|_| error!(CapitalCallError::CalculationError))
+    }
+
+    /// Rate of snapshot taken at finalization has to be within bounds, zero bound is disabled
+    pub fn check_rate(&self) -> Result<()> {
+        let rate = self.lp_rate()?;
+        require!(
+            (self.min_rate == 0 || rate >= self.min_rate)
+                && (self.max_rate == 0 || rate <= self.max_rate),
+            CapitalCallError::RateOutOfBounds
+        );
+        Ok(())
+    }
+
+    /// LP tokens of voucher with `amount` including bonus of its tier, net of fill fee
+    pub fn voucher_lp(&self, amount: u64) -> Result<u64> {
+        let bonus = self.bonus(amount)?;
+        let lp_amount = self.to_lp_token(
+            amount
+                .checked_add(bonus)
+                .ok_or_else(|| error!(CapitalCallError::CalculationError))?,
+        )?;
+        u64::try_from(
+            lp_amount as u128 * (BPS_DENOMINATOR - self.fill_fee_bps as u64) as u128
+                / BPS_DENOMINATOR as u128,
+        )
+        .map_err(|_| error!(CapitalCallError::CalculationError))
+    }
+
+    /// Fee on minted LP tokens by time it took to fill capital call, requires `filled_at`
+    pub fn fill_fee(&self) -> Result<u16> {
+        if self.fast_fill_fee_bps == 0 && self.slow_fill_fee_bps == 0 {
+            return Ok(0);
+        }
+        require!(self.filled_at > 0, CapitalCallError::CapitalCallNotFullyFunded);
+        if self.filled_at.saturating_sub(self.start_time) <= self.fast_fill_threshold {
+            Ok(self.fast_fill_fee_bps)
+        } else {
+            Ok(self.slow_fill_fee_bps)
+        }
+    }
+
+    /// Bonus of the highest tier reached by `amount`, in underlying tokens
+    pub fn bonus(&self, amount: u64) -> Result<u64> {
+        let bonus_bps = self
+            .bonus_tiers
+            .iter()
+            .rev()
+            .find(|tier| tier.min_amount > 0 && amount >= tier.min_amount)
+            .map_or(0, |tier| tier.bonus_bps);
+        u64::try_from(amount as u128 * bonus_bps as u128 / BPS_DENOMINATOR as u128)
+            .map_err(|_| error!(CapitalCallError::CalculationError))
+    }
+
+    /// LP tokens minted at finalization, rounding claims up takes a reserve of one LP token
+    /// unit per deposit
+    pub fn lp_to_mint(&self) -> Result<u64> {
+        let capital = self
+            .capacity
+            .checked_add(self.bonus_allocated)
+            .ok_or_else(|| error!(CapitalCallError::CalculationError))?;
+        let minted = self.to_lp_token(capital)?;
+        if !self.round_up_claims {
+            return Ok(minted);
+        }
+        minted
+            .checked_add(self.deposit_count as u64)
+            .ok_or_else(|| error!(CapitalCallError::CalculationError))
+    }
+
+    /// Matching of deposits from match reserve is configured
+    pub fn is_match_enabled(&self) -> bool {
+        self.match_bps > 0 && self.match_reserve != Pubkey::default()
+    }
+
+    /// Requested deposit `amount` reduced to `remaining` capacity, per tx fill limit and
+    /// `min_deposit` as `deposit` and `deposit_secondary` accept it
+    pub fn clamp_amount(&self, amount: u64, remaining: u64) -> Result<u64> {
+        // Reduce amount if this tx fills vault, unless capital call is strict
+        require!(
+            self.allow_clamp || amount <= remaining,
+            CapitalCallError::ExceedsRemainingCapacity
+        );
+        let amount = amount.min(remaining);
+
+        // Reduce amount to share of remaining capacity allowed per tx, at least one token
+        let amount = if self.max_fill_bps_per_tx > 0 {
+            let max_fill = u64::try_from(
+                remaining as u128 * self.max_fill_bps_per_tx as u128 / BPS_DENOMINATOR as u128,
+            )
+            .map_err(|_| error!(CapitalCallError::CalculationError))?;
+            amount.min(max_fill.max(1))
+        } else {
+            amount
+        };
+        self.clamp_to_min_deposit(amount, remaining)
+    }
+
+    /// Records fill time once capacity is reached, returns event to emit after every filling deposit
+    pub fn mark_filled(
+        &mut self,
+        capital_call: Pubkey,
+        now: u64,
+    ) -> Option<CapitalFullyRaisedEvent> {
+        if self.capacity != self.allocated {
+            return None;
+        }
+        // Capacity can be reached again after withdraw, first time is kept
+        if self.filled_at == 0 {
+            self.filled_at = now;
+        }
+        Some(CapitalFullyRaisedEvent {
+            program_version: PROGRAM_VERSION,
+            config: self.config,
+            capital_call,
+            filled_at: self.filled_at,
+        })
+    }
+
+    /// Deposit `amount` reduced so it doesn't leave remaining capacity below `min_deposit`.
+    /// Deposits below `min_deposit` are accepted only if they fill remaining capacity.
+    pub fn clamp_to_min_deposit(&self, amount: u64, remaining: u64) -> Result<u64> {
+        if self.min_deposit == 0 {
+            return Ok(amount);
+        }
+        let left = remaining
+            .checked_sub(amount)
+            .ok_or_else(|| error!(CapitalCallError::CalculationError))?;
+        let amount = if left > 0 && left < self.min_deposit {
+            remaining.saturating_sub(self.min_deposit)
+        } else {
+            amount
+        };
+        require!(
+            amount >= self.min_deposit || amount == remaining,
+            CapitalCallError::DepositBelowMinimum
+        );
+        Ok(amount)
+    }
+
+    /// Amount which still can be deposited
+    pub fn remaining_capacity(&self) -> Result<u64> {
+        require!(self.capacity > 0, CapitalCallError::CapacityNonZero);
+        self.capacity
+            .checked_sub(self.allocated)
+            .and_then(|remaining| remaining.checked_sub(self.pending_amount))
+            .ok_or_else(|| error!(CapitalCallError::CalculationError))
+    }
+
+    /// Vault balance implied by deposits, refunds and finalization
+    pub fn expected_vault_balance(&self) -> Result<u64> {
+        if self.is_lp_minted {
+            return self
+                .vault_reserve
+                .checked_add(self.yield_earned)
+                .ok_or_else(|| error!(CapitalCallError::CalculationError));
+        }
+        self.allocated
+            .checked_sub(self.redeemed)
+            .and_then(|balance| balance.checked_sub(self.secondary_allocated))
+            .and_then(|balance| balance.checked_add(self.pending_amount))
+            .and_then(|balance| balance.checked_add(self.refund_shortfall))
+            .and_then(|balance| balance.checked_sub(self.yield_principal))
+            .and_then(|balance| balance.checked_add(self.yield_earned))
+            .ok_or_else(|| error!(CapitalCallError::CalculationError))
+    }
+
+    /// Same conditions as checked by `refund`
+    pub fn is_refundable(&self, now: u64) -> bool {
+        !self.frozen
+            && (self.is_cancelled
+                || (self.capacity > self.allocated || self.is_finalize_overdue(now))
+                    && now >= self.refund_available_at())
+    }
+
+    /// Part of refunded `amount` paid out to depositor after haircut
+    pub fn refund_after_haircut(&self, amount: u64) -> Result<u64> {
+        u64::try_from(
+            amount as u128 * (BPS_DENOMINATOR - self.refund_haircut_bps as u64) as u128
+                / BPS_DENOMINATOR as u128,
+        )
+        .map_err(|_| error!(CapitalCallError::CalculationError))
+    }
+
+    /// Capital call stays open for `min_open_before_cancel` after start time unless it's frozen
+    /// for emergency
+    pub fn is_cancel_allowed(&self, now: u64, min_open_before_cancel: u64) -> bool {
+        self.frozen || now >= self.start_time.saturating_add(min_open_before_cancel)
+    }
+
+    /// Refunds are locked for `refund_lockup` after end time
+    pub fn refund_available_at(&self) -> u64 {
+        self.end_time.saturating_add(self.refund_lockup)
+    }
+
+    /// Fully funded capital call isn't finalized for `finalize_cooldown` after it's filled
+    pub fn is_in_finalize_cooldown(&self, now: u64) -> bool {
+        self.finalize_cooldown > 0
+            && self.capacity == self.allocated
+            && now < self.filled_at.saturating_add(self.finalize_cooldown)
+    }
+
+    /// LP tokens aren't minted within finalize window after end time
+    pub fn is_finalize_overdue(&self, now: u64) -> bool {
+        self.finalize_window > 0
+            && !self.is_lp_minted
+            && now >= self.end_time.saturating_add(self.finalize_window)
+    }
+
+    /// Time LP tokens of vouchers can be claimed in full at the earliest, assuming finalization
+    /// at end time and vesting of capital call
+    pub fn claim_horizon(&self) -> Result<u64> {
+        let vest_end = self
+            .unlock_points
+            .iter()
+            .map(|point| point.timestamp)
+            .max()
+            .unwrap_or(0)
+            .max(self.vest_start.saturating_add(self.vest_duration));
+        self.end_time
+            .checked_add(self.claim_delay)
+            .and_then(|claim_open| claim_open.checked_add(self.twap_release_duration))
+            .map(|claim_end| claim_end.max(vest_end))
+            .ok_or_else(|| error!(CapitalCallError::CalculationError))
+    }
+
+    /// Part of `total_lp` released pool-wide at `now`, linearly over release duration since
+    /// finalization
+    pub fn released_lp(&self, total_lp: u64, now: u64) -> Result<u64> {
+        if self.twap_release_duration == 0 {
+            return Ok(total_lp);
+        }
+        let elapsed = now.saturating_sub(self.finalized_at);
+        if elapsed >= self.twap_release_duration {
+            return Ok(total_lp);
+        }
+        u64::try_from(total_lp as u128 * elapsed as u128 / self.twap_release_duration as u128)
+            .map_err(|_| error!(CapitalCallError::CalculationError))
+    }
+
+    /// Share of LP tokens unlocked by unlock points up to `now`
+    pub fn unlocked_bps(&self, now: u64) -> u64 {
+        self.unlock_points
+            .iter()
+            .filter(|point| point.bps > 0 && point.timestamp <= now)
+            .map(|point| point.bps as u64)
+            .sum()
+    }
+
+    /// Capital isn't fully transferred to liquidity pool by finalize_chunked yet
+    pub fn is_finalizing(&self) -> bool {
+        self.finalize_chunk > 0 && self.finalize_cursor < self.capacity
+    }
+
+    /// Count `amount` of LP tokens transferred out of lp token pool, in total they can't
+    /// exceed LP tokens minted at finalization
+    pub fn distribute_lp(&mut self, amount: u64) -> Result<()> {
+        let lp_distributed = self
+            .lp_distributed
+            .checked_add(amount)
+            .ok_or_else(|| error!(CapitalCallError::CalculationError))?;
+        require!(
+            lp_distributed <= self.lp_minted,
+            CapitalCallError::OverClaim
+        );
+        self.lp_distributed = lp_distributed;
+        Ok(())
+    }
+
+    /// LP tokens pool balance implied by finalization and claims
+    pub fn expected_lp_pool_balance(&self) -> Result<u64> {
+        self.lp_minted
+            .checked_sub(self.lp_distributed)
+            .ok_or_else(|| error!(CapitalCallError::CalculationError))
+    }
+
+    /// LP tokens pool has to cover all outstanding claims, so accounting drift fails every
+    /// claim instead of the last ones
+    pub fn check_lp_pool_balance(&self, pool_amount: u64) -> Result<()> {
+        require!(
+            pool_amount >= self.expected_lp_pool_balance()?,
+            CapitalCallError::LpPoolShortfall
+        );
+        Ok(())
+    }
+
+    /// Liquidity snapshot taken at `now` has to match valuation stamped within max valuation
+    /// age, if it's enabled
+    pub fn check_valuation(&self, token_liquidity: u64, now: u64) -> Result<()> {
+        if self.max_valuation_age == 0 {
+            return Ok(());
+        }
+        require!(
+            self.valuation_timestamp > 0
+                && now <= self.valuation_timestamp.saturating_add(self.max_valuation_age),
+            CapitalCallError::ValuationStale
+        );
+        require!(
+            token_liquidity == self.valuation_liquidity,
+            CapitalCallError::ValuationMismatch
+        );
+        Ok(())
+    }
 
-    /// CHECK: Only for key
-    pub authority: UncheckedAccount<'info>,
+    /// Preconditions shared by every finalization path, checked on each call of instruction
+    pub fn check_finalizable(
+        &self,
+        config: &Config,
+        lp_mint: &Mint,
+        lp_mint_authority: &Pubkey,
+        now: u64,
+    ) -> Result<()> {
+        require!(!self.frozen, CapitalCallError::CapitalCallFrozen);
+        require!(
+            !self.finalization_paused,
+            CapitalCallError::FinalizationPaused
+        );
+        require!(!self.is_cancelled, CapitalCallError::CapitalCallCancelled);
+        require!(
+            lp_mint.mint_authority == COption::Some(*lp_mint_authority),
+            CapitalCallError::InvalidLpMintAuthority
+        );
+        require!(lp_mint.supply > 0, CapitalCallError::LpTokenSupplyNonZero);
+        require!(
+            lp_mint.decimals == config.lp_decimals,
+            CapitalCallError::LpMintDecimalsMismatch
+        );
+        // Depositors may have been refunded after finalize deadline
+        require!(
+            !self.is_finalize_overdue(now),
+            CapitalCallError::FinalizeDeadlinePassed
+        );
+        require!(
+            !self.is_in_finalize_cooldown(now),
+            CapitalCallError::FinalizeCooldown
+        );
+        if self.capacity == self.allocated && !self.is_lp_minted {
+            require!(
+                config.max_finalize_transfer == 0
+                    || self.finalize_transfer()? <= config.max_finalize_transfer,
+                CapitalCallError::FinalizeTransferTooLarge
+            );
+        }
+        Ok(())
+    }
 
-    /// CHECK: Only for bump calculation
-    #[account(
-        seeds = [
-            SEED_LP_MINT_AUTHORITY.as_ref(),
-            config.key().as_ref(),
-        ], bump
-    )]
-    pub lp_mint_authority: UncheckedAccount<'info>,
+    /// Part of primary capital kept in vault at finalization
+    pub fn finalize_reserve(&self) -> Result<u64> {
+        let capital = self.capacity - self.secondary_allocated;
+        u64::try_from(capital as u128 * self.reserve_bps as u128 / BPS_DENOMINATOR as u128)
+            .map_err(|_| error!(CapitalCallError::CalculationError))
+    }
 
-    pub lp_mint: Account<'info, Mint>,
+    /// Amount transferred from vault to liquidity pool by single finalize instruction
+    pub fn finalize_transfer(&self) -> Result<u64> {
+        let capital = self.capacity - self.secondary_allocated;
+        if self.finalize_chunk > 0 {
+            return Ok(self.finalize_chunk.min(capital));
+        }
+        Ok(capital - self.finalize_reserve()?)
+    }
 
-    #[account(
-        constraint = liquidity_pool.mint == MINT_PUBKEY.parse::<Pubkey>().unwrap()
-    )]
-    pub liquidity_pool: Account<'info, TokenAccount>,
+    /// Finalize variants other than mint_lp_tokens move whole capital to liquidity pool
+    pub fn check_plain_finalize(&self, config: &Config) -> Result<()> {
+        require!(!self.custodial_vault, CapitalCallError::InvalidCustodian);
+        require!(
+            self.yield_program == Pubkey::default(),
+            CapitalCallError::InvalidYieldProgram
+        );
+        require!(
+            self.secondary_mint == Pubkey::default(),
+            CapitalCallError::SecondaryDepositsUnsupported
+        );
+        require!(self.reserve_bps == 0, CapitalCallError::ReserveUnsupported);
+        require!(
+            self.fast_fill_fee_bps == 0 && self.slow_fill_fee_bps == 0,
+            CapitalCallError::FillFeeUnsupported
+        );
+        // Vault surplus is swept to treasury only by mint_lp_tokens
+        require!(
+            config.surplus_treasury == Pubkey::default(),
+            CapitalCallError::SurplusSweepUnsupported
+        );
+        require!(
+            !self.use_oracle_conversion,
+            CapitalCallError::OracleConversionUnsupported
+        );
+        Ok(())
+    }
 
-    #[account(mut)]
-    pub payer: Signer<'info>,
+    /// Stamp LP supply and token liquidity LP tokens are minted against
+    pub fn take_snapshot(&mut self, lp_supply: u64, token_liquidity: u64, now: u64) -> Result<()> {
+        self.check_valuation(token_liquidity, now)?;
+        self.lp_supply = lp_supply;
+        self.token_liquidity = token_liquidity;
+        self.check_rate()
+    }
 
-    pub system_program: Program<'info, System>,
+    /// LP supply must grow only by minted tokens, which capital is deployed against
+    pub fn check_lp_supply(&self, lp_supply: u64, minted: u64) -> Result<()> {
+        require!(
+            lp_supply == self.lp_supply + minted,
+            CapitalCallError::LpSupplyMismatch
+        );
+        Ok(())
+    }
+
+    /// Funding progress in basis points of capacity
+    pub fn fill_bps(&self) -> Result<u64> {
+        require!(self.capacity > 0, CapitalCallError::CapacityNonZero);
+        u64::try_from(self.allocated as u128 * BPS_DENOMINATOR as u128 / self.capacity as u128)
+            .map_err(|_| error!(CapitalCallError::CalculationError))
+    }
 }
 
-#[derive(Accounts)]
-#[instruction(start_time: u64, duration: u64, capacity: u64)]
-pub struct CreateCapitalCall<'info> {
-    #[account(
-        has_one = authority,
-        has_one = lp_mint,
-    )]
-    pub config: Box<Account<'info, Config>>,
+/// Tier record of wallet kept by `tier_oracle` of config
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct OracleTier {
+    pub wallet: Pubkey,
+    pub tier: u8,
+}
 
-    #[account(
-        init,
-        payer = payer,
-        space = CapitalCall::SPACE,
-        seeds = [
-            SEED_CAPITAL_CALL.as_ref(),
-            config.key().as_ref(),
-            start_time.to_le_bytes().as_ref(),
-            capacity.to_le_bytes().as_ref(),
-        ],
-        bump
-    )]
-    pub capital_call: Box<Account<'info, CapitalCall>>,
+/// Price of raised token in base units of liquidity pool kept by `oracle` of capital call,
+/// `price * 10^expo` with confidence interval `conf` in the same units
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct OraclePrice {
+    pub price: i64,
+    pub conf: u64,
+    pub expo: i32,
+    pub publish_time: i64,
+}
 
-    #[account(
-        init,
-        payer = payer,
-        token::mint = mint,
-        token::authority = capital_call,
-        seeds = [
-            SEED_VAULT.as_ref(),
-            capital_call.key().as_ref(),
-        ],
-        bump
-    )]
-    pub vault: Box<Account<'info, TokenAccount>>,
+/// Arguments of instruction invoked on `finalize_callback_program`
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct FinalizeCallbackArgs {
+    pub capital_call: Pubkey,
+    pub minted: u64,
+}
 
-    #[account(address = MINT_PUBKEY.parse::<Pubkey>().unwrap())]
-    pub mint: Box<Account<'info, Mint>>,
+/// How voucher is settled, a voucher is either refunded or claimed, never both
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum SettlementKind {
+    Unsettled,
+    Refund,
+    Claim,
+}
 
-    #[account(
-        init,
-        payer = payer,
-        token::mint = lp_mint,
-        token::authority = capital_call,
-        seeds = [
-            SEED_LP_TOKEN_POOL.as_ref(),
-            capital_call.key().as_ref(),
-        ], bump
-    )]
-    pub lp_token_pool: Box<Account<'info, TokenAccount>>,
-    pub lp_mint: Box<Account<'info, Mint>>,
+impl Default for SettlementKind {
+    fn default() -> Self {
+        SettlementKind::Unsettled
+    }
+}
 
-    pub authority: Signer<'info>,
+#[account]
+#[derive(Default)]
+pub struct Voucher {
+    pub capital_call: Pubkey,
+    pub authority: Pubkey,
+    pub amount: u64,
 
-    #[account(mut)]
-    pub payer: Signer<'info>,
+    // Time of last withdraw, zero if never withdrawn
+    pub last_refund_at: u64,
 
-    pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>,
-    pub rent: Sysvar<'info, Rent>,
-}
+    // Referrer earning fee from voucher's LP tokens, default pubkey if not referred
+    pub referrer: Pubkey,
 
-#[derive(Accounts)]
-pub struct Deposit<'info> {
-    #[account(
-        mut,
-        seeds = [
-            SEED_CAPITAL_CALL.as_ref(),
-            capital_call.config.as_ref(),
-            capital_call.start_time.to_le_bytes().as_ref(),
-            capital_call.capacity.to_le_bytes().as_ref(),
-        ],
-        bump = capital_call.bump
-    )]
-    pub capital_call: Account<'info, CapitalCall>,
+    // Token account of deposit and its owner
+    pub source_snapshot: Pubkey,
+    pub source_owner: Pubkey,
 
-    #[account(
-        init,
-        payer = authority,
-        space = Voucher::SPACE,
-        seeds = [
-            SEED_VOUCHER.as_ref(),
-            capital_call.key().as_ref(),
-            authority.key().as_ref(),
-        ],
-        bump
-    )]
-    pub voucher: Account<'info, Voucher>,
+    // Part of amount matched from match reserve of capital call
+    pub matched: u64,
 
-    #[account(
-        mut,
-        seeds = [
-            SEED_VAULT.as_ref(),
-            capital_call.key().as_ref(),
-        ],
-        bump = capital_call.vault_bump,
-    )]
-    pub vault: Account<'info, TokenAccount>,
+    // Amount is deposited in secondary mint to secondary vault
+    pub secondary: bool,
 
-    #[account(mut)]
-    pub authority: Signer<'info>,
+    // Memo of depositor given at deposit, opaque to the program
+    pub memo: [u8; 32],
 
-    #[account(mut)]
-    pub source: Account<'info, TokenAccount>,
+    // Deposited amount waiting for confirm_deposit since slot
+    pub pending_amount: u64,
+    pub pending_since_slot: u64,
 
-    pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>,
+    // Amount at the time of capital call snapshot, recorded by snapshot_vouchers
+    pub snapshot_amount: u64,
+    pub snapshotted: bool,
+
+    // Vesting schedule used instead of capital call schedule if `vest_override` is set
+    pub vest_override: bool,
+    pub vest_override_start: u64,
+    pub vest_override_cliff: u64,
+    pub vest_override_duration: u64,
+
+    // LP tokens (including referral fee) already claimed with claim_vested
+    pub claimed_lp: u64,
+
+    // Number of claim_vested before LP tokens were fully vested
+    pub claim_count: u8,
+
+    // Tier of depositor by tier oracle of config at deposit, informational
+    pub tier: u8,
+
+    // Set by the first refund or claim, settlement of the other kind is rejected
+    pub settled_via: SettlementKind,
+
+    pub bump: u8,
 }
 
-impl<'a, 'b, 'c, 'info> From<&mut Deposit<'info>>
-    for CpiContext<'a, 'b, 'c, 'info, Transfer<'info>>
-{
-    fn from(accounts: &mut Deposit<'info>) -> CpiContext<'a, 'b, 'c, 'info, Transfer<'info>> {
-        let cpi_accounts = Transfer {
-            from: accounts.source.to_account_info(),
-            to: accounts.vault.to_account_info(),
-            authority: accounts.authority.to_account_info(),
+impl Voucher {
+    pub const SPACE: usize = 8 + std::mem::size_of::<Voucher>();
+
+    /// Mark voucher as settled by `kind`, voucher already settled otherwise is rejected
+    pub fn settle(&mut self, kind: SettlementKind) -> Result<()> {
+        require!(
+            self.settled_via == SettlementKind::Unsettled || self.settled_via == kind,
+            CapitalCallError::AlreadySettledOtherwise
+        );
+        self.settled_via = kind;
+        Ok(())
+    }
+
+    /// Part of `total_lp` vested at `now` by schedule of voucher or capital call, limited by
+    /// LP tokens released by capital call
+    pub fn vested_lp(&self, capital_call: &CapitalCall, total_lp: u64, now: u64) -> Result<u64> {
+        let released = capital_call.released_lp(total_lp, now)?;
+        Ok(self.scheduled_lp(capital_call, total_lp, now)?.min(released))
+    }
+
+    fn scheduled_lp(&self, capital_call: &CapitalCall, total_lp: u64, now: u64) -> Result<u64> {
+        let (start, cliff, duration) = if self.vest_override {
+            (
+                self.vest_override_start,
+                self.vest_override_cliff,
+                self.vest_override_duration,
+            )
+        } else {
+            (
+                capital_call.vest_start,
+                capital_call.vest_cliff,
+                capital_call.vest_duration,
+            )
         };
-        let cpi_program = accounts.token_program.to_account_info();
-        CpiContext::new(cpi_program, cpi_accounts)
+
+        if !self.vest_override && capital_call.unlock_points[0].bps > 0 {
+            return u64::try_from(
+                total_lp as u128 * capital_call.unlocked_bps(now) as u128
+                    / BPS_DENOMINATOR as u128,
+            )
+            .map_err(|_| error!(CapitalCallError::CalculationError));
+        }
+        if duration == 0 {
+            return Ok(total_lp);
+        }
+        if now < start.saturating_add(cliff) {
+            return Ok(0);
+        }
+        let elapsed = now - start;
+        if elapsed >= duration {
+            return Ok(total_lp);
+        }
+        u64::try_from(total_lp as u128 * elapsed as u128 / duration as u128)
+            .map_err(|_| error!(CapitalCallError::CalculationError))
     }
 }
 
-#[derive(Accounts)]
-pub struct Refund<'info> {
-    #[account(
-        mut,
-        seeds = [
-            SEED_CAPITAL_CALL.as_ref(),
-            capital_call.config.as_ref(),
-            capital_call.start_time.to_le_bytes().as_ref(),
-            capital_call.capacity.to_le_bytes().as_ref(),
-        ],
-        bump = capital_call.bump
-    )]
-    pub capital_call: Account<'info, CapitalCall>,
+#[account]
+pub struct Referral {
+    pub capital_call: Pubkey,
+    pub referrer: Pubkey,
 
-    #[account(
-        mut,
-        close = authority,
-        seeds = [
-            SEED_VOUCHER.as_ref(),
-            capital_call.key().as_ref(),
-            authority.key().as_ref(),
-        ],
-        bump = voucher.bump,
-        has_one = authority,
-        has_one = capital_call,
-    )]
-    pub voucher: Account<'info, Voucher>,
+    // LP tokens withheld from claims of referred depositors and not yet claimed
+    pub accrued_lp: u64,
+    pub bump: u8,
+}
 
-    #[account(
-        mut,
-        seeds = [
-            SEED_VAULT.as_ref(),
-            capital_call.key().as_ref(),
-        ],
-        bump = capital_call.vault_bump,
-    )]
-    pub vault: Account<'info, TokenAccount>,
+impl Referral {
+    pub const SPACE: usize = 8 + std::mem::size_of::<Referral>();
+}
+
+#[account]
+pub struct LpLockup {
+    pub capital_call: Pubkey,
+    pub authority: Pubkey,
+    pub escrow: Pubkey,
 
-    #[account(mut)]
-    pub authority: Signer<'info>,
+    // LP tokens claimed to escrow, released not earlier than release time
+    pub amount: u64,
+    pub release_at: u64,
+    pub bump: u8,
+}
 
-    #[account(mut)]
-    pub destination: Account<'info, TokenAccount>,
+impl LpLockup {
+    pub const SPACE: usize = 8 + std::mem::size_of::<LpLockup>();
+}
 
-    pub token_program: Program<'info, Token>,
+#[account]
+pub struct Snapshot {
+    pub capital_call: Pubkey,
+    pub taken_at: u64,
+    pub total_allocated: u64,
+    // Vouchers recorded by snapshot_vouchers
+    pub vouchers: u32,
+    pub bump: u8,
 }
 
-impl<'a, 'b, 'c, 'info> From<&mut Refund<'info>>
-    for CpiContext<'a, 'b, 'c, 'info, Transfer<'info>>
-{
-    fn from(accounts: &mut Refund<'info>) -> CpiContext<'a, 'b, 'c, 'info, Transfer<'info>> {
-        let cpi_accounts = Transfer {
-            from: accounts.vault.to_account_info(),
-            to: accounts.destination.to_account_info(),
-            authority: accounts.capital_call.to_account_info(),
-        };
-        let cpi_program = accounts.token_program.to_account_info();
-        CpiContext::new(cpi_program, cpi_accounts)
-    }
+impl Snapshot {
+    pub const SPACE: usize = 8 + std::mem::size_of::<Snapshot>();
 }
 
-#[derive(Accounts)]
-pub struct MintLpTokens<'info> {
-    #[account(
-        has_one = lp_mint,
-        has_one = lp_mint_authority,
-        has_one = liquidity_pool,
-    )]
-    pub config: Account<'info, Config>,
+#[error_code]
+pub enum CapitalCallError {
+    BumpSeedNotInHashMap,
+    InvalidAuthority,
+    CapitalCallFrozen,
+    FinalizationPaused,
+    CapitalCallCancelled,
+    CapitalCallNotCancelled,
+    CapitalCallRetained,
+    CapitalCallNotRetained,
+    CancelTooEarly,
+    ProgramKilled,
 
-    #[account(
-        mut,
-        seeds = [
-            SEED_CAPITAL_CALL.as_ref(),
-            capital_call.config.as_ref(),
-            capital_call.start_time.to_le_bytes().as_ref(),
-            capital_call.capacity.to_le_bytes().as_ref(),
-        ],
-        bump = capital_call.bump,
-        has_one = config,
-        has_one = lp_token_pool,
-    )]
-    pub capital_call: Account<'info, CapitalCall>,
+    // Create Capital Call errors
+    DuplicateCapitalCall,
+    StartTimeMustBeInFuture,
+    DurationNonZero,
+    CapacityNonZero,
+    CreditTooLow,
+    TooManyOpenCalls,
+    StartTimeTooSoon,
+    InvalidDebtAccount,
+    CreditOutstandingMismatch,
+    InvalidBatch,
+    WindowTooShort,
+    CapacityOutOfRange,
+
+    // Update Capital Call errors
+    CapitalCallTermsLocked,
+    InvalidBps,
+    InvalidBonusTiers,
+    InvalidUnlockPoints,
+
+    // Shorten Duration errors
+    LpTokensAlreadyMinted,
+    EndTimeMustBeInFuture,
+    EndTimeMustBeAfterStartTime,
+    EndTimeMustBeShortened,
+
+    // Auto Extend errors
+    AutoExtendNotAllowed,
+    InsufficientFillToExtend,
 
-    #[account(
-        mut,
-        seeds = [
-            SEED_VAULT.as_ref(),
-            capital_call.key().as_ref(),
-        ],
-        bump = capital_call.vault_bump,
-    )]
-    pub vault: Account<'info, TokenAccount>,
+    // Deposit errors
+    CapitalCallNotStarted,
+    CapitalCallEnded,
+    CapitalCallAlreadyFullyFunded,
+    AmountNonZero,
+    VaultBalanceMismatch,
+    VoucherAlreadyExists,
+    RefundCooldown,
+    InvalidReferral,
+    MintNotNative,
+    RefundToClosedSource,
+    WrappedSourceNotEmpty,
+    InvalidCreditToken,
+    InsufficientCreditTokens,
+    UnauthorizedDepositorProgram,
+    DepositBelowMinimum,
+    ExceedsRemainingCapacity,
+    InvalidStakeAccount,
+    StakeCapExceeded,
+    LiquidityPoolUnhealthy,
+    InvalidLiquidityPool,
+    InvalidGatekeeperSignature,
+    InvalidInstructionsSysvar,
+    InvalidMatchReserve,
+    SecondaryMintAlreadySet,
+    SecondaryMintDecimalsMismatch,
+    SecondaryDepositsUnsupported,
+    InvalidSecondaryVault,
+    DepositNotConfirmed,
+    InvalidTierRecord,
+    SourceAccountFrozen,
+    SourceAccountDelegated,
+    InvalidParticipationAccount,
+
+    // Snapshot
+    InvalidVoucher,
+    BatchTooLarge,
+    SnapshotOutdated,
+    VoucherAlreadySnapshotted,
 
-    #[account(mut)]
-    pub liquidity_pool: Account<'info, TokenAccount>,
+    // Refund errors
+    CapitalCallNotEnded,
+    CapitalCallIsFullyFunded,
+    RefundLockup,
+    RefundMustGoToSource,
+    RefundMustGoToSourceOwner,
+    InvalidDestination,
+    ManualRefundRequired,
 
-    /// CHECK: Only for bump calculation
-    #[account(
-        seeds = [
-            SEED_LP_MINT_AUTHORITY.as_ref(),
-            config.key().as_ref(),
-        ], bump = config.bump
-    )]
-    pub lp_mint_authority: UncheckedAccount<'info>,
+    // Mint LP Tokens
+    InvalidLpMintAuthority,
+    LpTokenSupplyNonZero,
+    LpMintDecimalsMismatch,
+    CalculationError,
+    InvalidFinalizeCallbackProgram,
+    FinalizeDeadlinePassed,
+    LpSupplyMismatch,
+    RateOutOfBounds,
+    FinalizeTransferTooLarge,
+    FinalizeCooldown,
+    ValuationStale,
+    ValuationMismatch,
+    InvalidCustodian,
+    InvalidSurplusTreasury,
+    InvalidOracle,
+    InvalidOraclePrice,
+    OraclePriceStale,
+    OraclePriceUncertain,
+
+    // Finalize Split
+    CapitalCallNotFullyFunded,
+    InvalidSplit,
+
+    // Finalize and Distribute
+    InvalidDistribution,
+    ReserveUnsupported,
+    FillFeeUnsupported,
+    SurplusSweepUnsupported,
+    OracleConversionUnsupported,
+
+    // Finalize Chunked
+    FinalizeNotChunked,
+    ChunkedFinalizeRequired,
+    FinalizeAlreadyComplete,
+    FinalizationInProgress,
 
-    #[account(
-        mut,
-        seeds = [
-            SEED_LP_TOKEN_POOL.as_ref(),
-            capital_call.key().as_ref(),
-        ],
-        bump = capital_call.lp_token_pool_bump
-    )]
-    pub lp_token_pool: Account<'info, TokenAccount>,
+    // Claim
+    LpTokenNotMinted,
+    VestingNotComplete,
+    VestingAlreadyClaimed,
+    InvalidFeeLpAccount,
+    ClaimBelowMinimum,
+    ClaimNotOpenYet,
+    TooManyPartialClaims,
+    LpPoolShortfall,
+    InvalidLpTokenPoolMint,
+    InvalidDestinationMint,
+    AlreadySettledOtherwise,
+    OverClaim,
+
+    // Void Voucher errors
+    VoucherNotExpired,
+    InvalidVoidTreasury,
+    InvalidVoucherExpiry,
+
+    // Unwind errors
+    UnwindNotAllowed,
+
+    // LP Lockup errors
+    InvalidLpLockup,
+    LpLockupNotElapsed,
+
+    // Yield strategy errors
+    InvalidYieldProgram,
+    YieldPrincipalNotReturned,
 
-    #[account(mut)]
-    pub lp_mint: Account<'info, Mint>,
+    // Close
+    CapitalCallHasToBeFullyRefunded,
+    LpTokensHasToBeFullyDistributed,
+    InsufficientVaultBalance,
+    ReceiverNotPayer,
 
-    pub token_program: Program<'info, Token>,
+    // Close Config
+    OpenCallsRemain,
+    CapitalCallNotClosed,
+    RetainedVaultsRemain,
 }
 
-#[derive(Accounts)]
-pub struct Claim<'info> {
-    #[account(
-        mut,
-        seeds = [
-            SEED_CAPITAL_CALL.as_ref(),
-            capital_call.config.as_ref(),
-            capital_call.start_time.to_le_bytes().as_ref(),
-            capital_call.capacity.to_le_bytes().as_ref(),
-        ],
-        bump = capital_call.bump,
-        has_one = lp_token_pool,
-    )]
-    pub capital_call: Account<'info, CapitalCall>,
-
-    #[account(
-        mut,
-        seeds = [
-            SEED_LP_TOKEN_POOL.as_ref(),
-            capital_call.key().as_ref()],
-        bump = capital_call.lp_token_pool_bump
-    )]
-    pub lp_token_pool: Account<'info, TokenAccount>,
+#[event]
+pub struct CreateCapitalCallEvent {
+    pub program_version: u16,
+    pub config: Pubkey,
+    pub capital_call: Pubkey,
+    pub call_id: u64,
+    pub start_time: u64,
+    pub end_time: u64,
+    pub capacity: u64,
+    pub refund_available_at: u64,
+}
 
-    #[account(mut)]
-    pub authority: Signer<'info>,
+#[event]
+pub struct KillSwitchEvent {
+    pub program_version: u16,
+    pub config: Pubkey,
+    pub killed: bool,
+}
 
-    #[account(
-        mut,
-        close = authority,
-        seeds = [
-            SEED_VOUCHER.as_ref(),
-            capital_call.key().as_ref(),
-            authority.key().as_ref()],
-        bump = voucher.bump,
-        has_one = authority,
-        has_one = capital_call,
-    )]
-    pub voucher: Account<'info, Voucher>,
+#[event]
+pub struct CapitalCallFrozenEvent {
+    pub program_version: u16,
+    pub config: Pubkey,
+    pub capital_call: Pubkey,
+}
 
-    #[account(mut)]
-    pub destination: Account<'info, TokenAccount>,
+#[event]
+pub struct CapitalCallUnfrozenEvent {
+    pub program_version: u16,
+    pub config: Pubkey,
+    pub capital_call: Pubkey,
+}
 
-    pub token_program: Program<'info, Token>,
+#[event]
+pub struct CapitalCallUnwoundEvent {
+    pub program_version: u16,
+    pub config: Pubkey,
+    pub capital_call: Pubkey,
+    pub amount: u64,
 }
 
-impl<'a, 'b, 'c, 'info> From<&mut Claim<'info>> for CpiContext<'a, 'b, 'c, 'info, Transfer<'info>> {
-    fn from(accounts: &mut Claim<'info>) -> CpiContext<'a, 'b, 'c, 'info, Transfer<'info>> {
-        let cpi_accounts = Transfer {
-            from: accounts.lp_token_pool.to_account_info(),
-            to: accounts.destination.to_account_info(),
-            authority: accounts.capital_call.to_account_info(),
-        };
-        let cpi_program = accounts.token_program.to_account_info();
-        CpiContext::new(cpi_program, cpi_accounts)
-    }
+#[event]
+pub struct YieldWithdrawnEvent {
+    pub program_version: u16,
+    pub config: Pubkey,
+    pub capital_call: Pubkey,
+    pub principal: u64,
+    pub earned: u64,
 }
 
-#[derive(Accounts)]
-pub struct CloseCapitalCall<'info> {
-    #[account(
-        has_one = authority,
-        has_one = lp_mint,
-    )]
-    pub config: Box<Account<'info, Config>>,
+#[event]
+pub struct RefundHaircutSetEvent {
+    pub program_version: u16,
+    pub config: Pubkey,
+    pub capital_call: Pubkey,
+    pub haircut_bps: u16,
+}
 
-    #[account(
-        mut,
-        close = receiver,
-        seeds = [
-            SEED_CAPITAL_CALL.as_ref(),
-            capital_call.config.as_ref(),
-            capital_call.start_time.to_le_bytes().as_ref(),
-            capital_call.capacity.to_le_bytes().as_ref(),
-        ],
-        bump = capital_call.bump,
-        has_one = config,
-        has_one = vault,
-        has_one = lp_token_pool,
-    )]
-    pub capital_call: Box<Account<'info, CapitalCall>>,
+#[event]
+pub struct CapitalCallCancelledEvent {
+    pub program_version: u16,
+    pub config: Pubkey,
+    pub capital_call: Pubkey,
+}
 
-    pub authority: Signer<'info>,
+#[event]
+pub struct ValuationUpdatedEvent {
+    pub program_version: u16,
+    pub config: Pubkey,
+    pub capital_call: Pubkey,
+    pub token_liquidity: u64,
+    pub valuation_timestamp: u64,
+}
 
-    #[account(mut)]
-    pub receiver: SystemAccount<'info>,
+#[event]
+pub struct FinalizationPausedEvent {
+    pub program_version: u16,
+    pub config: Pubkey,
+    pub capital_call: Pubkey,
+    pub paused: bool,
+}
 
-    #[account(
-        mut,
-        seeds = [
-            SEED_LP_TOKEN_POOL.as_ref(),
-            capital_call.key().as_ref()],
-        bump = capital_call.lp_token_pool_bump
-    )]
-    pub lp_token_pool: Box<Account<'info, TokenAccount>>,
+#[event]
+pub struct EndTimeShortenedEvent {
+    pub program_version: u16,
+    pub config: Pubkey,
+    pub capital_call: Pubkey,
+    pub old_end_time: u64,
+    pub new_end_time: u64,
+}
 
-    #[account(
-        mut,
-        seeds = [
-            SEED_VAULT.as_ref(),
-            capital_call.key().as_ref()],
-        bump = capital_call.vault_bump,
-    )]
-    pub vault: Box<Account<'info, TokenAccount>>,
+#[event]
+pub struct EndTimeExtendedEvent {
+    pub program_version: u16,
+    pub config: Pubkey,
+    pub capital_call: Pubkey,
+    pub old_end_time: u64,
+    pub new_end_time: u64,
+}
 
-    #[account(mut)]
-    pub lp_mint: Box<Account<'info, Mint>>,
+#[event]
+pub struct DepositEvent {
+    pub program_version: u16,
+    pub config: Pubkey,
+    pub capital_call: Pubkey,
+    pub authority: Pubkey,
+    pub amount: u64,
+    pub matched: u64,
+    pub memo: [u8; 32],
+}
 
-    #[account(mut)]
-    pub destination: Box<Account<'info, TokenAccount>>,
+#[event]
+pub struct FundingProgressEvent {
+    pub program_version: u16,
+    pub config: Pubkey,
+    pub capital_call: Pubkey,
+    pub allocated: u64,
+    pub capacity: u64,
+    pub fill_bps: u64,
+}
 
-    pub token_program: Program<'info, Token>,
+#[event]
+pub struct CapitalFullyRaisedEvent {
+    pub program_version: u16,
+    pub config: Pubkey,
+    pub capital_call: Pubkey,
+    pub filled_at: u64,
 }
 
-#[account]
-pub struct Config {
+#[event]
+pub struct RefundEvent {
+    pub program_version: u16,
+    pub config: Pubkey,
+    pub capital_call: Pubkey,
     pub authority: Pubkey,
-    pub liquidity_pool: Pubkey,
-    pub lp_mint: Pubkey,
-    pub lp_mint_authority: Pubkey,
-    pub bump: u8,
+    pub amount: u64,
+    pub shortfall: u64,
 }
 
-impl Config {
-    pub const SPACE: usize = 8 + std::mem::size_of::<Config>();
+#[event]
+pub struct RefundPreviewEvent {
+    pub program_version: u16,
+    pub config: Pubkey,
+    pub capital_call: Pubkey,
+    pub authority: Pubkey,
+    pub eligible: bool,
+    pub amount: u64,
 }
 
-#[account]
-pub struct CapitalCall {
+#[event]
+pub struct DepositConfirmedEvent {
+    pub program_version: u16,
     pub config: Pubkey,
-    pub vault: Pubkey,
-    pub lp_token_pool: Pubkey,
-
-    // Start time of capital call
-    pub start_time: u64,
-
-    // End time of capital call
-    pub end_time: u64,
-
-    // Expected amount
-    pub capacity: u64,
+    pub capital_call: Pubkey,
+    pub authority: Pubkey,
+    pub amount: u64,
+}
 
-    // Allocated amount
-    pub allocated: u64,
+#[event]
+pub struct WithdrawEvent {
+    pub program_version: u16,
+    pub config: Pubkey,
+    pub capital_call: Pubkey,
+    pub authority: Pubkey,
+    pub amount: u64,
+}
 
-    // Redeemed or return tokens
-    pub redeemed: u64,
+#[event]
+pub struct SurplusSweptEvent {
+    pub program_version: u16,
+    pub config: Pubkey,
+    pub capital_call: Pubkey,
+    pub treasury: Pubkey,
+    pub amount: u64,
+}
 
+#[event]
+pub struct LpTokensMintedEvent {
+    pub program_version: u16,
+    pub config: Pubkey,
+    pub capital_call: Pubkey,
     pub token_liquidity: u64,
     pub lp_supply: u64,
     pub credit_outstanding: u64,
-
-    pub is_lp_minted: bool,
-
-    pub bump: u8,
-    pub vault_bump: u8,
-    pub lp_token_pool_bump: u8,
+    pub capital: u64,
+    pub minted: u64,
+    pub fill_fee: u64,
 }
 
-impl CapitalCall {
-    pub const SPACE: usize = 8 + std::mem::size_of::<CapitalCall>();
+#[event]
+pub struct RedeemEvent {
+    pub program_version: u16,
+    pub config: Pubkey,
+    pub capital_call: Pubkey,
+    pub authority: Pubkey,
+    pub lp_amount: u64,
+    pub amount: u64,
+    pub vault_reserve: u64,
+}
 
-    pub fn to_lp_token(&self, amount: u64) -> Result<u64> {
-        u64::try_from(
-            amount as u128 * (self.token_liquidity as u128 + self.credit_outstanding as u128)
-                / self.lp_supply as u128,
-        )
-        .map_err(|_| error!(CapitalCallError::CalculationError))
-    }
+#[event]
+pub struct SnapshotEvent {
+    pub program_version: u16,
+    pub config: Pubkey,
+    pub capital_call: Pubkey,
+    pub taken_at: u64,
+    pub total_allocated: u64,
+    pub vouchers: u32,
 }
 
-#[account]
-pub struct Voucher {
+#[event]
+pub struct AllocationEvent {
+    pub program_version: u16,
+    pub config: Pubkey,
     pub capital_call: Pubkey,
+    pub voucher: Pubkey,
     pub authority: Pubkey,
     pub amount: u64,
-    pub bump: u8,
+    pub computed_lp: u64,
 }
 
-impl Voucher {
-    pub const SPACE: usize = 8 + std::mem::size_of::<Voucher>();
+#[event]
+pub struct VoucherVoidedEvent {
+    pub program_version: u16,
+    pub config: Pubkey,
+    pub capital_call: Pubkey,
+    pub authority: Pubkey,
+    pub amount: u64,
+    pub lp_amount: u64,
+    pub treasury: Pubkey,
 }
 
-#[error_code]
-pub enum CapitalCallError {
-    BumpSeedNotInHashMap,
-
-    // Create Capital Call errors
-    StartTimeMustBeInFuture,
-    DurationNonZero,
-    CapacityNonZero,
-
-    // Deposit errors
-    CapitalCallNotStarted,
-    CapitalCallEnded,
-    CapitalCallAlreadyFullyFunded,
-    AmountNonZero,
+#[event]
+pub struct OrphanVoucherRecoveredEvent {
+    pub program_version: u16,
+    pub config: Pubkey,
+    pub capital_call: Pubkey,
+    pub voucher: Pubkey,
+    pub authority: Pubkey,
+    pub amount: u64,
+}
 
-    // Refund errors
-    CapitalCallNotEnded,
-    CapitalCallIsFullyFunded,
+#[event]
+pub struct VoucherStatusEvent {
+    pub program_version: u16,
+    pub config: Pubkey,
+    pub capital_call: Pubkey,
+    pub authority: Pubkey,
+    pub amount: u64,
+    pub claimable_lp: u64,
+    pub vested: u64,
+    pub is_claimable: bool,
+}
 
-    // Mint LP Tokens
-    InvalidLpMintAuthority,
-    LpTokenSupplyNonZero,
-    CalculationError,
+#[event]
+pub struct ConfigStatsEvent {
+    pub program_version: u16,
+    pub config: Pubkey,
+    pub total_allocated: u64,
+    pub total_capacity: u64,
+}
 
-    // Claim
-    LpTokenNotMinted,
+#[event]
+pub struct CapitalDeployedEvent {
+    pub program_version: u16,
+    pub config: Pubkey,
+    pub capital_call: Pubkey,
+    pub liquidity_pool: Pubkey,
+    pub amount: u64,
+}
 
-    // Close
-    CapitalCallHasToBeFullyRefunded,
-    LpTokensHasToBeFullyDistributed,
+#[event]
+pub struct FinalizeProgressEvent {
+    pub program_version: u16,
+    pub config: Pubkey,
+    pub capital_call: Pubkey,
+    pub amount: u64,
+    pub finalize_cursor: u64,
+    pub capital: u64,
 }
 
 #[event]
-pub struct DepositEvent {
+pub struct ClaimEvent {
+    pub program_version: u16,
     pub config: Pubkey,
     pub capital_call: Pubkey,
     pub authority: Pubkey,
     pub amount: u64,
+    pub lp_amount: u64,
+    pub referral_fee: u64,
+    pub claim_fee: u64,
+    pub lp_minted: u64,
+    pub lp_distributed: u64,
 }
 
 #[event]
-pub struct CapitalFullyRaisedEvent {
+pub struct VestedClaimEvent {
+    pub program_version: u16,
     pub config: Pubkey,
     pub capital_call: Pubkey,
+    pub authority: Pubkey,
+    pub lp_amount: u64,
+    pub referral_fee: u64,
+    pub claim_fee: u64,
+    pub claimed_lp: u64,
+    pub lp_minted: u64,
+    pub lp_distributed: u64,
 }
 
 #[event]
-pub struct RefundEvent {
+pub struct LpReleasedEvent {
+    pub program_version: u16,
     pub config: Pubkey,
     pub capital_call: Pubkey,
     pub authority: Pubkey,
@@ -912,21 +8266,412 @@ pub struct RefundEvent {
 }
 
 #[event]
-pub struct LpTokensMintedEvent {
+pub struct StrandedLpRecoveredEvent {
+    pub program_version: u16,
     pub config: Pubkey,
     pub capital_call: Pubkey,
-    pub token_liquidity: u64,
-    pub lp_supply: u64,
-    pub credit_outstanding: u64,
-    pub capital: u64,
-    pub minted: u64,
+    pub destination: Pubkey,
+    pub amount: u64,
 }
 
 #[event]
-pub struct ClaimEvent {
+pub struct ReferralClaimedEvent {
+    pub program_version: u16,
     pub config: Pubkey,
     pub capital_call: Pubkey,
-    pub authority: Pubkey,
-    pub amount: u64,
+    pub referrer: Pubkey,
     pub lp_amount: u64,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_spl::token::spl_token::state::AccountState;
+
+    fn capital_call(capacity: u64, allocated: u64) -> CapitalCall {
+        CapitalCall {
+            capacity,
+            allocated,
+            token_liquidity: 2_435_827,
+            lp_supply: 9_127_492,
+            credit_outstanding: 7_348_028,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn to_lp_token_matches_specification() {
+        let cc = capital_call(2_000_000, 2_000_000);
+        assert_eq!(cc.to_lp_token(2_000_000).unwrap(), 2_143_821);
+        assert_eq!(cc.to_lp_token(1_000_000).unwrap(), 1_071_910);
+    }
+
+    #[test]
+    fn to_lp_token_rounding() {
+        let mut cc = capital_call(2_000_000, 2_000_000);
+        cc.deposit_count = 2;
+        assert_eq!(cc.to_lp_token(1).unwrap(), 1);
+        assert_eq!(cc.to_lp_token(9_127_492).unwrap(), 9_783_855);
+        assert_eq!(cc.lp_to_mint().unwrap(), 2_143_821);
+
+        cc.round_up_claims = true;
+        assert_eq!(cc.to_lp_token(1).unwrap(), 2);
+        assert_eq!(cc.to_lp_token(1_000_000).unwrap(), 1_071_911);
+        assert_eq!(cc.to_lp_token(9_127_492).unwrap(), 9_783_855);
+        assert_eq!(cc.lp_to_mint().unwrap(), 2_143_824);
+    }
+
+    #[test]
+    fn zero_capacity_is_rejected() {
+        let cc = capital_call(0, 0);
+        let expected: error::Error = CapitalCallError::CapacityNonZero.into();
+        assert_eq!(cc.to_lp_token(1_000_000).unwrap_err(), expected);
+        assert_eq!(cc.remaining_capacity().unwrap_err(), expected);
+        assert_eq!(cc.fill_bps().unwrap_err(), expected);
+    }
+
+    #[test]
+    fn min_deposit_leaves_no_sliver() {
+        let mut cc = capital_call(1_000, 0);
+        cc.min_deposit = 100;
+        assert_eq!(cc.clamp_to_min_deposit(500, 1_000).unwrap(), 500);
+        assert_eq!(cc.clamp_to_min_deposit(950, 1_000).unwrap(), 900);
+        assert_eq!(cc.clamp_to_min_deposit(1_000, 1_000).unwrap(), 1_000);
+        assert_eq!(cc.clamp_to_min_deposit(60, 60).unwrap(), 60);
+
+        let expected: error::Error = CapitalCallError::DepositBelowMinimum.into();
+        assert_eq!(cc.clamp_to_min_deposit(50, 1_000).unwrap_err(), expected);
+        assert_eq!(cc.clamp_to_min_deposit(120, 150).unwrap_err(), expected);
+    }
+
+    #[test]
+    fn clamp_amount_to_capacity_and_fill_limit() {
+        let mut cc = capital_call(1_000, 0);
+        let expected: error::Error = CapitalCallError::ExceedsRemainingCapacity.into();
+        assert_eq!(cc.clamp_amount(1_200, 1_000).unwrap_err(), expected);
+
+        cc.allow_clamp = true;
+        assert_eq!(cc.clamp_amount(1_200, 1_000).unwrap(), 1_000);
+
+        cc.max_fill_bps_per_tx = 2_500;
+        assert_eq!(cc.clamp_amount(1_200, 1_000).unwrap(), 250);
+        assert_eq!(cc.clamp_amount(100, 1).unwrap(), 1);
+    }
+
+    #[test]
+    fn fill_time_kept_after_refill() {
+        let mut cc = capital_call(1_000, 500);
+        assert!(cc.mark_filled(Pubkey::default(), 10).is_none());
+
+        cc.allocated = 1_000;
+        assert_eq!(cc.mark_filled(Pubkey::default(), 20).unwrap().filled_at, 20);
+
+        // Withdraw and fill again
+        cc.allocated = 900;
+        assert!(cc.mark_filled(Pubkey::default(), 30).is_none());
+        cc.allocated = 1_000;
+        assert_eq!(cc.mark_filled(Pubkey::default(), 40).unwrap().filled_at, 20);
+        assert_eq!(cc.filled_at, 20);
+    }
+
+    #[test]
+    fn bonus_tiers() {
+        let mut cc = capital_call(2_000_000, 2_000_000);
+        cc.bonus_tiers[0] = BonusTier {
+            min_amount: 100_000,
+            bonus_bps: 100,
+        };
+        cc.bonus_tiers[1] = BonusTier {
+            min_amount: 1_000_000,
+            bonus_bps: 500,
+        };
+        assert_eq!(cc.bonus(99_999).unwrap(), 0);
+        assert_eq!(cc.bonus(500_000).unwrap(), 5_000);
+        assert_eq!(cc.bonus(1_000_000).unwrap(), 50_000);
+        assert_eq!(
+            cc.voucher_lp(1_000_000).unwrap(),
+            cc.to_lp_token(1_050_000).unwrap()
+        );
+    }
+
+    #[test]
+    fn rate_bounds() {
+        let mut cc = capital_call(2_000_000, 2_000_000);
+        assert_eq!(cc.lp_rate().unwrap(), 1_071_910_553);
+        assert!(cc.check_rate().is_ok());
+
+        cc.min_rate = 1_071_910_553;
+        cc.max_rate = 1_071_910_553;
+        assert!(cc.check_rate().is_ok());
+
+        let expected: error::Error = CapitalCallError::RateOutOfBounds.into();
+        cc.max_rate = 1_000_000_000;
+        assert_eq!(cc.check_rate().unwrap_err(), expected);
+        cc.max_rate = 0;
+        cc.min_rate = 1_100_000_000;
+        assert_eq!(cc.check_rate().unwrap_err(), expected);
+    }
+
+    #[test]
+    fn fill_progress() {
+        let cc = capital_call(2_000_000, 500_000);
+        assert_eq!(cc.remaining_capacity().unwrap(), 1_500_000);
+        assert_eq!(cc.fill_bps().unwrap(), 2_500);
+    }
+
+    #[test]
+    fn voucher_settled_once() {
+        let mut voucher = Voucher::default();
+        assert!(voucher.settle(SettlementKind::Claim).is_ok());
+        assert!(voucher.settle(SettlementKind::Claim).is_ok());
+
+        let expected: error::Error = CapitalCallError::AlreadySettledOtherwise.into();
+        assert_eq!(voucher.settle(SettlementKind::Refund).unwrap_err(), expected);
+    }
+
+    #[test]
+    fn finalizing_until_last_chunk() {
+        let mut cc = capital_call(2_000_000, 2_000_000);
+        cc.finalize_cursor = 1_000_000;
+        assert!(!cc.is_finalizing());
+
+        cc.finalize_chunk = 1_000_000;
+        assert!(cc.is_finalizing());
+        cc.finalize_cursor = 2_000_000;
+        assert!(!cc.is_finalizing());
+    }
+
+    #[test]
+    fn finalize_transfer_per_instruction() {
+        let mut cc = capital_call(2_000_000, 2_000_000);
+        cc.reserve_bps = 1_000;
+        assert_eq!(cc.finalize_reserve().unwrap(), 200_000);
+        assert_eq!(cc.finalize_transfer().unwrap(), 1_800_000);
+
+        cc.reserve_bps = 0;
+        cc.finalize_chunk = 500_000;
+        assert_eq!(cc.finalize_transfer().unwrap(), 500_000);
+        cc.finalize_chunk = 3_000_000;
+        assert_eq!(cc.finalize_transfer().unwrap(), 2_000_000);
+    }
+
+    #[test]
+    fn oracle_rate_of_fresh_confident_price() {
+        let mut cc = capital_call(2_000_000, 2_000_000);
+        cc.oracle_max_age = 60;
+        cc.oracle_max_conf_bps = 100;
+        let price = OraclePrice {
+            price: 2_500_000,
+            conf: 25_000,
+            expo: -6,
+            publish_time: 1_000,
+        };
+        assert_eq!(cc.oracle_rate(&price, 1_060).unwrap(), 2_500_000_000);
+
+        let expected: error::Error = CapitalCallError::OraclePriceStale.into();
+        assert_eq!(cc.oracle_rate(&price, 1_061).unwrap_err(), expected);
+
+        let uncertain = OraclePrice {
+            conf: 25_001,
+            ..price
+        };
+        let expected: error::Error = CapitalCallError::OraclePriceUncertain.into();
+        assert_eq!(cc.oracle_rate(&uncertain, 1_000).unwrap_err(), expected);
+
+        cc.use_oracle_conversion = true;
+        cc.oracle_rate = 2_500_000_000;
+        assert_eq!(cc.to_pool_units(1_000_000).unwrap(), 2_500_000);
+        assert_eq!(cc.from_pool_units(2_500_000).unwrap(), 1_000_000);
+        let unconverted = capital_call(2_000_000, 2_000_000);
+        assert_eq!(
+            cc.to_lp_token(800_000).unwrap(),
+            unconverted.to_lp_token(2_000_000).unwrap()
+        );
+    }
+
+    #[test]
+    fn distribute_lp_up_to_minted() {
+        let mut cc = CapitalCall {
+            lp_minted: 1_000,
+            ..Default::default()
+        };
+        cc.distribute_lp(600).unwrap();
+        cc.distribute_lp(400).unwrap();
+        assert_eq!(cc.lp_distributed, 1_000);
+
+        let expected: error::Error = CapitalCallError::OverClaim.into();
+        assert_eq!(cc.distribute_lp(1).unwrap_err(), expected);
+        assert_eq!(cc.lp_distributed, 1_000);
+    }
+
+    #[test]
+    fn lp_pool_shortfall() {
+        let cc = CapitalCall {
+            lp_minted: 1_000,
+            lp_distributed: 400,
+            ..Default::default()
+        };
+        assert!(cc.check_lp_pool_balance(600).is_ok());
+        assert!(cc.check_lp_pool_balance(601).is_ok());
+
+        let expected: error::Error = CapitalCallError::LpPoolShortfall.into();
+        assert_eq!(cc.check_lp_pool_balance(599).unwrap_err(), expected);
+    }
+
+    fn token_account(state: AccountState, delegated_amount: u64) -> TokenAccount {
+        let account = token::spl_token::state::Account {
+            state,
+            delegate: if delegated_amount > 0 {
+                COption::Some(Pubkey::new_unique())
+            } else {
+                COption::None
+            },
+            delegated_amount,
+            ..Default::default()
+        };
+        let mut data = vec![0; token::spl_token::state::Account::LEN];
+        token::spl_token::state::Account::pack(account, &mut data).unwrap();
+        TokenAccount::try_deserialize(&mut data.as_slice()).unwrap()
+    }
+
+    #[test]
+    fn deposit_source_state() {
+        let normal = token_account(AccountState::Initialized, 0);
+        assert!(check_deposit_source(&normal, false).is_ok());
+        assert!(check_deposit_source(&normal, true).is_ok());
+
+        let frozen: error::Error = CapitalCallError::SourceAccountFrozen.into();
+        let source = token_account(AccountState::Frozen, 0);
+        assert_eq!(check_deposit_source(&source, false).unwrap_err(), frozen);
+
+        let delegated: error::Error = CapitalCallError::SourceAccountDelegated.into();
+        let source = token_account(AccountState::Initialized, 100);
+        assert!(check_deposit_source(&source, false).is_ok());
+        assert_eq!(check_deposit_source(&source, true).unwrap_err(), delegated);
+    }
+
+    #[test]
+    fn fill_fee_by_time_to_fill() {
+        let mut cc = capital_call(2_000_000, 2_000_000);
+        assert_eq!(cc.fill_fee().unwrap(), 0);
+
+        cc.start_time = 1_000;
+        cc.fast_fill_threshold = 100;
+        cc.fast_fill_fee_bps = 50;
+        cc.slow_fill_fee_bps = 200;
+        let expected: error::Error = CapitalCallError::CapitalCallNotFullyFunded.into();
+        assert_eq!(cc.fill_fee().unwrap_err(), expected);
+
+        cc.filled_at = 1_100;
+        assert_eq!(cc.fill_fee().unwrap(), 50);
+        cc.filled_at = 1_101;
+        assert_eq!(cc.fill_fee().unwrap(), 200);
+
+        let lp = cc.voucher_lp(1_000_000).unwrap();
+        cc.fill_fee_bps = 200;
+        assert_eq!(cc.voucher_lp(1_000_000).unwrap(), lp * 9_800 / 10_000);
+    }
+
+    #[test]
+    fn vesting_schedule() {
+        let cc = CapitalCall {
+            vest_start: 1_000,
+            vest_cliff: 100,
+            vest_duration: 400,
+            ..Default::default()
+        };
+        let voucher = Voucher::default();
+        assert_eq!(voucher.vested_lp(&cc, 1_000, 1_099).unwrap(), 0);
+        assert_eq!(voucher.vested_lp(&cc, 1_000, 1_100).unwrap(), 250);
+        assert_eq!(voucher.vested_lp(&cc, 1_000, 1_400).unwrap(), 1_000);
+
+        let strategic = Voucher {
+            vest_override: true,
+            vest_override_start: 1_000,
+            vest_override_duration: 200,
+            ..Default::default()
+        };
+        assert_eq!(strategic.vested_lp(&cc, 1_000, 1_050).unwrap(), 250);
+        assert_eq!(strategic.vested_lp(&cc, 1_000, 1_200).unwrap(), 1_000);
+
+        let unvested = CapitalCall::default();
+        assert_eq!(voucher.vested_lp(&unvested, 1_000, 0).unwrap(), 1_000);
+    }
+
+    #[test]
+    fn unlock_points_schedule() {
+        let mut cc = CapitalCall {
+            vest_start: 1_000,
+            vest_duration: 400,
+            ..Default::default()
+        };
+        cc.unlock_points[0] = UnlockPoint {
+            timestamp: 1_100,
+            bps: 2_500,
+        };
+        cc.unlock_points[1] = UnlockPoint {
+            timestamp: 1_300,
+            bps: 7_500,
+        };
+        let voucher = Voucher::default();
+        assert_eq!(voucher.vested_lp(&cc, 1_000, 1_099).unwrap(), 0);
+        assert_eq!(voucher.vested_lp(&cc, 1_000, 1_100).unwrap(), 250);
+        assert_eq!(voucher.vested_lp(&cc, 1_000, 1_299).unwrap(), 250);
+        assert_eq!(voucher.vested_lp(&cc, 1_000, 1_300).unwrap(), 1_000);
+
+        // override schedule of voucher takes precedence
+        let strategic = Voucher {
+            vest_override: true,
+            vest_override_start: 1_000,
+            vest_override_duration: 200,
+            ..Default::default()
+        };
+        assert_eq!(strategic.vested_lp(&cc, 1_000, 1_050).unwrap(), 250);
+    }
+
+    #[test]
+    fn twap_release_limits_vesting() {
+        let cc = CapitalCall {
+            finalized_at: 1_000,
+            twap_release_duration: 400,
+            ..Default::default()
+        };
+        let voucher = Voucher::default();
+        assert_eq!(voucher.vested_lp(&cc, 1_000, 1_000).unwrap(), 0);
+        assert_eq!(voucher.vested_lp(&cc, 1_000, 1_100).unwrap(), 250);
+        assert_eq!(voucher.vested_lp(&cc, 1_000, 1_400).unwrap(), 1_000);
+
+        let vested = CapitalCall {
+            vest_start: 1_000,
+            vest_duration: 200,
+            ..cc
+        };
+        assert_eq!(voucher.vested_lp(&vested, 1_000, 1_100).unwrap(), 250);
+        assert_eq!(voucher.vested_lp(&vested, 1_000, 1_300).unwrap(), 750);
+    }
+
+    #[test]
+    fn claim_horizon_covers_vesting() {
+        let cc = CapitalCall {
+            end_time: 1_000,
+            claim_delay: 100,
+            twap_release_duration: 50,
+            ..Default::default()
+        };
+        assert_eq!(cc.claim_horizon().unwrap(), 1_150);
+
+        let vested = CapitalCall {
+            vest_start: 1_000,
+            vest_duration: 400,
+            ..cc
+        };
+        assert_eq!(vested.claim_horizon().unwrap(), 1_400);
+
+        let mut stepped = vested;
+        stepped.unlock_points[0] = UnlockPoint {
+            timestamp: 2_000,
+            bps: 10_000,
+        };
+        assert_eq!(stepped.claim_horizon().unwrap(), 2_000);
+    }
+}
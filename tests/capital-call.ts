@@ -3,7 +3,13 @@ import * as anchor from "@project-serum/anchor";
 import {BN, Program, web3} from "@project-serum/anchor";
 import {Keypair, PublicKey} from "@solana/web3.js";
 import {CapitalCall} from "../target/types/capital_call";
-import {AuthorityType, createAssociatedTokenAccountInstruction, createSetAuthorityInstruction} from "@solana/spl-token";
+import {CallbackMock} from "../target/types/callback_mock";
+import {
+  AuthorityType,
+  createAssociatedTokenAccountInstruction,
+  createSetAuthorityInstruction,
+  NATIVE_MINT
+} from "@solana/spl-token";
 
 import * as chai from 'chai';
 import {expect, use} from 'chai';
@@ -19,6 +25,7 @@ describe("capital-call", () => {
 
   const provider = anchor.getProvider();
   const program = anchor.workspace.CapitalCall as Program<CapitalCall>;
+  const callbackMock = anchor.workspace.CallbackMock as Program<CallbackMock>;
   const tokenProgram = anchor.Spl.token();
 
   const config = Keypair.generate();
@@ -36,7 +43,11 @@ describe("capital-call", () => {
     const TSCreditOutstanding = 7348028;
   const TSLpSupply = 9127492;
 
+  const NO_IDEMPOTENCY_KEY = Array(16).fill(0);
+  const NO_MEMO = Array(32).fill(0);
+
   let capitalCall1;
+  let feeLpAccount: PublicKey;
 
   async function getATA(owner: PublicKey, mint: PublicKey) {
     const [ata, _nonce] = await PublicKey.findProgramAddress(
@@ -51,6 +62,64 @@ describe("capital-call", () => {
     return account.amount.toNumber();
   }
 
+  async function currentBlockTime() {
+    return await provider.connection.getBlockTime(await provider.connection.getSlot());
+  }
+
+  async function findCapitalCall(startTime: number, capacity: number, idempotencyKey: number[]) {
+    const [capitalCall] = await PublicKey.findProgramAddress(
+      [
+        anchor.utils.bytes.utf8.encode("capital_call"),
+        config.publicKey.toBuffer(),
+        new BN(startTime).toArrayLike(Buffer, "le", 8),
+        new BN(capacity).toArrayLike(Buffer, "le", 8),
+        Buffer.from(idempotencyKey),
+      ],
+      program.programId
+    );
+    return capitalCall;
+  }
+
+  async function createCapitalCall(
+    startDelay: number,
+    duration: number,
+    capacity: number,
+    idempotencyKey: number[] = NO_IDEMPOTENCY_KEY,
+    manager: PublicKey = PublicKey.default,
+    refundLockup: number = 0,
+  ) {
+    const blockTime = await currentBlockTime();
+    const builder = program.methods.createCapitalCall(
+      new BN(blockTime + startDelay), // start_time
+      new BN(duration), // duration
+      new BN(capacity), // capacity
+      new BN(TSCreditOutstanding), // credit outstanding
+      idempotencyKey, // idempotency key
+      manager, // manager
+      new BN(refundLockup), // refund lockup
+    ).accounts({
+      config: config.publicKey,
+      capitalCall: await findCapitalCall(blockTime + startDelay, capacity, idempotencyKey),
+      mint: mint.publicKey,
+      lpMint: lpMint.publicKey,
+      authority: authority.publicKey,
+      payer: provider.wallet.publicKey,
+    }).signers([authority]);
+
+    const keys = await builder.pubkeys();
+    await builder.rpc();
+    return keys;
+  }
+
+  async function mintTokens(to: PublicKey, amount: number) {
+    await tokenProgram.methods.mintTo(new BN(amount))
+      .accounts({
+        mint: mint.publicKey,
+        to,
+        authority: provider.wallet.publicKey,
+      }).rpc();
+  }
+
   before(async () => {
     // init mint for tokens
     await tokenProgram.methods.initializeMint(6, provider.wallet.publicKey, null)
@@ -127,6 +196,8 @@ describe("capital-call", () => {
     await provider.connection.requestAirdrop(user1.publicKey, web3.LAMPORTS_PER_SOL);
     await provider.connection.requestAirdrop(user2.publicKey, web3.LAMPORTS_PER_SOL);
 
+    feeLpAccount = await getATA(provider.wallet.publicKey, lpMint.publicKey);
+
     // Init ata for tests users
     await provider.sendAndConfirm(
       new web3.Transaction()
@@ -179,6 +250,7 @@ describe("capital-call", () => {
     expect(configAcc.authority).to.be.deep.equal(authority.publicKey);
     expect(configAcc.lpMint).to.be.deep.equal(lpMint.publicKey);
     expect(configAcc.liquidityPool).to.be.deep.equal(liquidityPool.publicKey);
+    expect(configAcc.lpDecimals).to.be.equal(6);
 
     const [lpMintAuthority, _nonce] = await PublicKey.findProgramAddress(
       [anchor.utils.bytes.utf8.encode("lp_mint_authority"), config.publicKey.toBuffer()],
@@ -195,9 +267,13 @@ describe("capital-call", () => {
       new BN(blockTime + 1), // start_time
       new BN(duration), // duration
       new BN(2_000_000), // capacity - 2.0 Token
-      new BN(TSCreditOutstanding) // credit outstanding
+      new BN(TSCreditOutstanding), // credit outstanding
+      NO_IDEMPOTENCY_KEY, // idempotency key
+      PublicKey.default, // manager
+      new BN(0), // refund lockup
     ).accounts({
       config: config.publicKey,
+      capitalCall: await findCapitalCall(blockTime + 1, 2_000_000, NO_IDEMPOTENCY_KEY),
       mint: mint.publicKey,
       lpMint: lpMint.publicKey,
       authority: authority.publicKey,
@@ -208,9 +284,13 @@ describe("capital-call", () => {
       new BN(blockTime + 1), // start_time
       new BN(duration), // duration
       new BN(2_000_000), // capacity - 2.0 Token
-      new BN(TSCreditOutstanding) // credit outstanding
+      new BN(TSCreditOutstanding), // credit outstanding
+      NO_IDEMPOTENCY_KEY, // idempotency key
+      PublicKey.default, // manager
+      new BN(0), // refund lockup
     ).accounts({
       config: config.publicKey,
+      capitalCall: await findCapitalCall(blockTime + 1, 2_000_000, NO_IDEMPOTENCY_KEY),
       mint: mint.publicKey,
       lpMint: lpMint.publicKey,
       authority: authority.publicKey,
@@ -253,8 +333,9 @@ describe("capital-call", () => {
         authority: provider.wallet.publicKey,
       }).rpc();
 
-    await program.methods.deposit(new BN(1_000_000))
+    await program.methods.deposit(new BN(1_000_000), null, NO_MEMO)
       .accounts({
+        config: config.publicKey,
         capitalCall: capitalCall1,
         authority: user1.publicKey,
         source: ata1,
@@ -274,8 +355,9 @@ describe("capital-call", () => {
     expect(voucherAcc.authority).to.be.deep.equal(user1.publicKey);
     expect(voucherAcc.amount.toNumber()).to.be.deep.equal(1_000_000);
 
-    await program.methods.deposit(new BN(2_000_000))
+    await program.methods.deposit(new BN(2_000_000), null, NO_MEMO)
       .accounts({
+        config: config.publicKey,
         capitalCall: capitalCall1,
         authority: user2.publicKey,
         source: ata2,
@@ -325,7 +407,9 @@ describe("capital-call", () => {
   it("Should claim lp tokens", async () => {
     const ata1 = await getATA(user1.publicKey, lpMint.publicKey);
     await program.methods.claim().accounts({
+        config: config.publicKey,
         capitalCall: capitalCall1,
+        feeLpAccount,
         authority: user1.publicKey,
         destination: ata1,
       }).signers([user1])
@@ -339,7 +423,9 @@ describe("capital-call", () => {
 
     const ata2 = await getATA(user2.publicKey, lpMint.publicKey);
     await program.methods.claim().accounts({
+      config: config.publicKey,
       capitalCall: capitalCall1,
+      feeLpAccount,
       authority: user2.publicKey,
       destination: ata2,
     }).signers([user2])
@@ -353,7 +439,7 @@ describe("capital-call", () => {
   it("Should close completed capital call", async () => {
     const ata = await getATA(provider.wallet.publicKey, mint.publicKey);
 
-    const keys = await program.methods.close()
+    const keys = await program.methods.close(new BN(0))
       .accounts({
         config: config.publicKey,
         capitalCall: capitalCall1,
@@ -365,7 +451,7 @@ describe("capital-call", () => {
 
     expect(await tokenBalance(keys.lpTokenPool)).to.be.equal(1);
 
-    await program.methods.close()
+    await program.methods.close(new BN(0))
       .accounts({
         config: config.publicKey,
         capitalCall: capitalCall1,
@@ -390,9 +476,13 @@ describe("capital-call", () => {
       new BN(blockTime + 1), // start_time
       new BN(duration), // duration
       new BN(2_000_000), // capacity - 2.0 Token
-      new BN(TSCreditOutstanding) // credit outstanding
+      new BN(TSCreditOutstanding), // credit outstanding
+      NO_IDEMPOTENCY_KEY, // idempotency key
+      PublicKey.default, // manager
+      new BN(0), // refund lockup
     ).accounts({
       config: config.publicKey,
+      capitalCall: await findCapitalCall(blockTime + 1, 2_000_000, NO_IDEMPOTENCY_KEY),
       mint: mint.publicKey,
       lpMint: lpMint.publicKey,
       authority: authority.publicKey,
@@ -403,9 +493,13 @@ describe("capital-call", () => {
       new BN(blockTime + 1), // start_time
       new BN(duration), // duration
       new BN(2_000_000), // capacity - 2.0 Token
-      new BN(TSCreditOutstanding) // credit outstanding
+      new BN(TSCreditOutstanding), // credit outstanding
+      NO_IDEMPOTENCY_KEY, // idempotency key
+      PublicKey.default, // manager
+      new BN(0), // refund lockup
     ).accounts({
       config: config.publicKey,
+      capitalCall: await findCapitalCall(blockTime + 1, 2_000_000, NO_IDEMPOTENCY_KEY),
       mint: mint.publicKey,
       lpMint: lpMint.publicKey,
       authority: authority.publicKey,
@@ -418,8 +512,9 @@ describe("capital-call", () => {
     await new Promise(resolve => setTimeout(resolve, 1500));
 
     const ata2 = await getATA(user2.publicKey, mint.publicKey);
-    await program.methods.deposit(new BN(1_000_000))
+    await program.methods.deposit(new BN(1_000_000), null, NO_MEMO)
       .accounts({
+        config: config.publicKey,
         capitalCall: capitalCall,
         authority: user2.publicKey,
         source: ata2,
@@ -433,6 +528,7 @@ describe("capital-call", () => {
 
     await program.methods.refund()
       .accounts({
+        config: config.publicKey,
         capitalCall,
         authority: user2.publicKey,
         destination: ata2,
@@ -441,6 +537,4866 @@ describe("capital-call", () => {
     expect(await tokenBalance(ata2)).to.be.equal(1_000_000);
   });
 
+  it("Should shorten capital call duration", async () => {
+    const keys = await createCapitalCall(1, 100, 3_000_000);
+    const ccAcc = await program.account.capitalCall.fetch(keys.capitalCall);
+    const newEndTime = ccAcc.startTime.toNumber() + 5;
+
+    await program.methods.shortenDuration(new BN(newEndTime))
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+
+    const updated = await program.account.capitalCall.fetch(keys.capitalCall);
+    expect(updated.endTime.toNumber()).to.be.equal(newEndTime);
+  });
+
+  it("Should NOT shorten capital call duration into the past", async () => {
+    const keys = await createCapitalCall(1, 100, 3_000_001);
+    const blockTime = await currentBlockTime();
+
+    await expect(program.methods.shortenDuration(new BN(blockTime - 10))
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc()
+    ).to.be.rejectedWith(/EndTimeMustBeInFuture/);
+  });
+
+  it("Should aggregate config stats across capital calls", async () => {
+    const before = await program.account.config.fetch(config.publicKey);
+
+    const keys1 = await createCapitalCall(1, 100, 4_000_000);
+    const keys2 = await createCapitalCall(1, 100, 4_000_001);
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    const ata2 = await getATA(user2.publicKey, mint.publicKey);
+    await mintTokens(ata1, 500_000);
+    await mintTokens(ata2, 700_000);
+
+    await program.methods.deposit(new BN(500_000), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys1.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+
+    await program.methods.deposit(new BN(700_000), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys2.capitalCall,
+        authority: user2.publicKey,
+        source: ata2,
+      }).signers([user2]).rpc();
+
+    const configAcc = await program.account.config.fetch(config.publicKey);
+    expect(configAcc.totalAllocated.toNumber()).to.be.equal(before.totalAllocated.toNumber() + 1_200_000);
+    expect(configAcc.totalCapacity.toNumber()).to.be.equal(before.totalCapacity.toNumber() + 8_000_001);
+
+    const stats = await program.methods.configStats()
+      .accounts({config: config.publicKey})
+      .simulate();
+    const event = stats.events.find(e => e.name === "ConfigStatsEvent");
+    expect(event.data.totalAllocated.toNumber()).to.be.equal(configAcc.totalAllocated.toNumber());
+    expect(event.data.totalCapacity.toNumber()).to.be.equal(configAcc.totalCapacity.toNumber());
+  });
+
+  it("Should NOT create duplicate capital call with the same idempotency key", async () => {
+    const idempotencyKey = Array.from(Keypair.generate().publicKey.toBytes().slice(0, 16));
+    const blockTime = await currentBlockTime();
+    const startTime = blockTime + 5;
+    const capitalCall = await findCapitalCall(startTime, 5_000_000, idempotencyKey);
+
+    // duration isn't part of seeds, vary it to avoid identical transaction
+    const create = (duration: number) => program.methods.createCapitalCall(
+      new BN(startTime), // start_time
+      new BN(duration), // duration
+      new BN(5_000_000), // capacity
+      new BN(TSCreditOutstanding), // credit outstanding
+      idempotencyKey, // idempotency key
+      PublicKey.default, // manager
+      new BN(0), // refund lockup
+    ).accounts({
+      config: config.publicKey,
+      capitalCall,
+      mint: mint.publicKey,
+      lpMint: lpMint.publicKey,
+      authority: authority.publicKey,
+      payer: provider.wallet.publicKey,
+    }).signers([authority]).rpc();
+
+    await create(100);
+    const ccAcc = await program.account.capitalCall.fetch(capitalCall);
+    expect(ccAcc.idempotencyKey).to.be.deep.equal(idempotencyKey);
+
+    await expect(create(101)).to.be.rejectedWith(/DuplicateCapitalCall/);
+  });
+
+  it("Should detect tokens transferred directly to vault", async () => {
+    const keys = await createCapitalCall(1, 100, 6_000_000);
+    await program.methods.updateCapitalCall({strictVaultBalance: true})
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    const ata2 = await getATA(user2.publicKey, mint.publicKey);
+    await mintTokens(ata1, 100_000);
+    await mintTokens(ata2, 100_000);
+
+    await program.methods.deposit(new BN(100_000), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+
+    await program.methods.verifyVault()
+      .accounts({capitalCall: keys.capitalCall, vault: keys.vault})
+      .rpc();
+
+    // out-of-band transfer to vault
+    await mintTokens(keys.vault, 10);
+
+    await expect(program.methods.verifyVault()
+      .accounts({capitalCall: keys.capitalCall, vault: keys.vault})
+      .rpc()
+    ).to.be.rejectedWith(/VaultBalanceMismatch/);
+
+    await expect(program.methods.deposit(new BN(100_000), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user2.publicKey,
+        source: ata2,
+      }).signers([user2]).rpc()
+    ).to.be.rejectedWith(/VaultBalanceMismatch/);
+  });
+
+  it("Should accept deposits after tokens transferred directly to vault of non-strict capital call", async () => {
+    const keys = await createCapitalCall(1, 100, 1_000_107);
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 100_000);
+
+    // out-of-band transfer to vault
+    await mintTokens(keys.vault, 10);
+
+    await program.methods.deposit(new BN(100_000), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+
+    expect(await tokenBalance(keys.vault)).to.be.equal(100_010);
+    await expect(program.methods.verifyVault()
+      .accounts({capitalCall: keys.capitalCall, vault: keys.vault})
+      .rpc()
+    ).to.be.rejectedWith(/VaultBalanceMismatch/);
+  });
+
+  it("Should invoke finalize callback program", async () => {
+    await program.methods.updateConfig({finalizeCallbackProgram: callbackMock.programId})
+      .accounts({config: config.publicKey, authority: authority.publicKey})
+      .signers([authority]).rpc();
+
+    const keys = await createCapitalCall(1, 100, 1_000_001);
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 1_000_001);
+    await program.methods.deposit(new BN(1_000_001), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+
+    const mintLpTokens = program.methods.mintLpTokens()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        liquidityPool: liquidityPool.publicKey,
+        lpMint: lpMint.publicKey,
+      });
+
+    await expect(mintLpTokens.rpc()).to.be.rejectedWith(/InvalidFinalizeCallbackProgram/);
+
+    const tx = await mintLpTokens
+      .remainingAccounts([{pubkey: callbackMock.programId, isWritable: false, isSigner: false}])
+      .rpc();
+
+    const txInfo = await provider.connection.getTransaction(tx, {commitment: "confirmed"});
+    expect(txInfo.meta.logMessages).to.include(`Program ${callbackMock.programId} invoke [2]`);
+    expect(txInfo.meta.logMessages).to.include(`Program ${callbackMock.programId} success`);
+
+    await program.methods.updateConfig({finalizeCallbackProgram: PublicKey.default})
+      .accounts({config: config.publicKey, authority: authority.publicKey})
+      .signers([authority]).rpc();
+  });
+
+  it("Should NOT deposit before start slot", async () => {
+    const keys = await createCapitalCall(1, 100, 6_000_001);
+    const startSlot = (await provider.connection.getSlot()) + 10;
+
+    await program.methods.updateCapitalCall({startSlot: new BN(startSlot)})
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+
+    await new Promise(resolve => setTimeout(resolve, 1500));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 100_000);
+    const deposit = program.methods.deposit(new BN(100_000), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]);
+
+    await expect(deposit.rpc()).to.be.rejectedWith(/CapitalCallNotStarted/);
+
+    while (await provider.connection.getSlot() < startSlot) {
+      await new Promise(resolve => setTimeout(resolve, 400));
+    }
+
+    await deposit.rpc();
+    const ccAcc = await program.account.capitalCall.fetch(keys.capitalCall);
+    expect(ccAcc.allocated.toNumber()).to.be.equal(100_000);
+  });
+
+  it("Should NOT deposit twice from the same depositor", async () => {
+    const keys = await createCapitalCall(1, 100, 6_000_002);
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 200_000);
+    const deposit = (amount: number) => program.methods.deposit(new BN(amount), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+
+    await deposit(100_000);
+    await expect(deposit(50_000)).to.be.rejectedWith(/VoucherAlreadyExists/);
+  });
+
+  it("Should finalize capital call split between liquidity pools", async () => {
+    const secondPool = Keypair.generate();
+    await tokenProgram.methods.initializeAccount()
+      .accounts({
+        account: secondPool.publicKey,
+        mint: mint.publicKey,
+        authority: provider.wallet.publicKey,
+        rent: web3.SYSVAR_RENT_PUBKEY,
+      })
+      .signers([secondPool])
+      .preInstructions([await tokenProgram.account.token.createInstruction(secondPool)])
+      .rpc();
+    await mintTokens(secondPool.publicKey, 500_000);
+
+    const keys = await createCapitalCall(1, 100, 1_000_002);
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 1_000_002);
+    await program.methods.deposit(new BN(1_000_002), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+
+    const pool1Before = await tokenBalance(liquidityPool.publicKey);
+    const pool2Before = await tokenBalance(secondPool.publicKey);
+
+    const finalizeSplit = (bps: number[]) => program.methods.finalizeSplit(bps)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        lpMint: lpMint.publicKey,
+        authority: authority.publicKey,
+      })
+      .remainingAccounts([
+        {pubkey: liquidityPool.publicKey, isWritable: true, isSigner: false},
+        {pubkey: secondPool.publicKey, isWritable: true, isSigner: false},
+      ])
+      .signers([authority]).rpc();
+
+    await expect(finalizeSplit([6000, 3000])).to.be.rejectedWith(/InvalidSplit/);
+    await finalizeSplit([6000, 4000]);
+
+    expect(await tokenBalance(liquidityPool.publicKey)).to.be.equal(pool1Before + 600_001);
+    expect(await tokenBalance(secondPool.publicKey)).to.be.equal(pool2Before + 400_001);
+
+    const ccAcc = await program.account.capitalCall.fetch(keys.capitalCall);
+    expect(ccAcc.isLpMinted).to.be.true;
+    expect(ccAcc.splitFinalized).to.be.true;
+    expect(ccAcc.tokenLiquidity.toNumber()).to.be.equal(pool1Before + pool2Before);
+
+    // main pool never received the whole capital
+    await expect(program.methods.unwind()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+        liquidityPool: liquidityPool.publicKey,
+        poolAuthority: provider.wallet.publicKey,
+        lpMint: lpMint.publicKey,
+      }).signers([authority]).rpc()).to.be.rejectedWith(/UnwindNotAllowed/);
+  });
+
+  it("Should NOT mint LP tokens with LP mint of different decimals", async () => {
+    const otherLpMint = Keypair.generate();
+    await tokenProgram.methods.initializeMint(9, provider.wallet.publicKey, null)
+      .accounts({
+        mint: otherLpMint.publicKey,
+        rent: web3.SYSVAR_RENT_PUBKEY,
+      })
+      .preInstructions([await tokenProgram.account.mint.createInstruction(otherLpMint)])
+      .signers([otherLpMint]).rpc();
+
+    const keys = await createCapitalCall(1, 100, 6_000_003);
+
+    await expect(program.methods.mintLpTokens()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        liquidityPool: liquidityPool.publicKey,
+        lpMint: otherLpMint.publicKey,
+      }).rpc()
+    ).to.be.rejected;
+  });
+
+  it("Should NOT deposit again within refund cooldown after withdraw", async () => {
+    const keys = await createCapitalCall(1, 100, 6_000_004);
+    await program.methods.updateCapitalCall({refundCooldown: new BN(4)})
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 300_000);
+    const deposit = (amount: number) => program.methods.deposit(new BN(amount), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+
+    await deposit(100_000);
+    await program.methods.withdraw()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        destination: ata1,
+      }).signers([user1]).rpc();
+
+    let ccAcc = await program.account.capitalCall.fetch(keys.capitalCall);
+    expect(ccAcc.allocated.toNumber()).to.be.equal(0);
+
+    await expect(deposit(90_000)).to.be.rejectedWith(/RefundCooldown/);
+
+    await new Promise(resolve => setTimeout(resolve, 5000));
+    await deposit(80_000);
+
+    ccAcc = await program.account.capitalCall.fetch(keys.capitalCall);
+    expect(ccAcc.allocated.toNumber()).to.be.equal(80_000);
+  });
+
+  it("Should block user actions while capital call is frozen", async () => {
+    const keys = await createCapitalCall(1, 100, 1_000_003);
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const setFrozen = (frozen: boolean) => program.methods.setCallFrozen(frozen)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 1_000_003);
+    const deposit = (amount: number) => program.methods.deposit(new BN(amount), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+    const withdraw = () => program.methods.withdraw()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        destination: ata1,
+      }).signers([user1]).rpc();
+    const mintLpTokens = () => program.methods.mintLpTokens()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        liquidityPool: liquidityPool.publicKey,
+        lpMint: lpMint.publicKey,
+      }).rpc();
+
+    await setFrozen(true);
+    await expect(deposit(1_000_003)).to.be.rejectedWith(/CapitalCallFrozen/);
+
+    await setFrozen(false);
+    await deposit(1_000_003);
+
+    await setFrozen(true);
+    await expect(withdraw()).to.be.rejectedWith(/CapitalCallFrozen/);
+    await expect(mintLpTokens()).to.be.rejectedWith(/CapitalCallFrozen/);
+
+    await setFrozen(false);
+    await mintLpTokens();
+    const ccAcc = await program.account.capitalCall.fetch(keys.capitalCall);
+    expect(ccAcc.isLpMinted).to.be.true;
+  });
+
+  it("Should split claimed LP tokens with referrer", async () => {
+    const keys = await createCapitalCall(1, 100, 1_000_004);
+    await program.methods.updateCapitalCall({referralBps: 1_000})
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+
+    const [referral] = await PublicKey.findProgramAddress(
+      [anchor.utils.bytes.utf8.encode("referral"), keys.capitalCall.toBuffer(), user2.publicKey.toBuffer()],
+      program.programId,
+    );
+    await program.methods.registerReferral()
+      .accounts({config: config.publicKey, capitalCall: keys.capitalCall, referrer: user2.publicKey})
+      .signers([user2]).rpc();
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 1_000_004);
+    await program.methods.deposit(new BN(1_000_004), user2.publicKey, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      })
+      .remainingAccounts([{pubkey: referral, isWritable: false, isSigner: false}])
+      .signers([user1]).rpc();
+
+    await program.methods.mintLpTokens()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        liquidityPool: liquidityPool.publicKey,
+        lpMint: lpMint.publicKey,
+      }).rpc();
+
+    const ccAcc = await program.account.capitalCall.fetch(keys.capitalCall);
+    const lpAmount = new BN(1_000_004).mul(ccAcc.tokenLiquidity.add(ccAcc.creditOutstanding)).div(ccAcc.lpSupply).toNumber();
+    const fee = Math.floor(lpAmount * 1_000 / 10_000);
+
+    const lpAta1 = await getATA(user1.publicKey, lpMint.publicKey);
+    const lpAta2 = await getATA(user2.publicKey, lpMint.publicKey);
+    const balance1 = await tokenBalance(lpAta1);
+    const balance2 = await tokenBalance(lpAta2);
+
+    await program.methods.claim()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        feeLpAccount,
+        authority: user1.publicKey,
+        destination: lpAta1,
+      })
+      .remainingAccounts([{pubkey: referral, isWritable: true, isSigner: false}])
+      .signers([user1]).rpc();
+    expect(await tokenBalance(lpAta1)).to.be.equal(balance1 + lpAmount - fee);
+
+    await program.methods.claimReferral()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        referrer: user2.publicKey,
+        destination: lpAta2,
+      }).signers([user2]).rpc();
+    expect(await tokenBalance(lpAta2)).to.be.equal(balance2 + fee);
+  });
+
+  it("Should take snapshot of depositors", async () => {
+    const keys = await createCapitalCall(1, 100, 1_000_005);
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    const ata2 = await getATA(user2.publicKey, mint.publicKey);
+    await mintTokens(ata1, 400_000);
+    await mintTokens(ata2, 600_005);
+
+    for (const [user, ata, amount] of [[user1, ata1, 400_000], [user2, ata2, 600_005]] as [Keypair, PublicKey, number][]) {
+      await program.methods.deposit(new BN(amount), null, NO_MEMO)
+        .accounts({
+          config: config.publicKey,
+          capitalCall: keys.capitalCall,
+          authority: user.publicKey,
+          source: ata,
+        }).signers([user]).rpc();
+    }
+
+    const [voucher1] = await PublicKey.findProgramAddress(
+      [anchor.utils.bytes.utf8.encode("voucher"), keys.capitalCall.toBuffer(), user1.publicKey.toBuffer()],
+      program.programId,
+    );
+    const [voucher2] = await PublicKey.findProgramAddress(
+      [anchor.utils.bytes.utf8.encode("voucher"), keys.capitalCall.toBuffer(), user2.publicKey.toBuffer()],
+      program.programId,
+    );
+
+    const snapshotKeys = await program.methods.snapshot()
+      .accounts({config: config.publicKey, capitalCall: keys.capitalCall, authority: authority.publicKey})
+      .pubkeys();
+    await program.methods.snapshot()
+      .accounts({config: config.publicKey, capitalCall: keys.capitalCall, authority: authority.publicKey})
+      .signers([authority]).rpc();
+
+    // vouchers are recorded in batches against the snapshot
+    const snapshotVouchers = (vouchers: PublicKey[]) => program.methods.snapshotVouchers()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        snapshot: snapshotKeys.snapshot,
+        authority: authority.publicKey,
+      })
+      .remainingAccounts(vouchers.map(pubkey => ({pubkey, isWritable: true, isSigner: false})))
+      .signers([authority]).rpc();
+    await snapshotVouchers([voucher1]);
+    await snapshotVouchers([voucher2]);
+    await expect(snapshotVouchers([voucher1])).to.be.rejectedWith(/VoucherAlreadySnapshotted/);
+
+    expect((await program.account.voucher.fetch(voucher1)).snapshotAmount.toNumber()).to.be.equal(400_000);
+    expect((await program.account.voucher.fetch(voucher2)).snapshotAmount.toNumber()).to.be.equal(600_005);
+
+    await program.methods.mintLpTokens()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        liquidityPool: liquidityPool.publicKey,
+        lpMint: lpMint.publicKey,
+      }).rpc();
+    await program.methods.claim()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        feeLpAccount,
+        authority: user1.publicKey,
+        destination: await getATA(user1.publicKey, lpMint.publicKey),
+      }).signers([user1]).rpc();
+
+    const snapshotAcc = await program.account.snapshot.fetch(snapshotKeys.snapshot);
+    expect(snapshotAcc.capitalCall).to.be.deep.equal(keys.capitalCall);
+    expect(snapshotAcc.totalAllocated.toNumber()).to.be.equal(1_000_005);
+    expect(snapshotAcc.vouchers).to.be.equal(2);
+  });
+
+  it("Should close capital call retaining vault balance", async () => {
+    const keys = await createCapitalCall(1, 100, 6_000_005);
+    await mintTokens(keys.vault, 1_000);
+
+    const ata = await getATA(provider.wallet.publicKey, mint.publicKey);
+    const balance = await tokenBalance(ata);
+    const retainedVaults = (await program.account.config.fetch(config.publicKey)).retainedVaults;
+
+    await program.methods.close(new BN(400))
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+        receiver: provider.wallet.publicKey,
+        lpMint: lpMint.publicKey,
+        destination: ata,
+      }).signers([authority])
+      .rpc();
+
+    expect(await tokenBalance(ata)).to.be.equal(balance + 600);
+    expect(await tokenBalance(keys.vault)).to.be.equal(400);
+    expect((await program.account.capitalCall.fetch(keys.capitalCall)).retained).to.be.true;
+    expect((await program.account.config.fetch(config.publicKey)).retainedVaults).to.be.equal(retainedVaults + 1);
+
+    await expect(program.methods.close(new BN(0))
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+        receiver: provider.wallet.publicKey,
+        lpMint: lpMint.publicKey,
+        destination: ata,
+      }).signers([authority])
+      .rpc()
+    ).to.be.rejected;
+
+    await program.methods.sweepRetained()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+        receiver: provider.wallet.publicKey,
+        destination: ata,
+      }).signers([authority])
+      .rpc();
+
+    expect(await tokenBalance(ata)).to.be.equal(balance + 1_000);
+    expect(await provider.connection.getAccountInfo(keys.vault)).to.be.null;
+    expect(await program.account.capitalCall.fetchNullable(keys.capitalCall)).to.be.null;
+    expect((await program.account.config.fetch(config.publicKey)).retainedVaults).to.be.equal(retainedVaults);
+  });
+
+  it("Should emit funding progress at step boundaries only", async () => {
+    const keys = await createCapitalCall(1, 100, 1_000_000 + 6);
+    await program.methods.updateCapitalCall({progressEventStepBps: 2_500})
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    const ata2 = await getATA(user2.publicKey, mint.publicKey);
+    await mintTokens(ata1, 100_000);
+    await mintTokens(ata2, 200_000);
+
+    const deposit = (user: Keypair, source: PublicKey, amount: number) => program.methods.deposit(new BN(amount), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user.publicKey,
+        source,
+      }).signers([user]);
+
+    // 10% - below first step
+    let simulation = await deposit(user1, ata1, 100_000).simulate();
+    expect(simulation.events.map(e => e.name)).to.include("DepositEvent");
+    expect(simulation.events.map(e => e.name)).to.not.include("FundingProgressEvent");
+    await deposit(user1, ata1, 100_000).rpc();
+
+    // 30% - crosses 25% step
+    simulation = await deposit(user2, ata2, 200_000).simulate();
+    const progress = simulation.events.find(e => e.name === "FundingProgressEvent");
+    expect(progress.data.fillBps.toNumber()).to.be.equal(2_999);
+  });
+
+  it("Should recover LP tokens transferred directly to lp token pool", async () => {
+    const keys = await createCapitalCall(1, 100, 1_000_007);
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 1_000_007);
+    await program.methods.deposit(new BN(1_000_007), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+
+    await program.methods.mintLpTokens()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        liquidityPool: liquidityPool.publicKey,
+        lpMint: lpMint.publicKey,
+      }).rpc();
+
+    const lpAta1 = await getATA(user1.publicKey, lpMint.publicKey);
+    await tokenProgram.methods.transfer(new BN(1_000))
+      .accounts({
+        source: lpAta1,
+        destination: keys.lpTokenPool,
+        authority: user1.publicKey,
+      }).signers([user1]).rpc();
+
+    const treasury = await getATA(provider.wallet.publicKey, lpMint.publicKey);
+    const balance = await tokenBalance(treasury);
+    const pooled = await tokenBalance(keys.lpTokenPool);
+
+    await program.methods.recoverStrandedLp()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+        destination: treasury,
+      }).signers([authority]).rpc();
+
+    expect(await tokenBalance(treasury)).to.be.equal(balance + 1_000);
+    expect(await tokenBalance(keys.lpTokenPool)).to.be.equal(pooled - 1_000);
+
+    // minted LP tokens are still claimable
+    await program.methods.claim()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        feeLpAccount,
+        authority: user1.publicKey,
+        destination: lpAta1,
+      }).signers([user1]).rpc();
+  });
+
+  it("Should preview refund eligibility", async () => {
+    const keys = await createCapitalCall(1, 4, 1_000_008);
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 100_000);
+    await program.methods.deposit(new BN(100_000), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+
+    const [voucher] = await PublicKey.findProgramAddress(
+      [anchor.utils.bytes.utf8.encode("voucher"), keys.capitalCall.toBuffer(), user1.publicKey.toBuffer()],
+      program.programId,
+    );
+    const previewRefund = () => program.methods.previewRefund()
+      .accounts({capitalCall: keys.capitalCall, voucher})
+      .simulate();
+
+    // capital call is still active
+    let preview = (await previewRefund()).events.find(e => e.name === "RefundPreviewEvent");
+    expect(preview.data.eligible).to.be.false;
+    expect(preview.data.amount.toNumber()).to.be.equal(0);
+
+    await new Promise(resolve => setTimeout(resolve, 4000));
+    preview = (await previewRefund()).events.find(e => e.name === "RefundPreviewEvent");
+    expect(preview.data.eligible).to.be.true;
+    expect(preview.data.amount.toNumber()).to.be.equal(100_000);
+  });
+
+  it("Should NOT deposit SOL if capital call doesn't raise wrapped SOL", async () => {
+    const keys = await createCapitalCall(1, 100, 1_000_009);
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const wsol = await getATA(user1.publicKey, NATIVE_MINT);
+    await provider.sendAndConfirm(new web3.Transaction().add(
+      createAssociatedTokenAccountInstruction(provider.wallet.publicKey, wsol, user1.publicKey, NATIVE_MINT)));
+
+    const depositSol = () => program.methods.depositSol(new BN(100_000), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: wsol,
+      }).signers([user1]).rpc();
+    await expect(depositSol()).to.be.rejectedWith(/MintNotNative/);
+
+    // closed wrapped SOL account can't receive refunds
+    await program.methods.updateCapitalCall({refundToSource: true})
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await expect(depositSol()).to.be.rejectedWith(/RefundToClosedSource/);
+  });
+
+  it("Should vest LP tokens by voucher override before call schedule", async () => {
+    const keys = await createCapitalCall(1, 100, 1_000_010);
+    const vestStart = await currentBlockTime() - 500;
+    await program.methods.updateCapitalCall({vestStart: new BN(vestStart), vestCliff: new BN(100), vestDuration: new BN(100_000)})
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    const ata2 = await getATA(user2.publicKey, mint.publicKey);
+    await mintTokens(ata1, 500_000);
+    await mintTokens(ata2, 500_010);
+
+    for (const [user, ata, amount] of [[user1, ata1, 500_000], [user2, ata2, 500_010]] as [Keypair, PublicKey, number][]) {
+      await program.methods.deposit(new BN(amount), null, NO_MEMO)
+        .accounts({
+          config: config.publicKey,
+          capitalCall: keys.capitalCall,
+          authority: user.publicKey,
+          source: ata,
+        }).signers([user]).rpc();
+    }
+
+    // user2 negotiated schedule already fully vested
+    const [voucher2] = await PublicKey.findProgramAddress(
+      [anchor.utils.bytes.utf8.encode("voucher"), keys.capitalCall.toBuffer(), user2.publicKey.toBuffer()],
+      program.programId,
+    );
+    await program.methods.setVestingOverride(new BN(vestStart), new BN(0), new BN(1))
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        voucher: voucher2,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+
+    await program.methods.mintLpTokens()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        liquidityPool: liquidityPool.publicKey,
+        lpMint: lpMint.publicKey,
+      }).rpc();
+
+    const lpAta1 = await getATA(user1.publicKey, lpMint.publicKey);
+    const lpAta2 = await getATA(user2.publicKey, lpMint.publicKey);
+    const ccAcc = await program.account.capitalCall.fetch(keys.capitalCall);
+    const lpAmount1 = new BN(500_000).mul(ccAcc.tokenLiquidity.add(ccAcc.creditOutstanding)).div(ccAcc.lpSupply).toNumber();
+    const lpAmount2 = new BN(500_010).mul(ccAcc.tokenLiquidity.add(ccAcc.creditOutstanding)).div(ccAcc.lpSupply).toNumber();
+
+    await expect(program.methods.claim()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        feeLpAccount,
+        authority: user1.publicKey,
+        destination: lpAta1,
+      }).signers([user1]).rpc()).to.be.rejectedWith(/VestingNotComplete/);
+
+    const balance1 = await tokenBalance(lpAta1);
+    await program.methods.claimVested()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        feeLpAccount,
+        authority: user1.publicKey,
+        destination: lpAta1,
+      }).signers([user1]).rpc();
+    const vested1 = await tokenBalance(lpAta1) - balance1;
+    expect(vested1).to.be.greaterThan(0);
+    expect(vested1).to.be.lessThan(lpAmount1 / 100);
+
+    const balance2 = await tokenBalance(lpAta2);
+    await program.methods.claim()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        feeLpAccount,
+        authority: user2.publicKey,
+        destination: lpAta2,
+      }).signers([user2]).rpc();
+    expect(await tokenBalance(lpAta2)).to.be.equal(balance2 + lpAmount2);
+  });
+
+  it("Should mint credit tokens at deposit and burn them at claim", async () => {
+    const keys = await createCapitalCall(1, 100, 1_000_011);
+
+    const creditMint = Keypair.generate();
+    await tokenProgram.methods.initializeMint(6, keys.capitalCall, null)
+      .accounts({
+        mint: creditMint.publicKey,
+        rent: web3.SYSVAR_RENT_PUBKEY,
+      })
+      .preInstructions([await tokenProgram.account.mint.createInstruction(creditMint)])
+      .signers([creditMint]).rpc();
+    const creditAta = await getATA(user1.publicKey, creditMint.publicKey);
+    await provider.sendAndConfirm(new web3.Transaction().add(
+      createAssociatedTokenAccountInstruction(provider.wallet.publicKey, creditAta, user1.publicKey, creditMint.publicKey)));
+
+    await program.methods.updateCapitalCall({creditTokenMint: creditMint.publicKey})
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const creditAccounts = [
+      {pubkey: creditMint.publicKey, isWritable: true, isSigner: false},
+      {pubkey: creditAta, isWritable: true, isSigner: false},
+    ];
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 1_000_011);
+    const deposit = program.methods.deposit(new BN(1_000_011), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]);
+    await expect(deposit.rpc()).to.be.rejectedWith(/InvalidCreditToken/);
+    await deposit.remainingAccounts(creditAccounts).rpc();
+    expect(await tokenBalance(creditAta)).to.be.equal(1_000_011);
+
+    await program.methods.mintLpTokens()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        liquidityPool: liquidityPool.publicKey,
+        lpMint: lpMint.publicKey,
+      }).rpc();
+
+    await program.methods.claim()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        feeLpAccount,
+        authority: user1.publicKey,
+        destination: await getATA(user1.publicKey, lpMint.publicKey),
+      })
+      .remainingAccounts(creditAccounts)
+      .signers([user1]).rpc();
+    expect(await tokenBalance(creditAta)).to.be.equal(0);
+  });
+
+  it("Should reduce deposit to max fill per transaction", async () => {
+    const keys = await createCapitalCall(1, 100, 1_000_012);
+    await program.methods.updateCapitalCall({maxFillBpsPerTx: 5_000})
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 800_000);
+    const balance = await tokenBalance(ata1);
+
+    await program.methods.deposit(new BN(800_000), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+
+    expect(await tokenBalance(ata1)).to.be.equal(balance - 500_006);
+    expect(await tokenBalance(keys.vault)).to.be.equal(500_006);
+  });
+
+  it("Should transfer claim fee to fee LP account", async () => {
+    const keys = await createCapitalCall(1, 100, 1_000_013);
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    const ata2 = await getATA(user2.publicKey, mint.publicKey);
+    await mintTokens(ata1, 500_000);
+    await mintTokens(ata2, 500_013);
+
+    for (const [user, ata, amount] of [[user1, ata1, 500_000], [user2, ata2, 500_013]] as [Keypair, PublicKey, number][]) {
+      await program.methods.deposit(new BN(amount), null, NO_MEMO)
+        .accounts({
+          config: config.publicKey,
+          capitalCall: keys.capitalCall,
+          authority: user.publicKey,
+          source: ata,
+        }).signers([user]).rpc();
+    }
+
+    await program.methods.mintLpTokens()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        liquidityPool: liquidityPool.publicKey,
+        lpMint: lpMint.publicKey,
+      }).rpc();
+
+    const ccAcc = await program.account.capitalCall.fetch(keys.capitalCall);
+    const lpAmount = (amount: number) => new BN(amount).mul(ccAcc.tokenLiquidity.add(ccAcc.creditOutstanding)).div(ccAcc.lpSupply).toNumber();
+    const claim = (user: Keypair, destination: PublicKey) => program.methods.claim()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        feeLpAccount,
+        authority: user.publicKey,
+        destination,
+      }).signers([user]).rpc();
+    const updateClaimFee = (claimFeeBps: number) => program.methods.updateConfig({claimFeeBps, feeLpAccount})
+      .accounts({
+        config: config.publicKey,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+
+    const lpAta1 = await getATA(user1.publicKey, lpMint.publicKey);
+    const lpAta2 = await getATA(user2.publicKey, lpMint.publicKey);
+    const balance1 = await tokenBalance(lpAta1);
+    const balance2 = await tokenBalance(lpAta2);
+    const feeBalance = await tokenBalance(feeLpAccount);
+
+    await updateClaimFee(1_000);
+    await claim(user1, lpAta1);
+    const fee = Math.floor(lpAmount(500_000) * 1_000 / 10_000);
+    expect(await tokenBalance(lpAta1)).to.be.equal(balance1 + lpAmount(500_000) - fee);
+    expect(await tokenBalance(feeLpAccount)).to.be.equal(feeBalance + fee);
+
+    // zero bps - depositor gets everything
+    await updateClaimFee(0);
+    await claim(user2, lpAta2);
+    expect(await tokenBalance(lpAta2)).to.be.equal(balance2 + lpAmount(500_013));
+    expect(await tokenBalance(feeLpAccount)).to.be.equal(feeBalance + fee);
+  });
+
+  it("Should refund fully funded capital call not finalized before deadline", async () => {
+    const keys = await createCapitalCall(1, 3, 1_000_014);
+    await program.methods.updateCapitalCall({finalizeWindow: new BN(2)})
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await new Promise(resolve => setTimeout(resolve, 1500));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 1_000_014);
+    const balance = await tokenBalance(ata1);
+    await program.methods.deposit(new BN(1_000_014), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+
+    const refund = () => program.methods.refund()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        destination: ata1,
+      }).signers([user1]).rpc();
+
+    // wait until end_time, finalize deadline isn't passed yet
+    await new Promise(resolve => setTimeout(resolve, 3000));
+    await expect(refund()).to.be.rejectedWith(/CapitalCallIsFullyFunded/);
+
+    await new Promise(resolve => setTimeout(resolve, 2500));
+    await expect(program.methods.mintLpTokens()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        liquidityPool: liquidityPool.publicKey,
+        lpMint: lpMint.publicKey,
+      }).rpc()).to.be.rejectedWith(/FinalizeDeadlinePassed/);
+    await refund();
+    expect(await tokenBalance(ata1)).to.be.equal(balance);
+  });
+
+  it("Should allow manager to perform capital call actions only", async () => {
+    const keys = await createCapitalCall(1, 100, 1_000_015, NO_IDEMPOTENCY_KEY, user2.publicKey);
+    const ccAcc = await program.account.capitalCall.fetch(keys.capitalCall);
+    expect(ccAcc.manager).to.be.deep.equal(user2.publicKey);
+
+    const shortenDuration = (signer: Keypair, newEndTime: number) => program.methods.shortenDuration(new BN(newEndTime))
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: signer.publicKey,
+      }).signers([signer]).rpc();
+
+    await expect(shortenDuration(user1, ccAcc.endTime.toNumber() - 10)).to.be.rejectedWith(/InvalidAuthority/);
+    await shortenDuration(user2, ccAcc.endTime.toNumber() - 20);
+    expect((await program.account.capitalCall.fetch(keys.capitalCall)).endTime.toNumber())
+      .to.be.equal(ccAcc.endTime.toNumber() - 20);
+
+    await expect(program.methods.updateConfig({finalizeCallbackProgram: PublicKey.default})
+      .accounts({
+        config: config.publicKey,
+        authority: user2.publicKey,
+      }).signers([user2]).rpc()).to.be.rejectedWith(/ConstraintHasOne/);
+  });
+
+  it("Should accept deposits only from allowed depositor program", async () => {
+    const keys = await createCapitalCall(1, 100, 1_000_016);
+    await program.methods.updateCapitalCall({allowedDepositorProgram: callbackMock.programId})
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 100_000);
+
+    await expect(program.methods.deposit(new BN(100_000), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      })
+      .remainingAccounts([{pubkey: web3.SYSVAR_INSTRUCTIONS_PUBKEY, isWritable: false, isSigner: false}])
+      .signers([user1]).rpc()).to.be.rejectedWith(/UnauthorizedDepositorProgram/);
+
+    const [voucher] = await PublicKey.findProgramAddress(
+      [anchor.utils.bytes.utf8.encode("voucher"), keys.capitalCall.toBuffer(), user1.publicKey.toBuffer()],
+      program.programId,
+    );
+    await callbackMock.methods.forwardDeposit(new BN(100_000))
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        voucher,
+        vault: keys.vault,
+        authority: user1.publicKey,
+        source: ata1,
+        tokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
+        capitalCallProgram: program.programId,
+      })
+      .remainingAccounts([{pubkey: web3.SYSVAR_INSTRUCTIONS_PUBKEY, isWritable: false, isSigner: false}])
+      .signers([user1]).rpc();
+
+    expect(await tokenBalance(keys.vault)).to.be.equal(100_000);
+  });
+
+  it("Should NOT refund within refund lockup", async () => {
+    const keys = await createCapitalCall(1, 2, 1_000_017, NO_IDEMPOTENCY_KEY, PublicKey.default, 3);
+    const ccAcc = await program.account.capitalCall.fetch(keys.capitalCall);
+    expect(ccAcc.refundLockup.toNumber()).to.be.equal(3);
+    await new Promise(resolve => setTimeout(resolve, 1500));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 100_000);
+    const balance = await tokenBalance(ata1);
+    await program.methods.deposit(new BN(100_000), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+
+    const refund = () => program.methods.refund()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        destination: ata1,
+      }).signers([user1]).rpc();
+
+    // wait until end_time
+    await new Promise(resolve => setTimeout(resolve, 2000));
+    await expect(refund()).to.be.rejectedWith(/RefundLockup/);
+
+    await new Promise(resolve => setTimeout(resolve, 3500));
+    await refund();
+    expect(await tokenBalance(ata1)).to.be.equal(balance);
+  });
+
+  it("Should finalize, distribute and close capital call in one transaction", async () => {
+    const keys = await createCapitalCall(1, 100, 1_000_018);
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    const ata2 = await getATA(user2.publicKey, mint.publicKey);
+    await mintTokens(ata1, 400_000);
+    await mintTokens(ata2, 600_018);
+
+    const vouchers = [];
+    for (const [user, ata, amount] of [[user1, ata1, 400_000], [user2, ata2, 600_018]] as [Keypair, PublicKey, number][]) {
+      await program.methods.deposit(new BN(amount), null, NO_MEMO)
+        .accounts({
+          config: config.publicKey,
+          capitalCall: keys.capitalCall,
+          authority: user.publicKey,
+          source: ata,
+        }).signers([user]).rpc();
+      const [voucher] = await PublicKey.findProgramAddress(
+        [anchor.utils.bytes.utf8.encode("voucher"), keys.capitalCall.toBuffer(), user.publicKey.toBuffer()],
+        program.programId,
+      );
+      vouchers.push(voucher);
+    }
+
+    const lpAta1 = await getATA(user1.publicKey, lpMint.publicKey);
+    const lpAta2 = await getATA(user2.publicKey, lpMint.publicKey);
+    const balance1 = await tokenBalance(lpAta1);
+    const balance2 = await tokenBalance(lpAta2);
+    const lpSupply = (await tokenProgram.account.mint.fetch(lpMint.publicKey)).supply;
+    const liquidity = new BN(await tokenBalance(liquidityPool.publicKey)).add(new BN(TSCreditOutstanding));
+    const lpAmount = (amount: number) => new BN(amount).mul(liquidity).div(lpSupply).toNumber();
+
+    await program.methods.finalizeAndDistribute(2)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        liquidityPool: liquidityPool.publicKey,
+        lpMint: lpMint.publicKey,
+        feeLpAccount,
+        authority: authority.publicKey,
+        receiver: provider.wallet.publicKey,
+      })
+      .remainingAccounts([
+        {pubkey: vouchers[0], isWritable: true, isSigner: false},
+        {pubkey: lpAta1, isWritable: true, isSigner: false},
+        {pubkey: user1.publicKey, isWritable: true, isSigner: false},
+        {pubkey: vouchers[1], isWritable: true, isSigner: false},
+        {pubkey: lpAta2, isWritable: true, isSigner: false},
+        {pubkey: user2.publicKey, isWritable: true, isSigner: false},
+      ])
+      .signers([authority]).rpc();
+
+    expect(await tokenBalance(lpAta1)).to.be.equal(balance1 + lpAmount(400_000));
+    expect(await tokenBalance(lpAta2)).to.be.equal(balance2 + lpAmount(600_018));
+    expect(await program.account.voucher.fetchNullable(vouchers[0])).to.be.null;
+    expect(await program.account.voucher.fetchNullable(vouchers[1])).to.be.null;
+    expect(await program.account.capitalCall.fetchNullable(keys.capitalCall)).to.be.null;
+  });
+
+  it("Should NOT create capital call with credit outstanding below minimum", async () => {
+    const updateMinCredit = (minCreditOutstanding: number) => program.methods.updateConfig({minCreditOutstanding: new BN(minCreditOutstanding)})
+      .accounts({
+        config: config.publicKey,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+
+    await updateMinCredit(TSCreditOutstanding + 1);
+    await expect(createCapitalCall(1, 100, 1_000_019)).to.be.rejectedWith(/CreditTooLow/);
+
+    await updateMinCredit(TSCreditOutstanding);
+    const keys = await createCapitalCall(1, 100, 1_000_019);
+    const ccAcc = await program.account.capitalCall.fetch(keys.capitalCall);
+    expect(ccAcc.creditOutstanding.toNumber()).to.be.equal(TSCreditOutstanding);
+
+    await updateMinCredit(0);
+  });
+
+  it("Should log LP allocations of depositors", async () => {
+    const keys = await createCapitalCall(1, 100, 1_000_020);
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const wallet = (provider.wallet as anchor.Wallet).payer;
+    const depositors = [[user1, 300_000], [user2, 300_000], [wallet, 400_020]] as [Keypair, number][];
+    const vouchers = [];
+    for (const [user, amount] of depositors) {
+      const ata = await getATA(user.publicKey, mint.publicKey);
+      await mintTokens(ata, amount);
+      await program.methods.deposit(new BN(amount), null, NO_MEMO)
+        .accounts({
+          config: config.publicKey,
+          capitalCall: keys.capitalCall,
+          authority: user.publicKey,
+          source: ata,
+        }).signers([user]).rpc();
+      const [voucher] = await PublicKey.findProgramAddress(
+        [anchor.utils.bytes.utf8.encode("voucher"), keys.capitalCall.toBuffer(), user.publicKey.toBuffer()],
+        program.programId,
+      );
+      vouchers.push(voucher);
+    }
+
+    await program.methods.mintLpTokens()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        liquidityPool: liquidityPool.publicKey,
+        lpMint: lpMint.publicKey,
+      }).rpc();
+
+    const ccAcc = await program.account.capitalCall.fetch(keys.capitalCall);
+    const simulation = await program.methods.logAllocations()
+      .accounts({capitalCall: keys.capitalCall})
+      .remainingAccounts(vouchers.map(pubkey => ({pubkey, isWritable: false, isSigner: false})))
+      .simulate();
+
+    const allocations = simulation.events.filter(e => e.name === "AllocationEvent");
+    expect(allocations.length).to.be.equal(3);
+    allocations.forEach((allocation, i) => {
+      const [user, amount] = depositors[i];
+      const expected = new BN(amount).mul(ccAcc.tokenLiquidity.add(ccAcc.creditOutstanding)).div(ccAcc.lpSupply);
+      expect(allocation.data.authority).to.be.deep.equal(user.publicKey);
+      expect(allocation.data.computedLp.toNumber()).to.be.equal(expected.toNumber());
+    });
+  });
+
+  it("Should NOT deposit if liquidity pool is under minimal balance", async () => {
+    const keys = await createCapitalCall(1, 100, 1_000_021);
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 100_000);
+
+    const updateMinPoolBalance = (minPoolBalance: number) => program.methods.updateConfig({minPoolBalance: new BN(minPoolBalance)})
+      .accounts({
+        config: config.publicKey,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    const deposit = (pool: PublicKey[] = [liquidityPool.publicKey]) => program.methods.deposit(new BN(100_000), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      })
+      .remainingAccounts(pool.map(pubkey => ({pubkey, isWritable: false, isSigner: false})))
+      .signers([user1]).rpc();
+
+    const poolBalance = await tokenBalance(liquidityPool.publicKey);
+    await updateMinPoolBalance(poolBalance + 1);
+    await expect(deposit()).to.be.rejectedWith(/LiquidityPoolUnhealthy/);
+    await expect(deposit([])).to.be.rejectedWith(/InvalidLiquidityPool/);
+    await expect(deposit([ata1])).to.be.rejectedWith(/InvalidLiquidityPool/);
+
+    await updateMinPoolBalance(poolBalance);
+    await deposit();
+    expect(await tokenBalance(keys.vault)).to.be.equal(100_000);
+
+    await updateMinPoolBalance(0);
+  });
+
+  it("Should retry failed LP tokens minting without double minting", async () => {
+    await program.methods.updateConfig({finalizeCallbackProgram: callbackMock.programId})
+      .accounts({config: config.publicKey, authority: authority.publicKey})
+      .signers([authority]).rpc();
+
+    const keys = await createCapitalCall(1, 100, 1_000_022);
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 1_000_022);
+    await program.methods.deposit(new BN(1_000_022), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+
+    const lpSupply = async () => (await tokenProgram.account.mint.fetch(lpMint.publicKey)).supply.toNumber();
+    const mintLpTokens = program.methods.mintLpTokens()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        liquidityPool: liquidityPool.publicKey,
+        lpMint: lpMint.publicKey,
+      });
+    const supply = await lpSupply();
+
+    // fails in finalize callback after LP tokens are minted and capital is deployed
+    await expect(mintLpTokens.rpc()).to.be.rejectedWith(/InvalidFinalizeCallbackProgram/);
+    expect(await lpSupply()).to.be.equal(supply);
+    expect(await tokenBalance(keys.vault)).to.be.equal(1_000_022);
+    expect((await program.account.capitalCall.fetch(keys.capitalCall)).isLpMinted).to.be.false;
+
+    await mintLpTokens
+      .remainingAccounts([{pubkey: callbackMock.programId, isWritable: false, isSigner: false}])
+      .rpc();
+    const ccAcc = await program.account.capitalCall.fetch(keys.capitalCall);
+    expect(ccAcc.isLpMinted).to.be.true;
+    expect(await lpSupply()).to.be.equal(supply + ccAcc.lpMinted.toNumber());
+
+    // already finalized capital call is no-op, vary accounts to avoid identical transaction
+    await mintLpTokens
+      .remainingAccounts([
+        {pubkey: callbackMock.programId, isWritable: false, isSigner: false},
+        {pubkey: keys.vault, isWritable: false, isSigner: false},
+      ])
+      .rpc();
+    expect(await lpSupply()).to.be.equal(supply + ccAcc.lpMinted.toNumber());
+
+    await program.methods.updateConfig({finalizeCallbackProgram: PublicKey.default})
+      .accounts({config: config.publicKey, authority: authority.publicKey})
+      .signers([authority]).rpc();
+  });
+
+  it("Should deposit only with gatekeeper signed allowance", async () => {
+    const gatekeeper = Keypair.generate();
+    const keys = await createCapitalCall(1, 100, 1_000_023);
+    await program.methods.updateCapitalCall({gatekeeper: gatekeeper.publicKey})
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 200_000);
+
+    const expiry = await currentBlockTime() + 100;
+    const allowance = (signer: Keypair, maxAmount: number) => web3.Ed25519Program.createInstructionWithPrivateKey({
+      privateKey: signer.secretKey,
+      message: Buffer.concat([
+        keys.capitalCall.toBuffer(),
+        user1.publicKey.toBuffer(),
+        new BN(maxAmount).toArrayLike(Buffer, "le", 8),
+        new BN(expiry).toArrayLike(Buffer, "le", 8),
+      ]),
+    });
+    const deposit = (amount: number, signer: Keypair) => program.methods.deposit(new BN(amount), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      })
+      .remainingAccounts([{pubkey: web3.SYSVAR_INSTRUCTIONS_PUBKEY, isWritable: false, isSigner: false}])
+      .preInstructions([allowance(signer, 150_000)])
+      .signers([user1]).rpc();
+
+    await expect(deposit(100_000, Keypair.generate())).to.be.rejectedWith(/InvalidGatekeeperSignature/);
+    await expect(deposit(200_000, gatekeeper)).to.be.rejectedWith(/InvalidGatekeeperSignature/);
+    await deposit(100_000, gatekeeper);
+    expect(await tokenBalance(keys.vault)).to.be.equal(100_000);
+  });
+
+  it("Should auto extend nearly funded capital call once", async () => {
+    const keys = await createCapitalCall(1, 4, 1_000_024);
+    await program.methods.updateCapitalCall({autoExtendThresholdBps: 9_000, autoExtendDuration: new BN(100)})
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 950_000);
+    await program.methods.deposit(new BN(950_000), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+
+    const tryAutoExtend = () => program.methods.tryAutoExtend()
+      .accounts({config: config.publicKey, capitalCall: keys.capitalCall});
+    await expect(tryAutoExtend().rpc()).to.be.rejectedWith(/CapitalCallNotEnded/);
+    await new Promise(resolve => setTimeout(resolve, 4000));
+
+    const endTime = (await program.account.capitalCall.fetch(keys.capitalCall)).endTime.toNumber();
+    await tryAutoExtend().rpc();
+    const ccAcc = await program.account.capitalCall.fetch(keys.capitalCall);
+    expect(ccAcc.autoExtended).to.be.true;
+    expect(ccAcc.endTime.toNumber()).to.be.equal(endTime + 100);
+
+    // vary accounts to avoid identical transaction
+    await expect(tryAutoExtend()
+      .remainingAccounts([{pubkey: keys.vault, isWritable: false, isSigner: false}])
+      .rpc()).to.be.rejectedWith(/AutoExtendNotAllowed/);
+  });
+
+  it("Should NOT auto extend capital call under threshold", async () => {
+    const keys = await createCapitalCall(1, 4, 1_000_025);
+    await program.methods.updateCapitalCall({autoExtendThresholdBps: 9_000, autoExtendDuration: new BN(100)})
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 500_000);
+    await program.methods.deposit(new BN(500_000), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+    await new Promise(resolve => setTimeout(resolve, 4000));
+
+    const endTime = (await program.account.capitalCall.fetch(keys.capitalCall)).endTime.toNumber();
+    await expect(program.methods.tryAutoExtend()
+      .accounts({config: config.publicKey, capitalCall: keys.capitalCall})
+      .rpc()).to.be.rejectedWith(/AutoExtendNotAllowed/);
+    const ccAcc = await program.account.capitalCall.fetch(keys.capitalCall);
+    expect(ccAcc.autoExtended).to.be.false;
+    expect(ccAcc.endTime.toNumber()).to.be.equal(endTime);
+  });
+
+  it("Should NOT claim vested amount under minimal claim unless it is final", async () => {
+    const keys = await createCapitalCall(1, 100, 1_000_026);
+    const vestStart = await currentBlockTime() - 500;
+    await program.methods.updateCapitalCall({vestStart: new BN(vestStart), vestCliff: new BN(0), vestDuration: new BN(100_000)})
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    const ata2 = await getATA(user2.publicKey, mint.publicKey);
+    await mintTokens(ata1, 1_000_000);
+    await mintTokens(ata2, 26);
+
+    for (const [user, ata, amount] of [[user1, ata1, 1_000_000], [user2, ata2, 26]] as [Keypair, PublicKey, number][]) {
+      await program.methods.deposit(new BN(amount), null, NO_MEMO)
+        .accounts({
+          config: config.publicKey,
+          capitalCall: keys.capitalCall,
+          authority: user.publicKey,
+          source: ata,
+        }).signers([user]).rpc();
+    }
+
+    // user2 dust is fully vested
+    const [voucher2] = await PublicKey.findProgramAddress(
+      [anchor.utils.bytes.utf8.encode("voucher"), keys.capitalCall.toBuffer(), user2.publicKey.toBuffer()],
+      program.programId,
+    );
+    await program.methods.setVestingOverride(new BN(vestStart), new BN(0), new BN(1))
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        voucher: voucher2,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+
+    await program.methods.mintLpTokens()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        liquidityPool: liquidityPool.publicKey,
+        lpMint: lpMint.publicKey,
+      }).rpc();
+
+    const updateMinClaim = (minClaim: number) => program.methods.updateConfig({minClaim: new BN(minClaim)})
+      .accounts({
+        config: config.publicKey,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    const claimVested = (user: Keypair, destination: PublicKey) => program.methods.claimVested()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        feeLpAccount,
+        authority: user.publicKey,
+        destination,
+      }).signers([user]).rpc();
+
+    const lpAta1 = await getATA(user1.publicKey, lpMint.publicKey);
+    const lpAta2 = await getATA(user2.publicKey, lpMint.publicKey);
+    const balance1 = await tokenBalance(lpAta1);
+    const balance2 = await tokenBalance(lpAta2);
+
+    // ~0.5% of 1_000_000 is vested
+    await updateMinClaim(100_000);
+    await expect(claimVested(user1, lpAta1)).to.be.rejectedWith(/ClaimBelowMinimum/);
+    await claimVested(user2, lpAta2);
+    expect(await tokenBalance(lpAta2)).to.be.greaterThan(balance2);
+
+    await updateMinClaim(1_000);
+    await claimVested(user1, lpAta1);
+    expect(await tokenBalance(lpAta1)).to.be.greaterThan(balance1);
+
+    await updateMinClaim(0);
+  });
+
+  it("Should NOT create capital call over max concurrent calls", async () => {
+    const updateMaxConcurrentCalls = (maxConcurrentCalls: number) => program.methods.updateConfig({maxConcurrentCalls})
+      .accounts({
+        config: config.publicKey,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+
+    const openCalls = (await program.account.config.fetch(config.publicKey)).openCalls;
+    await updateMaxConcurrentCalls(openCalls + 2);
+
+    await createCapitalCall(1, 100, 1_000_027);
+    await createCapitalCall(1, 100, 1_000_028);
+    expect((await program.account.config.fetch(config.publicKey)).openCalls).to.be.equal(openCalls + 2);
+    await expect(createCapitalCall(1, 100, 1_000_029)).to.be.rejectedWith(/TooManyOpenCalls/);
+
+    await updateMaxConcurrentCalls(0);
+    await createCapitalCall(1, 100, 1_000_029);
+  });
+
+  it("Should emit voucher status before and after LP tokens minting", async () => {
+    const keys = await createCapitalCall(1, 100, 1_000_030);
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 1_000_030);
+    await program.methods.deposit(new BN(1_000_030), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+
+    const [voucher] = await PublicKey.findProgramAddress(
+      [anchor.utils.bytes.utf8.encode("voucher"), keys.capitalCall.toBuffer(), user1.publicKey.toBuffer()],
+      program.programId,
+    );
+    const voucherStatus = async () => {
+      const simulation = await program.methods.voucherStatus()
+        .accounts({capitalCall: keys.capitalCall, voucher})
+        .simulate();
+      return simulation.events.find(e => e.name === "VoucherStatusEvent").data;
+    };
+
+    const before = await voucherStatus();
+    expect(before.authority).to.be.deep.equal(user1.publicKey);
+    expect(before.amount.toNumber()).to.be.equal(1_000_030);
+    expect(before.claimableLp.toNumber()).to.be.equal(0);
+    expect(before.vested.toNumber()).to.be.equal(0);
+    expect(before.isClaimable).to.be.false;
+
+    await program.methods.mintLpTokens()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        liquidityPool: liquidityPool.publicKey,
+        lpMint: lpMint.publicKey,
+      }).rpc();
+
+    const ccAcc = await program.account.capitalCall.fetch(keys.capitalCall);
+    const lpAmount = new BN(1_000_030).mul(ccAcc.tokenLiquidity.add(ccAcc.creditOutstanding)).div(ccAcc.lpSupply).toNumber();
+    const after = await voucherStatus();
+    expect(after.amount.toNumber()).to.be.equal(1_000_030);
+    expect(after.claimableLp.toNumber()).to.be.equal(lpAmount);
+    expect(after.vested.toNumber()).to.be.equal(lpAmount);
+    expect(after.isClaimable).to.be.true;
+  });
+
+  it("Should refund only to source of deposit", async () => {
+    const keys = await createCapitalCall(1, 3, 1_000_031);
+    await program.methods.updateCapitalCall({refundToSource: true})
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await new Promise(resolve => setTimeout(resolve, 1500));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    const ata2 = await getATA(user2.publicKey, mint.publicKey);
+    await mintTokens(ata1, 100_000);
+    const balance = await tokenBalance(ata1);
+    await program.methods.deposit(new BN(100_000), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+    await new Promise(resolve => setTimeout(resolve, 3000));
+
+    const refund = (destination: PublicKey) => program.methods.refund()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        destination,
+      }).signers([user1]).rpc();
+
+    await expect(refund(ata2)).to.be.rejectedWith(/RefundMustGoToSource/);
+    await refund(ata1);
+    expect(await tokenBalance(ata1)).to.be.equal(balance);
+  });
+
+  it("Should deposit as config authority without gatekeeper allowance only with bypass", async () => {
+    const keys = await createCapitalCall(1, 100, 1_000_032);
+    await program.methods.updateCapitalCall({gatekeeper: Keypair.generate().publicKey})
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+
+    await provider.connection.confirmTransaction(
+      await provider.connection.requestAirdrop(authority.publicKey, web3.LAMPORTS_PER_SOL)
+    );
+    const ata = await getATA(authority.publicKey, mint.publicKey);
+    await provider.sendAndConfirm(new web3.Transaction().add(
+      createAssociatedTokenAccountInstruction(provider.wallet.publicKey, ata, authority.publicKey, mint.publicKey)
+    ));
+    await mintTokens(ata, 200_000);
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const updateBypass = (authorityBypassWhitelist: boolean) => program.methods.updateConfig({authorityBypassWhitelist})
+      .accounts({
+        config: config.publicKey,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    const deposit = (amount: number) => program.methods.deposit(new BN(amount), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+        source: ata,
+      })
+      .remainingAccounts([{pubkey: web3.SYSVAR_INSTRUCTIONS_PUBKEY, isWritable: false, isSigner: false}])
+      .signers([authority]).rpc();
+
+    await expect(deposit(100_000)).to.be.rejectedWith(/InvalidGatekeeperSignature/);
+
+    await updateBypass(true);
+    await deposit(200_000);
+    expect(await tokenBalance(keys.vault)).to.be.equal(200_000);
+
+    await updateBypass(false);
+  });
+
+  it("Should match deposits from reserve until it's exhausted", async () => {
+    const keys = await createCapitalCall(1, 100, 1_000_033);
+
+    // reserve of provider delegating matched tokens to capital call
+    const reserve = Keypair.generate();
+    await tokenProgram.methods.initializeAccount()
+      .accounts({
+        account: reserve.publicKey,
+        mint: mint.publicKey,
+        authority: provider.wallet.publicKey,
+        rent: web3.SYSVAR_RENT_PUBKEY,
+      })
+      .signers([reserve])
+      .preInstructions([await tokenProgram.account.token.createInstruction(reserve)])
+      .rpc();
+    await mintTokens(reserve.publicKey, 10_000);
+    await tokenProgram.methods.approve(new BN(10_000))
+      .accounts({
+        source: reserve.publicKey,
+        delegate: keys.capitalCall,
+        authority: provider.wallet.publicKey,
+      }).rpc();
+
+    await program.methods.updateCapitalCall({matchReserve: reserve.publicKey, matchBps: 5_000})
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    const ata2 = await getATA(user2.publicKey, mint.publicKey);
+    await mintTokens(ata1, 20_000);
+    await mintTokens(ata2, 10_000);
+
+    const deposit = (user: Keypair, source: PublicKey, amount: number) => program.methods.deposit(new BN(amount), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user.publicKey,
+        source,
+      })
+      .remainingAccounts([{pubkey: reserve.publicKey, isWritable: true, isSigner: false}])
+      .signers([user]).rpc();
+    const voucherAmount = async (user: Keypair) => {
+      const [voucher] = await PublicKey.findProgramAddress(
+        [anchor.utils.bytes.utf8.encode("voucher"), keys.capitalCall.toBuffer(), user.publicKey.toBuffer()],
+        program.programId,
+      );
+      return (await program.account.voucher.fetch(voucher)).amount.toNumber();
+    };
+
+    await deposit(user1, ata1, 20_000);
+    expect(await voucherAmount(user1)).to.be.equal(30_000);
+    expect(await tokenBalance(reserve.publicKey)).to.be.equal(0);
+    expect(await tokenBalance(keys.vault)).to.be.equal(30_000);
+
+    // exhausted reserve matches nothing
+    await deposit(user2, ata2, 10_000);
+    expect(await voucherAmount(user2)).to.be.equal(10_000);
+    expect(await tokenBalance(keys.vault)).to.be.equal(40_000);
+  });
+
+  it("Should void unclaimed voucher only after expiry", async () => {
+    const keys = await createCapitalCall(1, 4, 1_000_034);
+    const updateVoucherExpiry = async (expiry: number) => program.methods.updateCapitalCall({voucherExpiry: new BN(expiry)})
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    // vouchers can't expire before end time
+    await expect(updateVoucherExpiry(await currentBlockTime() + 3)).to.be.rejectedWith(/InvalidVoucherExpiry/);
+    await updateVoucherExpiry(await currentBlockTime() + 7);
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 1_000_034);
+    await program.methods.deposit(new BN(1_000_034), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+
+    await program.methods.mintLpTokens()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        liquidityPool: liquidityPool.publicKey,
+        lpMint: lpMint.publicKey,
+      }).rpc();
+
+    const [voucher] = await PublicKey.findProgramAddress(
+      [anchor.utils.bytes.utf8.encode("voucher"), keys.capitalCall.toBuffer(), user1.publicKey.toBuffer()],
+      program.programId,
+    );
+    const updateVoidTreasury = (voidTreasury: PublicKey) => program.methods.updateConfig({voidTreasury})
+      .accounts({
+        config: config.publicKey,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await updateVoidTreasury(feeLpAccount);
+    const voidVoucher = (treasury: PublicKey = feeLpAccount) => program.methods.voidVoucher()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        voucher,
+        depositor: user1.publicKey,
+        treasury,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+
+    await expect(voidVoucher()).to.be.rejectedWith(/VoucherNotExpired/);
+    await new Promise(resolve => setTimeout(resolve, 5000));
+
+    // forfeited LP tokens go only to treasury of config
+    await expect(voidVoucher(keys.lpTokenPool))
+      .to.be.rejectedWith(/InvalidVoidTreasury/);
+
+    const ccAcc = await program.account.capitalCall.fetch(keys.capitalCall);
+    const lpAmount = new BN(1_000_034).mul(ccAcc.tokenLiquidity.add(ccAcc.creditOutstanding)).div(ccAcc.lpSupply).toNumber();
+    const treasuryBalance = await tokenBalance(feeLpAccount);
+    const voucherRent = (await provider.connection.getAccountInfo(voucher)).lamports;
+    const depositorLamports = await provider.connection.getBalance(user1.publicKey);
+    await voidVoucher();
+    expect(await tokenBalance(feeLpAccount)).to.be.equal(treasuryBalance + lpAmount);
+    expect(await tokenBalance(keys.lpTokenPool)).to.be.equal(0);
+    expect(await provider.connection.getAccountInfo(voucher)).to.be.null;
+    // LP tokens are forfeited, but rent of voucher goes back to depositor
+    expect(await provider.connection.getBalance(user1.publicKey)).to.be.equal(depositorLamports + voucherRent);
+
+    await updateVoidTreasury(PublicKey.default);
+  });
+
+  it("Should NOT log allocations of more vouchers than max batch size", async () => {
+    const keys = await createCapitalCall(1, 100, 1_000_035);
+
+    // MAX_BATCH_SIZE + 1
+    const vouchers = Array.from({length: 21}, () => Keypair.generate().publicKey);
+    await expect(program.methods.logAllocations()
+      .accounts({capitalCall: keys.capitalCall})
+      .remainingAccounts(vouchers.map(pubkey => ({pubkey, isWritable: false, isSigner: false})))
+      .rpc()).to.be.rejectedWith(/BatchTooLarge/);
+  });
+
+  it("Should finalize capital call funded in primary and secondary mint", async () => {
+    const keys = await createCapitalCall(1, 100, 1_000_036);
+
+    const secondaryMint = Keypair.generate();
+    await tokenProgram.methods.initializeMint(6, provider.wallet.publicKey, null)
+      .accounts({
+        mint: secondaryMint.publicKey,
+        rent: web3.SYSVAR_RENT_PUBKEY,
+      })
+      .preInstructions([await tokenProgram.account.mint.createInstruction(secondaryMint)])
+      .signers([secondaryMint]).rpc();
+    const secondaryPool = Keypair.generate();
+    await tokenProgram.methods.initializeAccount()
+      .accounts({
+        account: secondaryPool.publicKey,
+        mint: secondaryMint.publicKey,
+        authority: provider.wallet.publicKey,
+        rent: web3.SYSVAR_RENT_PUBKEY,
+      })
+      .signers([secondaryPool])
+      .preInstructions([await tokenProgram.account.token.createInstruction(secondaryPool)])
+      .rpc();
+
+    const [secondaryVault] = await PublicKey.findProgramAddress(
+      [anchor.utils.bytes.utf8.encode("secondary_vault"), keys.capitalCall.toBuffer()],
+      program.programId,
+    );
+    await program.methods.initSecondaryVault()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        vault: keys.vault,
+        mint: mint.publicKey,
+        secondaryVault,
+        secondaryMint: secondaryMint.publicKey,
+        secondaryLiquidityPool: secondaryPool.publicKey,
+        authority: authority.publicKey,
+        payer: provider.wallet.publicKey,
+      }).signers([authority]).rpc();
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 600_000);
+    await program.methods.deposit(new BN(600_000), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+
+    const secondaryAta2 = await getATA(user2.publicKey, secondaryMint.publicKey);
+    await provider.sendAndConfirm(new web3.Transaction().add(
+      createAssociatedTokenAccountInstruction(provider.wallet.publicKey, secondaryAta2, user2.publicKey, secondaryMint.publicKey)
+    ));
+    await tokenProgram.methods.mintTo(new BN(400_036))
+      .accounts({
+        mint: secondaryMint.publicKey,
+        to: secondaryAta2,
+        authority: provider.wallet.publicKey,
+      }).rpc();
+    await program.methods.depositSecondary(new BN(400_036), NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        secondaryVault,
+        authority: user2.publicKey,
+        source: secondaryAta2,
+      }).signers([user2]).rpc();
+
+    let ccAcc = await program.account.capitalCall.fetch(keys.capitalCall);
+    expect(ccAcc.allocated.toNumber()).to.be.equal(1_000_036);
+    expect(ccAcc.secondaryAllocated.toNumber()).to.be.equal(400_036);
+    expect(await tokenBalance(keys.vault)).to.be.equal(600_000);
+    expect(await tokenBalance(secondaryVault)).to.be.equal(400_036);
+
+    const poolBalance = await tokenBalance(liquidityPool.publicKey);
+    await program.methods.mintLpTokens()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        liquidityPool: liquidityPool.publicKey,
+        lpMint: lpMint.publicKey,
+      })
+      .remainingAccounts([
+        {pubkey: secondaryVault, isWritable: true, isSigner: false},
+        {pubkey: secondaryPool.publicKey, isWritable: true, isSigner: false},
+      ]).rpc();
+
+    ccAcc = await program.account.capitalCall.fetch(keys.capitalCall);
+    expect(ccAcc.isLpMinted).to.be.true;
+    expect(ccAcc.lpMinted.toNumber()).to.be.equal(
+      new BN(1_000_036).mul(ccAcc.tokenLiquidity.add(ccAcc.creditOutstanding)).div(ccAcc.lpSupply).toNumber()
+    );
+    expect(await tokenBalance(liquidityPool.publicKey)).to.be.equal(poolBalance + 600_000);
+    expect(await tokenBalance(secondaryPool.publicKey)).to.be.equal(400_036);
+    expect(await tokenBalance(secondaryVault)).to.be.equal(0);
+  });
+
+  it("Should count deposit toward fill only after confirmation", async () => {
+    const keys = await createCapitalCall(1, 100, 1_000_037);
+    await program.methods.updateCapitalCall({confirmationSlots: new BN(5)})
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 1_000_037);
+    await program.methods.deposit(new BN(1_000_037), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+
+    let ccAcc = await program.account.capitalCall.fetch(keys.capitalCall);
+    expect(ccAcc.allocated.toNumber()).to.be.equal(0);
+    expect(ccAcc.pendingAmount.toNumber()).to.be.equal(1_000_037);
+    expect(await tokenBalance(keys.vault)).to.be.equal(1_000_037);
+    await expect(program.methods.updateCapitalCall({refundCooldown: new BN(1)})
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc()).to.be.rejectedWith(/CapitalCallTermsLocked/);
+
+    const [voucher] = await PublicKey.findProgramAddress(
+      [anchor.utils.bytes.utf8.encode("voucher"), keys.capitalCall.toBuffer(), user1.publicKey.toBuffer()],
+      program.programId,
+    );
+    const confirmDeposit = () => program.methods.confirmDeposit()
+      .accounts({config: config.publicKey, capitalCall: keys.capitalCall, voucher})
+      .rpc();
+    const mintLpTokens = () => program.methods.mintLpTokens()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        liquidityPool: liquidityPool.publicKey,
+        lpMint: lpMint.publicKey,
+      }).rpc();
+
+    // pending deposit doesn't fill capital call
+    await mintLpTokens();
+    expect((await program.account.capitalCall.fetch(keys.capitalCall)).isLpMinted).to.be.false;
+    await expect(confirmDeposit()).to.be.rejectedWith(/DepositNotConfirmed/);
+
+    await new Promise(resolve => setTimeout(resolve, 4000));
+    await confirmDeposit();
+    ccAcc = await program.account.capitalCall.fetch(keys.capitalCall);
+    expect(ccAcc.allocated.toNumber()).to.be.equal(1_000_037);
+    expect(ccAcc.pendingAmount.toNumber()).to.be.equal(0);
+    expect((await program.account.voucher.fetch(voucher)).amount.toNumber()).to.be.equal(1_000_037);
+
+    await mintLpTokens();
+    expect((await program.account.capitalCall.fetch(keys.capitalCall)).isLpMinted).to.be.true;
+  });
+
+  it("Should redeem LP tokens against vault reserve", async () => {
+    const keys = await createCapitalCall(1, 100, 1_000_038);
+    await program.methods.updateCapitalCall({reserveBps: 1_000, claimDelay: new BN(3)})
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 1_000_038);
+    await program.methods.deposit(new BN(1_000_038), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+
+    const poolBalance = await tokenBalance(liquidityPool.publicKey);
+    await program.methods.mintLpTokens()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        liquidityPool: liquidityPool.publicKey,
+        lpMint: lpMint.publicKey,
+      }).rpc();
+    expect(await tokenBalance(keys.vault)).to.be.equal(100_003);
+    expect(await tokenBalance(liquidityPool.publicKey)).to.be.equal(poolBalance + 1_000_038 - 100_003);
+
+    const lpAta1 = await getATA(user1.publicKey, lpMint.publicKey);
+    const redeem = (lpAmount: BN, user: Keypair = user1, source: PublicKey = lpAta1, destination: PublicKey = ata1) => program.methods.redeem(lpAmount)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        lpMint: lpMint.publicKey,
+        authority: user.publicKey,
+        source,
+        destination,
+      }).signers([user]).rpc();
+
+    // reserve opens with claims
+    await expect(redeem(new BN(1))).to.be.rejectedWith(/ClaimNotOpenYet/);
+    await new Promise(resolve => setTimeout(resolve, 3000));
+
+    await program.methods.claim()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        feeLpAccount,
+        authority: user1.publicKey,
+        destination: lpAta1,
+      }).signers([user1]).rpc();
+
+    const ccAcc = await program.account.capitalCall.fetch(keys.capitalCall);
+    const value = ccAcc.tokenLiquidity.add(ccAcc.creditOutstanding);
+    const lpAmount = new BN(50_000).mul(value).div(ccAcc.lpSupply);
+    const amount = lpAmount.mul(ccAcc.lpSupply).div(value).toNumber();
+
+    const lpBalance = await tokenBalance(lpAta1);
+    const balance = await tokenBalance(ata1);
+    await redeem(lpAmount);
+    expect(await tokenBalance(lpAta1)).to.be.equal(lpBalance - lpAmount.toNumber());
+    expect(await tokenBalance(ata1)).to.be.equal(balance + amount);
+    expect(await tokenBalance(keys.vault)).to.be.equal(100_003 - amount);
+    expect((await program.account.capitalCall.fetch(keys.capitalCall)).vaultReserve.toNumber()).to.be.equal(100_003 - amount);
+
+    // remaining reserve is less than 100_000
+    await expect(redeem(new BN(100_000).mul(value).div(ccAcc.lpSupply))).to.be.rejectedWith(/InsufficientVaultBalance/);
+
+    // reserve is a pool-wide exit, LP tokens of config redeem regardless of their depositor
+    const lpAta2 = await getATA(user2.publicKey, lpMint.publicKey);
+    const ata2 = await getATA(user2.publicKey, mint.publicKey);
+    await tokenProgram.methods.transfer(lpAmount)
+      .accounts({
+        source: lpAta1,
+        destination: lpAta2,
+        authority: user1.publicKey,
+      }).signers([user1]).rpc();
+    const balance2 = await tokenBalance(ata2);
+    await redeem(lpAmount, user2, lpAta2, ata2);
+    expect(await tokenBalance(ata2)).to.be.equal(balance2 + amount);
+    expect(await tokenBalance(keys.vault)).to.be.equal(100_003 - 2 * amount);
+  });
+
+  it("Should NOT mint LP tokens while finalization is paused", async () => {
+    const keys = await createCapitalCall(1, 100, 1_000_039);
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 1_000_039);
+    await program.methods.deposit(new BN(1_000_039), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+
+    const setFinalizationPaused = (paused: boolean) => program.methods.setFinalizationPaused(paused)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    const mintLpTokens = () => program.methods.mintLpTokens()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        liquidityPool: liquidityPool.publicKey,
+        lpMint: lpMint.publicKey,
+      }).rpc();
+
+    await setFinalizationPaused(true);
+    await expect(mintLpTokens()).to.be.rejectedWith(/FinalizationPaused/);
+
+    await setFinalizationPaused(false);
+    await mintLpTokens();
+    expect((await program.account.capitalCall.fetch(keys.capitalCall)).isLpMinted).to.be.true;
+  });
+
+  it("Should claim and refund only to associated token accounts in ATA destinations mode", async () => {
+    const user3 = Keypair.generate();
+    await provider.connection.confirmTransaction(
+      await provider.connection.requestAirdrop(user3.publicKey, web3.LAMPORTS_PER_SOL)
+    );
+    const ata3 = await getATA(user3.publicKey, mint.publicKey);
+    await provider.sendAndConfirm(new web3.Transaction().add(
+      createAssociatedTokenAccountInstruction(provider.wallet.publicKey, ata3, user3.publicKey, mint.publicKey)
+    ));
+    await mintTokens(ata3, 1_000_050);
+
+    const updateAtaDestinations = (ataDestinations: boolean) => program.methods.updateConfig({ataDestinations})
+      .accounts({
+        config: config.publicKey,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    const deposit = (capitalCall: PublicKey, amount: number) => program.methods.deposit(new BN(amount), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall,
+        authority: user3.publicKey,
+        source: ata3,
+      }).signers([user3]).rpc();
+
+    const keys = await createCapitalCall(1, 100, 1_000_040);
+    const refundKeys = await createCapitalCall(1, 3, 1_000_041);
+    await new Promise(resolve => setTimeout(resolve, 1500));
+    await deposit(keys.capitalCall, 1_000_040);
+    await deposit(refundKeys.capitalCall, 10);
+    await updateAtaDestinations(true);
+
+    await program.methods.mintLpTokens()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        liquidityPool: liquidityPool.publicKey,
+        lpMint: lpMint.publicKey,
+      }).rpc();
+
+    const claim = (destination: PublicKey) => program.methods.claim()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        feeLpAccount,
+        authority: user3.publicKey,
+        destination,
+      }).signers([user3]);
+    await expect(claim(feeLpAccount).rpc()).to.be.rejectedWith(/InvalidDestination/);
+
+    // LP tokens ATA is created in the same transaction
+    const lpAta3 = await getATA(user3.publicKey, lpMint.publicKey);
+    await claim(lpAta3)
+      .preInstructions([
+        createAssociatedTokenAccountInstruction(user3.publicKey, lpAta3, user3.publicKey, lpMint.publicKey)
+      ]).rpc();
+    const ccAcc = await program.account.capitalCall.fetch(keys.capitalCall);
+    const lpAmount = new BN(1_000_040).mul(ccAcc.tokenLiquidity.add(ccAcc.creditOutstanding)).div(ccAcc.lpSupply).toNumber();
+    expect(await tokenBalance(lpAta3)).to.be.equal(lpAmount);
+
+    await new Promise(resolve => setTimeout(resolve, 3000));
+    const refund = (destination: PublicKey) => program.methods.refund()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: refundKeys.capitalCall,
+        authority: user3.publicKey,
+        destination,
+      }).signers([user3]).rpc();
+    await expect(refund(await getATA(user1.publicKey, mint.publicKey))).to.be.rejectedWith(/InvalidDestination/);
+    const balance = await tokenBalance(ata3);
+    await refund(ata3);
+    expect(await tokenBalance(ata3)).to.be.equal(balance + 10);
+
+    await updateAtaDestinations(false);
+  });
+
+  it("Should refund cancelled capital call right away and close it", async () => {
+    const keys = await createCapitalCall(1, 100, 1_000_042);
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 100_000);
+    const balance = await tokenBalance(ata1);
+    await program.methods.deposit(new BN(100_000), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+
+    const closeCancelled = () => program.methods.closeCancelled()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+        receiver: authority.publicKey,
+        destination: ata1,
+      }).signers([authority]).rpc();
+    await expect(closeCancelled()).to.be.rejectedWith(/CapitalCallNotCancelled/);
+
+    await program.methods.cancel()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await expect(closeCancelled()).to.be.rejectedWith(/CapitalCallHasToBeFullyRefunded/);
+
+    await program.methods.refund()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        destination: ata1,
+      }).signers([user1]).rpc();
+    expect(await tokenBalance(ata1)).to.be.equal(balance);
+
+    await closeCancelled();
+    expect(await program.account.capitalCall.fetchNullable(keys.capitalCall)).to.be.null;
+    expect(await provider.connection.getAccountInfo(keys.vault)).to.be.null;
+    expect(await provider.connection.getAccountInfo(keys.lpTokenPool)).to.be.null;
+  });
+
+  it("Should NOT create capital call starting sooner than min lead time", async () => {
+    const updateMinLeadTime = (minLeadTime: number) => program.methods.updateConfig({minLeadTime: new BN(minLeadTime)})
+      .accounts({
+        config: config.publicKey,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+
+    await updateMinLeadTime(100);
+    await expect(createCapitalCall(1, 100, 1_000_043)).to.be.rejectedWith(/StartTimeTooSoon/);
+    await createCapitalCall(200, 100, 1_000_043);
+
+    await updateMinLeadTime(0);
+  });
+
+  it("Should NOT mint LP tokens transferring more than max finalize transfer", async () => {
+    const keys = await createCapitalCall(1, 100, 1_000_044);
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 1_000_044);
+    await program.methods.deposit(new BN(1_000_044), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+
+    const updateMaxFinalizeTransfer = (maxFinalizeTransfer: number) => program.methods.updateConfig({maxFinalizeTransfer: new BN(maxFinalizeTransfer)})
+      .accounts({
+        config: config.publicKey,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    const mintLpTokens = () => program.methods.mintLpTokens()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        liquidityPool: liquidityPool.publicKey,
+        lpMint: lpMint.publicKey,
+      }).rpc();
+
+    await updateMaxFinalizeTransfer(1_000_043);
+    await expect(mintLpTokens()).to.be.rejectedWith(/FinalizeTransferTooLarge/);
+
+    await updateMaxFinalizeTransfer(1_000_044);
+    await mintLpTokens();
+    expect((await program.account.capitalCall.fetch(keys.capitalCall)).isLpMinted).to.be.true;
+
+    await updateMaxFinalizeTransfer(0);
+  });
+
+  it("Should stamp events with program version", async () => {
+    const stats = await program.methods.configStats()
+      .accounts({config: config.publicKey})
+      .simulate();
+    const event = stats.events.find(e => e.name === "ConfigStatsEvent");
+    expect(event.data.programVersion).to.be.equal(1);
+  });
+
+  it("Should claim only after claim delay since LP tokens minting", async () => {
+    const keys = await createCapitalCall(1, 100, 1_000_045);
+    await program.methods.updateCapitalCall({claimDelay: new BN(3)})
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 1_000_045);
+    await program.methods.deposit(new BN(1_000_045), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+    await program.methods.mintLpTokens()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        liquidityPool: liquidityPool.publicKey,
+        lpMint: lpMint.publicKey,
+      }).rpc();
+
+    const lpAta1 = await getATA(user1.publicKey, lpMint.publicKey);
+    const claim = () => program.methods.claim()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        feeLpAccount,
+        authority: user1.publicKey,
+        destination: lpAta1,
+      }).signers([user1]).rpc();
+
+    await expect(claim()).to.be.rejectedWith(/ClaimNotOpenYet/);
+    await new Promise(resolve => setTimeout(resolve, 4000));
+    await claim();
+  });
+
+  it("Should close config only without open capital calls", async () => {
+    const config2 = Keypair.generate();
+    await program.methods.initialize()
+      .accounts({
+        config: config2.publicKey,
+        lpMint: lpMint.publicKey,
+        liquidityPool: liquidityPool.publicKey,
+        payer: provider.wallet.publicKey,
+        authority: authority.publicKey,
+      }).signers([config2])
+      .rpc();
+
+    const startTime = await currentBlockTime() + 100;
+    const [capitalCall] = await PublicKey.findProgramAddress(
+      [
+        anchor.utils.bytes.utf8.encode("capital_call"),
+        config2.publicKey.toBuffer(),
+        new BN(startTime).toArrayLike(Buffer, "le", 8),
+        new BN(1_000_046).toArrayLike(Buffer, "le", 8),
+        Buffer.from(NO_IDEMPOTENCY_KEY),
+      ],
+      program.programId
+    );
+    const builder = program.methods.createCapitalCall(
+      new BN(startTime), new BN(100), new BN(1_000_046), new BN(TSCreditOutstanding),
+      NO_IDEMPOTENCY_KEY, PublicKey.default, new BN(0),
+    ).accounts({
+      config: config2.publicKey,
+      capitalCall,
+      mint: mint.publicKey,
+      lpMint: lpMint.publicKey,
+      authority: authority.publicKey,
+      payer: provider.wallet.publicKey,
+    }).signers([authority]);
+    await builder.rpc();
+
+    const [vault] = await PublicKey.findProgramAddress(
+      [anchor.utils.bytes.utf8.encode("vault"), capitalCall.toBuffer()],
+      program.programId
+    );
+    const closeConfig = (closed: PublicKey[] = []) => program.methods.closeConfig()
+      .accounts({
+        config: config2.publicKey,
+        authority: authority.publicKey,
+        receiver: authority.publicKey,
+      })
+      .remainingAccounts(closed.map(pubkey => ({pubkey, isWritable: false, isSigner: false})))
+      .signers([authority]).rpc();
+    await expect(closeConfig()).to.be.rejectedWith(/OpenCallsRemain/);
+    await expect(closeConfig([capitalCall, vault])).to.be.rejectedWith(/CapitalCallNotClosed/);
+
+    await program.methods.cancel()
+      .accounts({
+        config: config2.publicKey,
+        capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await program.methods.closeCancelled()
+      .accounts({
+        config: config2.publicKey,
+        capitalCall,
+        authority: authority.publicKey,
+        receiver: authority.publicKey,
+        destination: await getATA(user1.publicKey, mint.publicKey),
+      }).signers([authority]).rpc();
+
+    await closeConfig([capitalCall, vault]);
+    expect(await program.account.config.fetchNullable(config2.publicKey)).to.be.null;
+  });
+
+  it("Should store deposit memo on voucher and in event", async () => {
+    const keys = await createCapitalCall(1, 100, 1_000_047);
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 100_000);
+    const memo = Array.from(Buffer.from("order-1000047".padEnd(32, "\0")));
+    const deposit = program.methods.deposit(new BN(100_000), null, memo)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]);
+
+    const simulation = await deposit.simulate();
+    const event = simulation.events.find(e => e.name === "DepositEvent");
+    expect(event.data.memo).to.be.deep.equal(memo);
+    await deposit.rpc();
+
+    const [voucher] = await PublicKey.findProgramAddress(
+      [anchor.utils.bytes.utf8.encode("voucher"), keys.capitalCall.toBuffer(), user1.publicKey.toBuffer()],
+      program.programId,
+    );
+    expect((await program.account.voucher.fetch(voucher)).memo).to.be.deep.equal(memo);
+  });
+
+  it("Should claim only LP tokens released so far over TWAP release duration", async () => {
+    const keys = await createCapitalCall(1, 100, 1_000_048);
+    await program.methods.updateCapitalCall({twapReleaseDuration: new BN(8)})
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 1_000_048);
+    await program.methods.deposit(new BN(1_000_048), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+    await program.methods.mintLpTokens()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        liquidityPool: liquidityPool.publicKey,
+        lpMint: lpMint.publicKey,
+      }).rpc();
+
+    const lpAta1 = await getATA(user1.publicKey, lpMint.publicKey);
+    const balance = await tokenBalance(lpAta1);
+    const claimAccounts = {
+      config: config.publicKey,
+      capitalCall: keys.capitalCall,
+      feeLpAccount,
+      authority: user1.publicKey,
+      destination: lpAta1,
+    };
+
+    await expect(program.methods.claim().accounts(claimAccounts).signers([user1]).rpc())
+      .to.be.rejectedWith(/VestingNotComplete/);
+
+    await new Promise(resolve => setTimeout(resolve, 2000));
+    await program.methods.claimVested().accounts(claimAccounts).signers([user1]).rpc();
+    const ccAcc = await program.account.capitalCall.fetch(keys.capitalCall);
+    const lpAmount = new BN(1_000_048).mul(ccAcc.tokenLiquidity.add(ccAcc.creditOutstanding)).div(ccAcc.lpSupply).toNumber();
+    const released = await tokenBalance(lpAta1) - balance;
+    expect(released).to.be.greaterThan(0);
+    expect(released).to.be.lessThan(lpAmount);
+
+    await new Promise(resolve => setTimeout(resolve, 8000));
+    await program.methods.claim().accounts(claimAccounts).signers([user1]).rpc();
+    expect(await tokenBalance(lpAta1) - balance).to.be.equal(lpAmount);
+  });
+
+  it("Should create capital call only with credit outstanding matching debt account", async () => {
+    const debtOwner = Keypair.generate();
+    const debtAccount = await getATA(debtOwner.publicKey, mint.publicKey);
+    await provider.sendAndConfirm(new web3.Transaction().add(
+      createAssociatedTokenAccountInstruction(provider.wallet.publicKey, debtAccount, debtOwner.publicKey, mint.publicKey)
+    ));
+    await mintTokens(debtAccount, TSCreditOutstanding - 1);
+
+    const updateDebtAccount = (debtAccount: PublicKey) => program.methods.updateConfig({debtAccount})
+      .accounts({
+        config: config.publicKey,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    const createCapitalCallWithDebt = async () => {
+      const startTime = await currentBlockTime() + 100;
+      await program.methods.createCapitalCall(
+        new BN(startTime), new BN(100), new BN(1_000_049), new BN(TSCreditOutstanding),
+        NO_IDEMPOTENCY_KEY, PublicKey.default, new BN(0),
+      ).accounts({
+        config: config.publicKey,
+        capitalCall: await findCapitalCall(startTime, 1_000_049, NO_IDEMPOTENCY_KEY),
+        mint: mint.publicKey,
+        lpMint: lpMint.publicKey,
+        authority: authority.publicKey,
+        payer: provider.wallet.publicKey,
+      })
+        .remainingAccounts([{pubkey: debtAccount, isSigner: false, isWritable: false}])
+        .signers([authority]).rpc();
+    };
+
+    await updateDebtAccount(debtAccount);
+    await expect(createCapitalCall(1, 100, 1_000_049)).to.be.rejectedWith(/InvalidDebtAccount/);
+    await expect(createCapitalCallWithDebt()).to.be.rejectedWith(/CreditOutstandingMismatch/);
+
+    await mintTokens(debtAccount, 1);
+    await createCapitalCallWithDebt();
+
+    await updateDebtAccount(PublicKey.default);
+  });
+
+  it("Should allow guardian only emergency actions", async () => {
+    const guardian = Keypair.generate();
+    await program.methods.setGuardian(guardian.publicKey)
+      .accounts({
+        config: config.publicKey,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+
+    const keys = await createCapitalCall(1, 100, 1_000_051);
+    await program.methods.setCallFrozen(true)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: guardian.publicKey,
+      }).signers([guardian]).rpc();
+    expect((await program.account.capitalCall.fetch(keys.capitalCall)).frozen).to.be.true;
+    await program.methods.setCallFrozen(false)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: guardian.publicKey,
+      }).signers([guardian]).rpc();
+
+    const blockTime = await currentBlockTime();
+    await expect(program.methods.createCapitalCall(
+      new BN(blockTime + 1), new BN(100), new BN(1_000_052), new BN(TSCreditOutstanding),
+      NO_IDEMPOTENCY_KEY, PublicKey.default, new BN(0),
+    ).accounts({
+      config: config.publicKey,
+      capitalCall: await findCapitalCall(blockTime + 1, 1_000_052, NO_IDEMPOTENCY_KEY),
+      mint: mint.publicKey,
+      lpMint: lpMint.publicKey,
+      authority: guardian.publicKey,
+      payer: provider.wallet.publicKey,
+    }).signers([guardian]).rpc()).to.be.rejectedWith(/ConstraintHasOne/);
+
+    await expect(program.methods.close(new BN(0))
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: guardian.publicKey,
+        receiver: provider.wallet.publicKey,
+        lpMint: lpMint.publicKey,
+        destination: await getATA(user1.publicKey, mint.publicKey),
+      }).signers([guardian]).rpc()).to.be.rejectedWith(/ConstraintHasOne/);
+
+    // refunds move funds
+    await expect(program.methods.cancelAndRefund()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: guardian.publicKey,
+      }).signers([guardian]).rpc()).to.be.rejectedWith(/InvalidAuthority/);
+
+    await program.methods.setGuardian(PublicKey.default)
+      .accounts({
+        config: config.publicKey,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+  });
+
+  it("Should re-finalize unwound capital call with fresh pool state", async () => {
+    const keys = await createCapitalCall(1, 100, 1_000_053);
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 1_000_053);
+    await program.methods.deposit(new BN(1_000_053), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+
+    const mintLpTokens = () => program.methods.mintLpTokens()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        liquidityPool: liquidityPool.publicKey,
+        lpMint: lpMint.publicKey,
+      }).rpc();
+
+    await mintLpTokens();
+    const finalized = await program.account.capitalCall.fetch(keys.capitalCall);
+    const poolBalance = await tokenBalance(liquidityPool.publicKey);
+
+    await program.methods.unwind()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+        liquidityPool: liquidityPool.publicKey,
+        poolAuthority: provider.wallet.publicKey,
+        lpMint: lpMint.publicKey,
+      }).signers([authority]).rpc();
+
+    let ccAcc = await program.account.capitalCall.fetch(keys.capitalCall);
+    expect(ccAcc.isLpMinted).to.be.false;
+    expect(ccAcc.lpSupply.toNumber()).to.be.equal(0);
+    expect(ccAcc.tokenLiquidity.toNumber()).to.be.equal(0);
+    expect(await tokenBalance(keys.vault)).to.be.equal(1_000_053);
+    expect(await tokenBalance(keys.lpTokenPool)).to.be.equal(0);
+    expect(await tokenBalance(liquidityPool.publicKey)).to.be.equal(poolBalance - 1_000_053);
+
+    // pool state changes before re-finalization
+    await mintTokens(liquidityPool.publicKey, 5_000_000);
+    await mintLpTokens();
+
+    ccAcc = await program.account.capitalCall.fetch(keys.capitalCall);
+    expect(ccAcc.isLpMinted).to.be.true;
+    expect(ccAcc.tokenLiquidity.toNumber()).to.be.equal(finalized.tokenLiquidity.toNumber() + 5_000_000);
+    expect(ccAcc.lpMinted.toNumber()).to.be.greaterThan(finalized.lpMinted.toNumber());
+  });
+
+  it("Should reduce deposit leaving sliver below min deposit and fill capacity", async () => {
+    const keys = await createCapitalCall(1, 100, 1_000_054);
+    await program.methods.updateCapitalCall({minDeposit: new BN(100_000)})
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    const ata2 = await getATA(user2.publicKey, mint.publicKey);
+    await mintTokens(ata1, 1_000_000);
+    await mintTokens(ata2, 100_000);
+    const deposit = (user: Keypair, source: PublicKey, amount: number) => program.methods.deposit(new BN(amount), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user.publicKey,
+        source,
+      }).signers([user]).rpc();
+
+    await deposit(user1, ata1, 950_000);
+    expect((await program.account.capitalCall.fetch(keys.capitalCall)).allocated.toNumber()).to.be.equal(900_054);
+
+    await expect(deposit(user2, ata2, 50_000)).to.be.rejectedWith(/DepositBelowMinimum/);
+    await deposit(user2, ata2, 100_000);
+    expect((await program.account.capitalCall.fetch(keys.capitalCall)).allocated.toNumber()).to.be.equal(1_000_054);
+  });
+
+  it("Should claim more LP tokens per unit in higher bonus tier", async () => {
+    const keys = await createCapitalCall(1, 100, 1_000_055);
+    const updateBonusTiers = (bonusTiers) => program.methods.updateCapitalCall({bonusTiers})
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await expect(updateBonusTiers([
+      {minAmount: new BN(500_000), bonusBps: 500},
+      {minAmount: new BN(100_000), bonusBps: 100},
+    ])).to.be.rejectedWith(/InvalidBonusTiers/);
+    await updateBonusTiers([
+      {minAmount: new BN(100_000), bonusBps: 100},
+      {minAmount: new BN(500_000), bonusBps: 500},
+    ]);
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    const ata2 = await getATA(user2.publicKey, mint.publicKey);
+    await mintTokens(ata1, 100_055);
+    await mintTokens(ata2, 900_000);
+    const deposit = (user: Keypair, source: PublicKey, amount: number) => program.methods.deposit(new BN(amount), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user.publicKey,
+        source,
+      }).signers([user]).rpc();
+    await deposit(user1, ata1, 100_055);
+    await deposit(user2, ata2, 900_000);
+
+    await program.methods.mintLpTokens()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        liquidityPool: liquidityPool.publicKey,
+        lpMint: lpMint.publicKey,
+      }).rpc();
+
+    const claim = async (user: Keypair) => {
+      const destination = await getATA(user.publicKey, lpMint.publicKey);
+      const balance = await tokenBalance(destination);
+      await program.methods.claim()
+        .accounts({
+          config: config.publicKey,
+          capitalCall: keys.capitalCall,
+          feeLpAccount,
+          authority: user.publicKey,
+          destination,
+        }).signers([user]).rpc();
+      return await tokenBalance(destination) - balance;
+    };
+    const lp1 = await claim(user1);
+    const lp2 = await claim(user2);
+
+    const ccAcc = await program.account.capitalCall.fetch(keys.capitalCall);
+    const toLp = (amount: number) => new BN(amount).mul(ccAcc.tokenLiquidity.add(ccAcc.creditOutstanding)).div(ccAcc.lpSupply).toNumber();
+    expect(lp1).to.be.equal(toLp(100_055 + 1_000));
+    expect(lp2).to.be.equal(toLp(900_000 + 45_000));
+    expect(lp2 / 900_000).to.be.greaterThan(lp1 / 100_055);
+  });
+
+  it("Should cap deposit at multiple of depositor's stake", async () => {
+    const stakeMint = Keypair.generate();
+    await tokenProgram.methods.initializeMint(6, provider.wallet.publicKey, null)
+      .accounts({
+        mint: stakeMint.publicKey,
+        rent: web3.SYSVAR_RENT_PUBKEY,
+      })
+      .preInstructions([await tokenProgram.account.mint.createInstruction(stakeMint)])
+      .signers([stakeMint]).rpc();
+    const createStake = async (user: Keypair, amount: number) => {
+      const stake = await getATA(user.publicKey, stakeMint.publicKey);
+      await provider.sendAndConfirm(new web3.Transaction().add(
+        createAssociatedTokenAccountInstruction(provider.wallet.publicKey, stake, user.publicKey, stakeMint.publicKey)
+      ));
+      await tokenProgram.methods.mintTo(new BN(amount))
+        .accounts({
+          mint: stakeMint.publicKey,
+          to: stake,
+          authority: provider.wallet.publicKey,
+        }).rpc();
+      return stake;
+    };
+    const stake1 = await createStake(user1, 100_000);
+    const stake2 = await createStake(user2, 300_000);
+
+    const keys = await createCapitalCall(1, 100, 1_000_056);
+    await program.methods.updateCapitalCall({stakeMint: stakeMint.publicKey, stakeCapMultiplier: new BN(2)})
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    const ata2 = await getATA(user2.publicKey, mint.publicKey);
+    await mintTokens(ata1, 300_000);
+    await mintTokens(ata2, 600_000);
+    const deposit = (user: Keypair, source: PublicKey, stake: PublicKey, amount: number) => program.methods.deposit(new BN(amount), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user.publicKey,
+        source,
+      })
+      .remainingAccounts([{pubkey: stake, isSigner: false, isWritable: false}])
+      .signers([user]).rpc();
+
+    await expect(deposit(user1, ata1, stake2, 200_000)).to.be.rejectedWith(/InvalidStakeAccount/);
+    await expect(deposit(user1, ata1, stake1, 300_000)).to.be.rejectedWith(/StakeCapExceeded/);
+    await deposit(user1, ata1, stake1, 200_000);
+    await deposit(user2, ata2, stake2, 600_000);
+
+    const ccAcc = await program.account.capitalCall.fetch(keys.capitalCall);
+    expect(ccAcc.allocated.toNumber()).to.be.equal(800_000);
+  });
+
+  it("Should NOT mint LP tokens during finalize cooldown after capital call is filled", async () => {
+    const keys = await createCapitalCall(1, 100, 1_000_057);
+    await program.methods.updateCapitalCall({finalizeCooldown: new BN(4)})
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 1_000_057);
+    await program.methods.deposit(new BN(1_000_057), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+    expect((await program.account.capitalCall.fetch(keys.capitalCall)).filledAt.toNumber()).to.be.greaterThan(0);
+
+    const mintLpTokens = () => program.methods.mintLpTokens()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        liquidityPool: liquidityPool.publicKey,
+        lpMint: lpMint.publicKey,
+      }).rpc();
+
+    await expect(mintLpTokens()).to.be.rejectedWith(/FinalizeCooldown/);
+    await new Promise(resolve => setTimeout(resolve, 5000));
+    await mintLpTokens();
+    expect((await program.account.capitalCall.fetch(keys.capitalCall)).isLpMinted).to.be.true;
+  });
+
+  it("Should record filled at time once capital call is filled", async () => {
+    const keys = await createCapitalCall(1, 100, 1_000_058);
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    const ata2 = await getATA(user2.publicKey, mint.publicKey);
+    await mintTokens(ata1, 500_000);
+    await mintTokens(ata2, 500_058);
+    const deposit = (user: Keypair, source: PublicKey, amount: number) => program.methods.deposit(new BN(amount), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user.publicKey,
+        source,
+      }).signers([user]);
+
+    await deposit(user1, ata1, 500_000).rpc();
+    expect((await program.account.capitalCall.fetch(keys.capitalCall)).filledAt.toNumber()).to.be.equal(0);
+
+    const simulation = await deposit(user2, ata2, 500_058).simulate();
+    const event = simulation.events.find(e => e.name === "CapitalFullyRaisedEvent");
+    expect(event.data.filledAt.toNumber()).to.be.greaterThan(0);
+
+    const before = await currentBlockTime();
+    await deposit(user2, ata2, 500_058).rpc();
+    const filledAt = (await program.account.capitalCall.fetch(keys.capitalCall)).filledAt.toNumber();
+    expect(filledAt).to.be.at.least(before);
+    expect(filledAt).to.be.at.most(await currentBlockTime());
+  });
+
+  it("Should reduce deposit over remaining capacity only if clamp is allowed", async () => {
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 2_000_000);
+    const depositOverCapacity = async (capacity: number, allowClamp: boolean) => {
+      const keys = await createCapitalCall(1, 100, capacity);
+      await program.methods.updateCapitalCall({allowClamp})
+        .accounts({
+          config: config.publicKey,
+          capitalCall: keys.capitalCall,
+          authority: authority.publicKey,
+        }).signers([authority]).rpc();
+      await new Promise(resolve => setTimeout(resolve, 2000));
+
+      await program.methods.deposit(new BN(capacity + 1), null, NO_MEMO)
+        .accounts({
+          config: config.publicKey,
+          capitalCall: keys.capitalCall,
+          authority: user1.publicKey,
+          source: ata1,
+        }).signers([user1]).rpc();
+      return keys;
+    };
+
+    const keys = await depositOverCapacity(1_000_059, true);
+    expect((await program.account.capitalCall.fetch(keys.capitalCall)).allocated.toNumber()).to.be.equal(1_000_059);
+
+    await expect(depositOverCapacity(1_000_060, false)).to.be.rejectedWith(/ExceedsRemainingCapacity/);
+  });
+
+  it("Should mint LP tokens of custodial capital call only with custodian signature", async () => {
+    const custodian = Keypair.generate();
+    const keys = await createCapitalCall(1, 100, 1_000_061);
+    await program.methods.updateCapitalCall({custodialVault: true, custodian: custodian.publicKey})
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 1_000_061);
+    await program.methods.deposit(new BN(1_000_061), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+
+    const mintLpTokens = program.methods.mintLpTokens()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        liquidityPool: liquidityPool.publicKey,
+        lpMint: lpMint.publicKey,
+      });
+    await expect(mintLpTokens.rpc()).to.be.rejectedWith(/InvalidCustodian/);
+
+    await mintLpTokens
+      .remainingAccounts([{pubkey: custodian.publicKey, isSigner: true, isWritable: false}])
+      .signers([custodian])
+      .rpc();
+    expect((await program.account.capitalCall.fetch(keys.capitalCall)).isLpMinted).to.be.true;
+  });
+
+  it("Should mint LP tokens only with exchange rate within bounds", async () => {
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    const mintLpTokensWithRate = async (capacity: number, minRate: number, maxRate: number) => {
+      const keys = await createCapitalCall(1, 100, capacity);
+      await program.methods.updateCapitalCall({minRate: new BN(minRate), maxRate: new BN(maxRate)})
+        .accounts({
+          config: config.publicKey,
+          capitalCall: keys.capitalCall,
+          authority: authority.publicKey,
+        }).signers([authority]).rpc();
+      await new Promise(resolve => setTimeout(resolve, 2000));
+
+      await mintTokens(ata1, capacity);
+      await program.methods.deposit(new BN(capacity), null, NO_MEMO)
+        .accounts({
+          config: config.publicKey,
+          capitalCall: keys.capitalCall,
+          authority: user1.publicKey,
+          source: ata1,
+        }).signers([user1]).rpc();
+      await program.methods.mintLpTokens()
+        .accounts({
+          config: config.publicKey,
+          capitalCall: keys.capitalCall,
+          liquidityPool: liquidityPool.publicKey,
+          lpMint: lpMint.publicKey,
+        }).rpc();
+      return keys;
+    };
+
+    await expect(mintLpTokensWithRate(1_000_062, 0, 1)).to.be.rejectedWith(/RateOutOfBounds/);
+
+    const keys = await mintLpTokensWithRate(1_000_063, 1, 0);
+    expect((await program.account.capitalCall.fetch(keys.capitalCall)).isLpMinted).to.be.true;
+  });
+
+  it("Should recover voucher of closed capital call", async () => {
+    const keys = await createCapitalCall(1, 100, 1_000_064);
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 100_000);
+    await program.methods.deposit(new BN(100_000), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+
+    const [voucher1] = await PublicKey.findProgramAddress(
+      [anchor.utils.bytes.utf8.encode("voucher"), keys.capitalCall.toBuffer(), user1.publicKey.toBuffer()],
+      program.programId,
+    );
+    const ccAcc = await program.account.capitalCall.fetch(keys.capitalCall);
+    const recoverOrphanVoucher = (capacity: BN = ccAcc.capacity) => program.methods.recoverOrphanVoucher(ccAcc.startTime, capacity, ccAcc.idempotencyKey)
+      .accounts({
+        config: config.publicKey,
+        authority: authority.publicKey,
+        capitalCall: keys.capitalCall,
+        voucher: voucher1,
+        depositor: user1.publicKey,
+      }).signers([authority]).rpc();
+    await expect(recoverOrphanVoucher()).to.be.rejectedWith(/CapitalCallNotClosed/);
+
+    await program.methods.cancel()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await program.methods.refund()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        destination: ata1,
+      }).signers([user1]).rpc();
+    await program.methods.closeCancelled()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+        receiver: authority.publicKey,
+        destination: ata1,
+      }).signers([authority]).rpc();
+    expect(await program.account.voucher.fetchNullable(voucher1)).to.not.be.null;
+
+    // capital call has to be derived from config
+    await expect(recoverOrphanVoucher(ccAcc.capacity.addn(1))).to.be.rejectedWith(/ConstraintSeeds/);
+
+    const lamports = await provider.connection.getBalance(user1.publicKey);
+    await recoverOrphanVoucher();
+    expect(await program.account.voucher.fetchNullable(voucher1)).to.be.null;
+    expect(await provider.connection.getBalance(user1.publicKey)).to.be.greaterThan(lamports);
+  });
+
+  it("Should earn yield on raised tokens until LP tokens are minted", async () => {
+    const [marketAuthority] = await PublicKey.findProgramAddress(
+      [anchor.utils.bytes.utf8.encode("market")],
+      callbackMock.programId
+    );
+    const receiptMint = Keypair.generate();
+    await tokenProgram.methods.initializeMint(6, marketAuthority, null)
+      .accounts({
+        mint: receiptMint.publicKey,
+        rent: web3.SYSVAR_RENT_PUBKEY,
+      })
+      .preInstructions([await tokenProgram.account.mint.createInstruction(receiptMint)])
+      .signers([receiptMint]).rpc();
+    const createTokenAccount = async (tokenMint: PublicKey, owner: PublicKey) => {
+      const account = Keypair.generate();
+      await tokenProgram.methods.initializeAccount()
+        .accounts({
+          account: account.publicKey,
+          mint: tokenMint,
+          authority: owner,
+          rent: web3.SYSVAR_RENT_PUBKEY,
+        })
+        .signers([account])
+        .preInstructions([await tokenProgram.account.token.createInstruction(account)])
+        .rpc();
+      return account.publicKey;
+    };
+    const reserve = await createTokenAccount(mint.publicKey, marketAuthority);
+
+    await program.methods.updateConfig({yieldProgram: callbackMock.programId})
+      .accounts({config: config.publicKey, authority: authority.publicKey})
+      .signers([authority]).rpc();
+
+    const keys = await createCapitalCall(1, 100, 1_000_065);
+    await program.methods.updateCapitalCall({yieldStrategy: true})
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    const receipt = await createTokenAccount(receiptMint.publicKey, keys.capitalCall);
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const yieldAccounts = [
+      {pubkey: callbackMock.programId, isWritable: false, isSigner: false},
+      {pubkey: reserve, isWritable: true, isSigner: false},
+      {pubkey: receiptMint.publicKey, isWritable: true, isSigner: false},
+      {pubkey: receipt, isWritable: true, isSigner: false},
+      {pubkey: marketAuthority, isWritable: false, isSigner: false},
+    ];
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 1_000_065);
+    const deposit = program.methods.deposit(new BN(1_000_065), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]);
+    await expect(deposit.rpc()).to.be.rejectedWith(/InvalidYieldProgram/);
+    await deposit.remainingAccounts(yieldAccounts).rpc();
+
+    expect(await tokenBalance(keys.vault)).to.be.equal(0);
+    expect(await tokenBalance(reserve)).to.be.equal(1_000_065);
+    expect(await tokenBalance(receipt)).to.be.equal(1_000_065);
+    expect((await program.account.capitalCall.fetch(keys.capitalCall)).yieldPrincipal.toNumber())
+      .to.be.equal(1_000_065);
+
+    // accrue yield in money market
+    await mintTokens(reserve, 5_000);
+
+    const liquidityPoolBalance = await tokenBalance(liquidityPool.publicKey);
+    await program.methods.mintLpTokens()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        liquidityPool: liquidityPool.publicKey,
+        lpMint: lpMint.publicKey,
+      })
+      .remainingAccounts(yieldAccounts)
+      .rpc();
+
+    const capitalCallAccount = await program.account.capitalCall.fetch(keys.capitalCall);
+    expect(capitalCallAccount.isLpMinted).to.be.true;
+    expect(capitalCallAccount.yieldPrincipal.toNumber()).to.be.equal(0);
+    expect(capitalCallAccount.yieldEarned.toNumber()).to.be.equal(5_000);
+    expect(await tokenBalance(liquidityPool.publicKey)).to.be.equal(liquidityPoolBalance + 1_000_065);
+    expect(await tokenBalance(keys.vault)).to.be.equal(5_000);
+    expect(await tokenBalance(receipt)).to.be.equal(0);
+    expect(await tokenBalance(reserve)).to.be.equal(0);
+
+    await program.methods.updateConfig({yieldProgram: PublicKey.default})
+      .accounts({config: config.publicKey, authority: authority.publicKey})
+      .signers([authority]).rpc();
+  });
+
+  it("Should retain haircut of refunds of cancelled capital call in vault", async () => {
+    const refundWithHaircut = async (capacity: number, haircutBps: number) => {
+      const keys = await createCapitalCall(1, 100, capacity);
+      await new Promise(resolve => setTimeout(resolve, 2000));
+
+      const ata1 = await getATA(user1.publicKey, mint.publicKey);
+      await mintTokens(ata1, 100_000);
+      await program.methods.deposit(new BN(100_000), null, NO_MEMO)
+        .accounts({
+          config: config.publicKey,
+          capitalCall: keys.capitalCall,
+          authority: user1.publicKey,
+          source: ata1,
+        }).signers([user1]).rpc();
+
+      const setRefundHaircut = () => program.methods.setRefundHaircut(haircutBps)
+        .accounts({
+          config: config.publicKey,
+          capitalCall: keys.capitalCall,
+          authority: authority.publicKey,
+        }).signers([authority]).rpc();
+      await expect(setRefundHaircut()).to.be.rejectedWith(/CapitalCallNotCancelled/);
+
+      await program.methods.cancel()
+        .accounts({
+          config: config.publicKey,
+          capitalCall: keys.capitalCall,
+          authority: authority.publicKey,
+        }).signers([authority]).rpc();
+      await setRefundHaircut();
+
+      const balance = await tokenBalance(ata1);
+      await program.methods.refund()
+        .accounts({
+          config: config.publicKey,
+          capitalCall: keys.capitalCall,
+          authority: user1.publicKey,
+          destination: ata1,
+        }).signers([user1]).rpc();
+      await expect(setRefundHaircut()).to.be.rejectedWith(/CapitalCallTermsLocked/);
+
+      const capitalCallAccount = await program.account.capitalCall.fetch(keys.capitalCall);
+      return {
+        refunded: await tokenBalance(ata1) - balance,
+        retained: await tokenBalance(keys.vault),
+        shortfall: capitalCallAccount.refundShortfall.toNumber(),
+      };
+    };
+
+    expect(await refundWithHaircut(1_000_066, 0))
+      .to.be.deep.equal({refunded: 100_000, retained: 0, shortfall: 0});
+    expect(await refundWithHaircut(1_000_067, 2_000))
+      .to.be.deep.equal({refunded: 80_000, retained: 20_000, shortfall: 20_000});
+  });
+
+  it("Should NOT claim vested amount partially over max partial claims", async () => {
+    const keys = await createCapitalCall(1, 100, 1_000_068);
+    const vestStart = await currentBlockTime() - 500;
+    await program.methods.updateCapitalCall({vestStart: new BN(vestStart), vestCliff: new BN(0), vestDuration: new BN(100_000)})
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 1_000_068);
+    await program.methods.deposit(new BN(1_000_068), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+    await program.methods.mintLpTokens()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        liquidityPool: liquidityPool.publicKey,
+        lpMint: lpMint.publicKey,
+      }).rpc();
+
+    const updateMaxPartialClaims = (maxPartialClaims: number) => program.methods.updateConfig({maxPartialClaims})
+      .accounts({
+        config: config.publicKey,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    const lpAta1 = await getATA(user1.publicKey, lpMint.publicKey);
+    const claimVested = () => program.methods.claimVested()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        feeLpAccount,
+        authority: user1.publicKey,
+        destination: lpAta1,
+      }).signers([user1]).rpc();
+
+    await updateMaxPartialClaims(2);
+    for (let i = 0; i < 2; i++) {
+      await claimVested();
+      await new Promise(resolve => setTimeout(resolve, 2000));
+    }
+    await expect(claimVested()).to.be.rejectedWith(/TooManyPartialClaims/);
+
+    // final claim of fully vested voucher
+    const [voucher1] = await PublicKey.findProgramAddress(
+      [anchor.utils.bytes.utf8.encode("voucher"), keys.capitalCall.toBuffer(), user1.publicKey.toBuffer()],
+      program.programId,
+    );
+    await program.methods.setVestingOverride(new BN(vestStart), new BN(0), new BN(1))
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        voucher: voucher1,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await claimVested();
+    expect((await program.account.voucher.fetch(voucher1)).claimCount).to.be.equal(2);
+
+    await updateMaxPartialClaims(0);
+  });
+
+  it("Should cancel capital call and refund depositors to their associated token accounts", async () => {
+    const user3 = Keypair.generate();
+    await provider.connection.confirmTransaction(
+      await provider.connection.requestAirdrop(user3.publicKey, web3.LAMPORTS_PER_SOL)
+    );
+    const ata3 = await getATA(user3.publicKey, mint.publicKey);
+    await provider.sendAndConfirm(new web3.Transaction().add(
+      createAssociatedTokenAccountInstruction(provider.wallet.publicKey, ata3, user3.publicKey, mint.publicKey)
+    ));
+
+    const keys = await createCapitalCall(1, 100, 1_000_069);
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const depositors: [Keypair, PublicKey, number][] = [
+      [user1, await getATA(user1.publicKey, mint.publicKey), 100_000],
+      [user2, await getATA(user2.publicKey, mint.publicKey), 200_000],
+      [user3, ata3, 300_000],
+    ];
+    const remainingAccounts = [];
+    const balances = [];
+    for (const [user, ata, amount] of depositors) {
+      await mintTokens(ata, amount);
+      balances.push(await tokenBalance(ata));
+      await program.methods.deposit(new BN(amount), null, NO_MEMO)
+        .accounts({
+          config: config.publicKey,
+          capitalCall: keys.capitalCall,
+          authority: user.publicKey,
+          source: ata,
+        }).signers([user]).rpc();
+
+      const [voucher] = await PublicKey.findProgramAddress(
+        [anchor.utils.bytes.utf8.encode("voucher"), keys.capitalCall.toBuffer(), user.publicKey.toBuffer()],
+        program.programId,
+      );
+      remainingAccounts.push(
+        {pubkey: voucher, isWritable: true, isSigner: false},
+        {pubkey: ata, isWritable: true, isSigner: false},
+        {pubkey: user.publicKey, isWritable: true, isSigner: false},
+      );
+    }
+
+    const cancelAndRefund = program.methods.cancelAndRefund()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      })
+      .remainingAccounts(remainingAccounts)
+      .signers([authority]);
+    const simulation = await cancelAndRefund.simulate();
+    expect(simulation.events.filter(e => e.name === "RefundEvent").map(e => e.data.amount.toNumber()))
+      .to.be.deep.equal([100_000, 200_000, 300_000]);
+    await cancelAndRefund.rpc();
+
+    for (const [i, [_user, ata, amount]] of depositors.entries()) {
+      expect(await tokenBalance(ata)).to.be.equal(balances[i] + amount);
+      expect(await program.account.voucher.fetchNullable(remainingAccounts[i * 3].pubkey)).to.be.null;
+    }
+    const capitalCallAccount = await program.account.capitalCall.fetch(keys.capitalCall);
+    expect(capitalCallAccount.isCancelled).to.be.true;
+    expect(capitalCallAccount.redeemed.toNumber()).to.be.equal(600_000);
+    expect(await tokenBalance(keys.vault)).to.be.equal(0);
+  });
+
+  it("Should NOT cancel capital call before min open period unless it is frozen", async () => {
+    const updateMinOpenBeforeCancel = (minOpenBeforeCancel: number) => program.methods.updateConfig({minOpenBeforeCancel: new BN(minOpenBeforeCancel)})
+      .accounts({
+        config: config.publicKey,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    const cancel = (capitalCall: PublicKey) => program.methods.cancel()
+      .accounts({
+        config: config.publicKey,
+        capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+
+    await updateMinOpenBeforeCancel(4);
+    const keys = await createCapitalCall(1, 100, 1_000_070);
+    await expect(cancel(keys.capitalCall)).to.be.rejectedWith(/CancelTooEarly/);
+    await new Promise(resolve => setTimeout(resolve, 6000));
+    await cancel(keys.capitalCall);
+    expect((await program.account.capitalCall.fetch(keys.capitalCall)).isCancelled).to.be.true;
+
+    const frozenKeys = await createCapitalCall(1, 100, 1_000_071);
+    await program.methods.setCallFrozen(true)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: frozenKeys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await cancel(frozenKeys.capitalCall);
+    expect((await program.account.capitalCall.fetch(frozenKeys.capitalCall)).isCancelled).to.be.true;
+
+    await updateMinOpenBeforeCancel(0);
+  });
+
+  it("Should refund only to owner of deposit source if required", async () => {
+    const keys = await createCapitalCall(1, 3, 1_000_072);
+    await program.methods.updateCapitalCall({refundToSourceOwner: true})
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    const ata2 = await getATA(user2.publicKey, mint.publicKey);
+    await mintTokens(ata1, 100_000);
+    await program.methods.deposit(new BN(100_000), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+
+    const [voucher1] = await PublicKey.findProgramAddress(
+      [anchor.utils.bytes.utf8.encode("voucher"), keys.capitalCall.toBuffer(), user1.publicKey.toBuffer()],
+      program.programId,
+    );
+    expect((await program.account.voucher.fetch(voucher1)).sourceOwner).to.be.deep.equal(user1.publicKey);
+    await new Promise(resolve => setTimeout(resolve, 3000));
+
+    const refund = (destination: PublicKey) => program.methods.refund()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        destination,
+      }).signers([user1]).rpc();
+    await expect(refund(ata2)).to.be.rejectedWith(/RefundMustGoToSourceOwner/);
+
+    const balance = await tokenBalance(ata1);
+    await refund(ata1);
+    expect(await tokenBalance(ata1)).to.be.equal(balance + 100_000);
+  });
+
+  it("Should create capital calls in batch", async () => {
+    const blockTime = await currentBlockTime();
+    const params = [1_000_073, 1_000_074].map(capacity => ({
+      startTime: new BN(blockTime + 10),
+      duration: new BN(100),
+      capacity: new BN(capacity),
+      creditOutstanding: new BN(TSCreditOutstanding),
+      idempotencyKey: NO_IDEMPOTENCY_KEY,
+      manager: PublicKey.default,
+      refundLockup: new BN(0),
+    }));
+
+    const remainingAccounts = [];
+    const capitalCalls = [];
+    for (const p of params) {
+      const capitalCall = await findCapitalCall(blockTime + 10, p.capacity.toNumber(), NO_IDEMPOTENCY_KEY);
+      const [vault] = await PublicKey.findProgramAddress(
+        [anchor.utils.bytes.utf8.encode("vault"), capitalCall.toBuffer()],
+        program.programId
+      );
+      const [lpTokenPool] = await PublicKey.findProgramAddress(
+        [anchor.utils.bytes.utf8.encode("lp_token_pool"), capitalCall.toBuffer()],
+        program.programId
+      );
+      capitalCalls.push(capitalCall);
+      remainingAccounts.push(
+        {pubkey: capitalCall, isWritable: true, isSigner: false},
+        {pubkey: vault, isWritable: true, isSigner: false},
+        {pubkey: lpTokenPool, isWritable: true, isSigner: false},
+      );
+    }
+
+    const openCalls = (await program.account.config.fetch(config.publicKey)).openCalls;
+    const createBatch = program.methods.createCapitalCallsBatch(params)
+      .accounts({
+        config: config.publicKey,
+        mint: mint.publicKey,
+        lpMint: lpMint.publicKey,
+        authority: authority.publicKey,
+        payer: provider.wallet.publicKey,
+      })
+      .remainingAccounts(remainingAccounts)
+      .signers([authority]);
+    const simulation = await createBatch.simulate();
+    expect(simulation.events.filter(e => e.name === "CreateCapitalCallEvent").map(e => e.data.capitalCall))
+      .to.be.deep.equal(capitalCalls);
+    await createBatch.rpc();
+
+    for (const [i, capitalCall] of capitalCalls.entries()) {
+      const capitalCallAccount = await program.account.capitalCall.fetch(capitalCall);
+      expect(capitalCallAccount.config).to.be.deep.equal(config.publicKey);
+      expect(capitalCallAccount.capacity.toNumber()).to.be.equal(params[i].capacity.toNumber());
+      expect(capitalCallAccount.vault).to.be.deep.equal(remainingAccounts[i * 3 + 1].pubkey);
+      expect(await tokenBalance(capitalCallAccount.vault)).to.be.equal(0);
+      expect(await tokenBalance(capitalCallAccount.lpTokenPool)).to.be.equal(0);
+    }
+    expect((await program.account.config.fetch(config.publicKey)).openCalls).to.be.equal(openCalls + 2);
+  });
+
+  it("Should charge low fill fee if capital call filled fast", async () => {
+    const keys = await createCapitalCall(1, 3, 1_000_075);
+    await program.methods.updateConfig({feeLpAccount})
+      .accounts({
+        config: config.publicKey,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await program.methods.updateCapitalCall({
+      fastFillThreshold: new BN(1_000),
+      fastFillFeeBps: 100,
+      slowFillFeeBps: 1_000,
+    })
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 1_000_075);
+    await program.methods.deposit(new BN(1_000_075), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+
+    const mintLpTokens = program.methods.mintLpTokens()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        liquidityPool: liquidityPool.publicKey,
+        lpMint: lpMint.publicKey,
+      });
+    await expect(mintLpTokens.rpc()).to.be.rejectedWith(/InvalidFeeLpAccount/);
+
+    const feeBalance = await tokenBalance(feeLpAccount);
+    await mintLpTokens
+      .remainingAccounts([{pubkey: feeLpAccount, isWritable: true, isSigner: false}])
+      .rpc();
+
+    const ccAcc = await program.account.capitalCall.fetch(keys.capitalCall);
+    const minted = new BN(1_000_075).mul(ccAcc.tokenLiquidity.add(ccAcc.creditOutstanding)).div(ccAcc.lpSupply).toNumber();
+    const fee = Math.floor(minted * 100 / 10_000);
+    expect(ccAcc.fillFeeBps).to.be.equal(100);
+    expect(ccAcc.fillFeeLp.toNumber()).to.be.equal(fee);
+    expect(await tokenBalance(feeLpAccount)).to.be.equal(feeBalance + fee);
+    expect(await tokenBalance(ccAcc.lpTokenPool)).to.be.equal(minted - fee);
+
+    const lpAta1 = await getATA(user1.publicKey, lpMint.publicKey);
+    const balance = await tokenBalance(lpAta1);
+    await program.methods.claim()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        feeLpAccount,
+        authority: user1.publicKey,
+        destination: lpAta1,
+      }).signers([user1]).rpc();
+    expect(await tokenBalance(lpAta1)).to.be.equal(balance + Math.floor(minted * 9_900 / 10_000));
+  });
+
+  it("Should charge high fill fee if capital call filled slowly", async () => {
+    const keys = await createCapitalCall(1, 5, 1_000_076);
+    await program.methods.updateConfig({feeLpAccount})
+      .accounts({
+        config: config.publicKey,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await program.methods.updateCapitalCall({
+      fastFillThreshold: new BN(1),
+      fastFillFeeBps: 100,
+      slowFillFeeBps: 1_000,
+    })
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await new Promise(resolve => setTimeout(resolve, 4000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 1_000_076);
+    await program.methods.deposit(new BN(1_000_076), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+
+    const feeBalance = await tokenBalance(feeLpAccount);
+    const mintLpTokens = program.methods.mintLpTokens()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        liquidityPool: liquidityPool.publicKey,
+        lpMint: lpMint.publicKey,
+      })
+      .remainingAccounts([{pubkey: feeLpAccount, isWritable: true, isSigner: false}]);
+    const simulation = await mintLpTokens.simulate();
+    const [event] = simulation.events.filter(e => e.name === "LpTokensMintedEvent");
+    await mintLpTokens.rpc();
+
+    const ccAcc = await program.account.capitalCall.fetch(keys.capitalCall);
+    const minted = new BN(1_000_076).mul(ccAcc.tokenLiquidity.add(ccAcc.creditOutstanding)).div(ccAcc.lpSupply).toNumber();
+    const fee = Math.floor(minted * 1_000 / 10_000);
+    expect(event.data.fillFee.toNumber()).to.be.equal(fee);
+    expect(ccAcc.fillFeeBps).to.be.equal(1_000);
+    expect(await tokenBalance(feeLpAccount)).to.be.equal(feeBalance + fee);
+    expect(await tokenBalance(ccAcc.lpTokenPool)).to.be.equal(minted - fee);
+  });
+
+  it("Should block all capital calls of config while killed", async () => {
+    const keys = await createCapitalCall(1, 3, 1_000_077);
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 200_000);
+    const deposit = () => program.methods.deposit(new BN(100_000), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+    await deposit();
+
+    const setKilled = (killed: boolean, signer: Keypair = authority) => program.methods.setKilled(killed)
+      .accounts({
+        config: config.publicKey,
+        authority: signer.publicKey,
+      }).signers([signer]).rpc();
+    await expect(setKilled(true, user1)).to.be.rejectedWith(/InvalidAuthority/);
+    await setKilled(true);
+    expect((await program.account.config.fetch(config.publicKey)).killed).to.be.true;
+
+    await expect(deposit()).to.be.rejectedWith(/ProgramKilled/);
+    await expect(program.methods.refund()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        destination: ata1,
+      }).signers([user1]).rpc()).to.be.rejectedWith(/ProgramKilled/);
+    await expect(program.methods.claim()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        feeLpAccount,
+        authority: user1.publicKey,
+        destination: await getATA(user1.publicKey, lpMint.publicKey),
+      }).signers([user1]).rpc()).to.be.rejectedWith(/ProgramKilled/);
+    await expect(program.methods.mintLpTokens()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        liquidityPool: liquidityPool.publicKey,
+        lpMint: lpMint.publicKey,
+      }).rpc()).to.be.rejectedWith(/ProgramKilled/);
+    await expect(program.methods.close(new BN(0))
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+        receiver: provider.wallet.publicKey,
+        lpMint: lpMint.publicKey,
+        destination: await getATA(provider.wallet.publicKey, mint.publicKey),
+      }).signers([authority]).rpc()).to.be.rejectedWith(/ProgramKilled/);
+    await expect(createCapitalCall(10, 3, 1_000_078)).to.be.rejectedWith(/ProgramKilled/);
+
+    // unkilling restores normal operation
+    await setKilled(false);
+    await deposit();
+    expect((await program.account.capitalCall.fetch(keys.capitalCall)).allocated.toNumber()).to.be.equal(200_000);
+  });
+
+  it("Should assign sequential ids to created capital calls", async () => {
+    const nextCallId = (await program.account.config.fetch(config.publicKey)).nextCallId.toNumber();
+
+    const callIds = [];
+    for (const capacity of [1_000_079, 1_000_080, 1_000_081]) {
+      const keys = await createCapitalCall(10, 3, capacity);
+      callIds.push((await program.account.capitalCall.fetch(keys.capitalCall)).callId.toNumber());
+    }
+
+    expect(callIds).to.be.deep.equal([nextCallId, nextCallId + 1, nextCallId + 2]);
+    expect((await program.account.config.fetch(config.publicKey)).nextCallId.toNumber()).to.be.equal(nextCallId + 3);
+  });
+
+  it("Should reject claim to destination of other mint than LP mint", async () => {
+    const keys = await createCapitalCall(1, 3, 1_000_082);
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 1_000_082);
+    await program.methods.deposit(new BN(1_000_082), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+    await program.methods.mintLpTokens()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        liquidityPool: liquidityPool.publicKey,
+        lpMint: lpMint.publicKey,
+      }).rpc();
+
+    const claim = (destination: PublicKey) => program.methods.claim()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        feeLpAccount,
+        authority: user1.publicKey,
+        destination,
+      }).signers([user1]).rpc();
+    await expect(claim(ata1)).to.be.rejectedWith(/InvalidDestinationMint/);
+
+    const lpAta1 = await getATA(user1.publicKey, lpMint.publicKey);
+    const balance = await tokenBalance(lpAta1);
+    await claim(lpAta1);
+    expect(await tokenBalance(lpAta1)).to.be.greaterThan(balance);
+  });
+
+  it("Should mint LP tokens within max valuation age of valuation", async () => {
+    const keys = await createCapitalCall(1, 3, 1_000_083);
+    await program.methods.updateCapitalCall({maxValuationAge: new BN(100)})
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 1_000_083);
+    await program.methods.deposit(new BN(1_000_083), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+
+    const mintLpTokens = () => program.methods.mintLpTokens()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        liquidityPool: liquidityPool.publicKey,
+        lpMint: lpMint.publicKey,
+      }).rpc();
+    const updateValuation = (tokenLiquidity: number) => program.methods.updateValuation(new BN(tokenLiquidity))
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+
+    // valuation isn't stamped yet
+    await expect(mintLpTokens()).to.be.rejectedWith(/ValuationStale/);
+
+    const tokenLiquidity = await tokenBalance(liquidityPool.publicKey);
+    await updateValuation(tokenLiquidity + 1);
+    await expect(mintLpTokens()).to.be.rejectedWith(/ValuationMismatch/);
+
+    await updateValuation(tokenLiquidity);
+    await mintLpTokens();
+    const ccAcc = await program.account.capitalCall.fetch(keys.capitalCall);
+    expect(ccAcc.isLpMinted).to.be.true;
+    expect(ccAcc.tokenLiquidity.toNumber()).to.be.equal(tokenLiquidity);
+  });
+
+  it("Should NOT mint LP tokens beyond max valuation age of valuation", async () => {
+    const keys = await createCapitalCall(1, 3, 1_000_084);
+    await program.methods.updateCapitalCall({maxValuationAge: new BN(2)})
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 1_000_084);
+    await program.methods.deposit(new BN(1_000_084), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+
+    const mintLpTokens = () => program.methods.mintLpTokens()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        liquidityPool: liquidityPool.publicKey,
+        lpMint: lpMint.publicKey,
+      }).rpc();
+    const updateValuation = () => tokenBalance(liquidityPool.publicKey)
+      .then(tokenLiquidity => program.methods.updateValuation(new BN(tokenLiquidity))
+        .accounts({
+          config: config.publicKey,
+          capitalCall: keys.capitalCall,
+          authority: authority.publicKey,
+        }).signers([authority]).rpc());
+
+    await updateValuation();
+    await new Promise(resolve => setTimeout(resolve, 4000));
+    await expect(mintLpTokens()).to.be.rejectedWith(/ValuationStale/);
+    expect((await program.account.capitalCall.fetch(keys.capitalCall)).isLpMinted).to.be.false;
+
+    // fresh valuation
+    await updateValuation();
+    await mintLpTokens();
+    expect((await program.account.capitalCall.fetch(keys.capitalCall)).isLpMinted).to.be.true;
+  });
+
+  it("Should create capital call only with duration spanning min window slots", async () => {
+    const updateMinWindowSlots = (minWindowSlots: number) => program.methods.updateConfig({minWindowSlots: new BN(minWindowSlots)})
+      .accounts({
+        config: config.publicKey,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    // 25 slots of 400ms are 10 seconds
+    await updateMinWindowSlots(25);
+
+    await expect(createCapitalCall(10, 9, 1_000_085)).to.be.rejectedWith(/WindowTooShort/);
+    const keys = await createCapitalCall(10, 10, 1_000_086);
+    expect((await program.account.capitalCall.fetch(keys.capitalCall)).capacity.toNumber()).to.be.equal(1_000_086);
+
+    await updateMinWindowSlots(0);
+  });
+
+  it("Should escrow claimed LP tokens until lockup elapses", async () => {
+    const keys = await createCapitalCall(1, 3, 1_000_087);
+    await program.methods.updateCapitalCall({lpLockup: new BN(4)})
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 1_000_087);
+    await program.methods.deposit(new BN(1_000_087), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+    await program.methods.mintLpTokens()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        liquidityPool: liquidityPool.publicKey,
+        lpMint: lpMint.publicKey,
+      }).rpc();
+
+    const [lpLockup] = await PublicKey.findProgramAddress(
+      [anchor.utils.bytes.utf8.encode("lp_lockup"), keys.capitalCall.toBuffer(), user1.publicKey.toBuffer()],
+      program.programId,
+    );
+    const [escrow] = await PublicKey.findProgramAddress(
+      [anchor.utils.bytes.utf8.encode("lp_escrow"), keys.capitalCall.toBuffer(), user1.publicKey.toBuffer()],
+      program.programId,
+    );
+    await program.methods.initLpLockup()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        lpLockup,
+        escrow,
+        lpMint: lpMint.publicKey,
+        authority: user1.publicKey,
+      }).signers([user1]).rpc();
+
+    const lpAta1 = await getATA(user1.publicKey, lpMint.publicKey);
+    const claim = (destination: PublicKey) => program.methods.claim()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        feeLpAccount,
+        authority: user1.publicKey,
+        destination,
+      })
+      .remainingAccounts([{pubkey: lpLockup, isWritable: true, isSigner: false}])
+      .signers([user1]).rpc();
+    // LP tokens can't be claimed around escrow
+    await expect(claim(lpAta1)).to.be.rejectedWith(/InvalidLpLockup/);
+    await claim(escrow);
+
+    const ccAcc = await program.account.capitalCall.fetch(keys.capitalCall);
+    const lpAmount = new BN(1_000_087).mul(ccAcc.tokenLiquidity.add(ccAcc.creditOutstanding)).div(ccAcc.lpSupply).toNumber();
+    expect(await tokenBalance(escrow)).to.be.equal(lpAmount);
+    expect(ccAcc.lpLocked.toNumber()).to.be.equal(lpAmount);
+    expect((await program.account.lpLockup.fetch(lpLockup)).amount.toNumber()).to.be.equal(lpAmount);
+
+    const releaseLp = () => program.methods.releaseLp()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        lpLockup,
+        escrow,
+        authority: user1.publicKey,
+        destination: lpAta1,
+      }).signers([user1]).rpc();
+    await expect(releaseLp()).to.be.rejectedWith(/LpLockupNotElapsed/);
+    await new Promise(resolve => setTimeout(resolve, 5000));
+
+    const balance = await tokenBalance(lpAta1);
+    await releaseLp();
+    expect(await tokenBalance(lpAta1)).to.be.equal(balance + lpAmount);
+    expect((await program.account.capitalCall.fetch(keys.capitalCall)).lpLocked.toNumber()).to.be.equal(0);
+    expect(await provider.connection.getAccountInfo(lpLockup)).to.be.null;
+    expect(await provider.connection.getAccountInfo(escrow)).to.be.null;
+  });
+
+  it("Should create capital call only with capacity within config bounds", async () => {
+    const updateCapacityBounds = (minCapacity: number, maxCapacity: number) => program.methods.updateConfig({
+      minCapacity: new BN(minCapacity),
+      maxCapacity: new BN(maxCapacity),
+    })
+      .accounts({
+        config: config.publicKey,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await updateCapacityBounds(1_000_089, 1_000_090);
+
+    await expect(createCapitalCall(10, 3, 1_000_088)).to.be.rejectedWith(/CapacityOutOfRange/);
+    await expect(createCapitalCall(10, 3, 1_000_091)).to.be.rejectedWith(/CapacityOutOfRange/);
+    await createCapitalCall(10, 3, 1_000_089);
+    await createCapitalCall(10, 3, 1_000_090);
+
+    await updateCapacityBounds(0, 0);
+  });
+
+  it("Should store tier of depositor by tier oracle on voucher", async () => {
+    const keys = await createCapitalCall(1, 100, 1_000_092);
+    const updateTierOracle = (tierOracle: PublicKey) => program.methods.updateConfig({tierOracle})
+      .accounts({
+        config: config.publicKey,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await updateTierOracle(callbackMock.programId);
+
+    const [tierRecord] = await PublicKey.findProgramAddress(
+      [anchor.utils.bytes.utf8.encode("tier"), user1.publicKey.toBuffer()],
+      callbackMock.programId,
+    );
+    await callbackMock.methods.setTier(user1.publicKey, 3)
+      .accounts({
+        tierRecord,
+        payer: provider.wallet.publicKey,
+      }).rpc();
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const deposit = (user: Keypair) => getATA(user.publicKey, mint.publicKey)
+      .then(async source => {
+        await mintTokens(source, 100_000);
+        return program.methods.deposit(new BN(100_000), null, NO_MEMO)
+          .accounts({
+            config: config.publicKey,
+            capitalCall: keys.capitalCall,
+            authority: user.publicKey,
+            source,
+          })
+          .remainingAccounts([{pubkey: tierRecord, isWritable: false, isSigner: false}])
+          .signers([user]).rpc();
+      });
+
+    // record of other wallet
+    await expect(deposit(user2)).to.be.rejectedWith(/InvalidTierRecord/);
+
+    await deposit(user1);
+    const [voucher] = await PublicKey.findProgramAddress(
+      [anchor.utils.bytes.utf8.encode("voucher"), keys.capitalCall.toBuffer(), user1.publicKey.toBuffer()],
+      program.programId,
+    );
+    expect((await program.account.voucher.fetch(voucher)).tier).to.be.equal(3);
+
+    await updateTierOracle(PublicKey.default);
+  });
+
+  it("Should return rent of closed capital call to its payer if required", async () => {
+    const updateRentToPayer = (rentToPayer: boolean) => program.methods.updateConfig({rentToPayer})
+      .accounts({
+        config: config.publicKey,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await updateRentToPayer(true);
+
+    const keys = await createCapitalCall(1, 100, 1_000_093);
+    expect((await program.account.capitalCall.fetch(keys.capitalCall)).payer).to.be.deep.equal(provider.wallet.publicKey);
+
+    const ata = await getATA(provider.wallet.publicKey, mint.publicKey);
+    const close = (receiver: PublicKey) => program.methods.close(new BN(0))
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+        receiver,
+        lpMint: lpMint.publicKey,
+        destination: ata,
+      }).signers([authority]).rpc();
+    await expect(close(authority.publicKey)).to.be.rejectedWith(/ReceiverNotPayer/);
+
+    await close(provider.wallet.publicKey);
+    expect(await program.account.capitalCall.fetchNullable(keys.capitalCall)).to.be.null;
+
+    await updateRentToPayer(false);
+  });
+
+  it("Should claim LP tokens unlocked at discrete unlock points", async () => {
+    const keys = await createCapitalCall(1, 3, 1_000_094);
+    const blockTime = await currentBlockTime();
+    await program.methods.updateCapitalCall({
+      unlockPoints: [
+        {timestamp: new BN(blockTime + 8), bps: 4_000},
+        {timestamp: new BN(blockTime + 14), bps: 6_000},
+      ],
+    })
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 1_000_094);
+    await program.methods.deposit(new BN(1_000_094), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+    await program.methods.mintLpTokens()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        liquidityPool: liquidityPool.publicKey,
+        lpMint: lpMint.publicKey,
+      }).rpc();
+
+    const ccAcc = await program.account.capitalCall.fetch(keys.capitalCall);
+    const lpAmount = new BN(1_000_094).mul(ccAcc.tokenLiquidity.add(ccAcc.creditOutstanding)).div(ccAcc.lpSupply).toNumber();
+    const lpAta1 = await getATA(user1.publicKey, lpMint.publicKey);
+    const claimAccounts = {
+      config: config.publicKey,
+      capitalCall: keys.capitalCall,
+      feeLpAccount,
+      authority: user1.publicKey,
+      destination: lpAta1,
+    };
+    const claimVested = () => program.methods.claimVested().accounts(claimAccounts).signers([user1]).rpc();
+    const claim = () => program.methods.claim().accounts(claimAccounts).signers([user1]).rpc();
+    const balance = await tokenBalance(lpAta1);
+
+    // nothing is unlocked before the first point
+    await expect(claimVested()).to.be.rejectedWith(/AmountNonZero/);
+
+    while (await currentBlockTime() < blockTime + 8) {
+      await new Promise(resolve => setTimeout(resolve, 500));
+    }
+    await claimVested();
+    const unlocked = Math.floor(lpAmount * 4_000 / 10_000);
+    expect(await tokenBalance(lpAta1)).to.be.equal(balance + unlocked);
+    await expect(claim()).to.be.rejectedWith(/VestingNotComplete/);
+
+    while (await currentBlockTime() < blockTime + 14) {
+      await new Promise(resolve => setTimeout(resolve, 500));
+    }
+    await claim();
+    expect(await tokenBalance(lpAta1)).to.be.equal(balance + lpAmount);
+  });
+
+  it("Should NOT deposit from source with active delegate if rejected by config", async () => {
+    const updateRejectDelegatedSource = (rejectDelegatedSource: boolean) => program.methods.updateConfig({rejectDelegatedSource})
+      .accounts({
+        config: config.publicKey,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await updateRejectDelegatedSource(true);
+
+    const keys = await createCapitalCall(1, 100, 1_000_095);
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 1_000);
+    await tokenProgram.methods.approve(new BN(1_000))
+      .accounts({
+        source: ata1,
+        delegate: Keypair.generate().publicKey,
+        authority: user1.publicKey,
+      }).signers([user1]).rpc();
+
+    const deposit = () => program.methods.deposit(new BN(1_000), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+    await expect(deposit()).to.be.rejectedWith(/SourceAccountDelegated/);
+
+    await tokenProgram.methods.revoke()
+      .accounts({
+        source: ata1,
+        authority: user1.publicKey,
+      }).signers([user1]).rpc();
+    await deposit();
+    expect((await program.account.capitalCall.fetch(keys.capitalCall)).allocated.toNumber()).to.be.equal(1_000);
+
+    await updateRejectDelegatedSource(false);
+  });
+
+  it("Should finalize capital call in chunks", async () => {
+    const keys = await createCapitalCall(1, 100, 1_000_096);
+    await program.methods.updateCapitalCall({finalizeChunk: new BN(600_000)})
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 1_000_096);
+    await program.methods.deposit(new BN(1_000_096), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+
+    const finalizeAccounts = {
+      config: config.publicKey,
+      capitalCall: keys.capitalCall,
+      lpMint: lpMint.publicKey,
+    };
+    await expect(program.methods.mintLpTokens().accounts(finalizeAccounts).rpc())
+      .to.be.rejectedWith(/ChunkedFinalizeRequired/);
+
+    const lpAta1 = await getATA(user1.publicKey, lpMint.publicKey);
+    const claim = () => program.methods.claim()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        feeLpAccount,
+        authority: user1.publicKey,
+        destination: lpAta1,
+      }).signers([user1]).rpc();
+    const poolBefore = await tokenBalance(liquidityPool.publicKey);
+
+    const first = program.methods.finalizeChunked().accounts(finalizeAccounts);
+    const simulation = await first.simulate();
+    const event = simulation.events.find(e => e.name === "FinalizeProgressEvent");
+    expect(event.data.amount.toNumber()).to.be.equal(600_000);
+    expect(event.data.finalizeCursor.toNumber()).to.be.equal(600_000);
+    await first.rpc();
+
+    let ccAcc = await program.account.capitalCall.fetch(keys.capitalCall);
+    expect(ccAcc.isLpMinted).to.be.true;
+    expect(ccAcc.finalizeCursor.toNumber()).to.be.equal(600_000);
+    expect(await tokenBalance(liquidityPool.publicKey)).to.be.equal(poolBefore + 600_000);
+    await expect(claim()).to.be.rejectedWith(/FinalizationInProgress/);
+
+    await program.methods.finalizeChunked().accounts(finalizeAccounts).rpc();
+    ccAcc = await program.account.capitalCall.fetch(keys.capitalCall);
+    expect(ccAcc.finalizeCursor.toNumber()).to.be.equal(1_000_096);
+    expect(await tokenBalance(liquidityPool.publicKey)).to.be.equal(poolBefore + 1_000_096);
+    await expect(program.methods.finalizeChunked().accounts(finalizeAccounts).rpc())
+      .to.be.rejectedWith(/FinalizeAlreadyComplete/);
+
+    const balance = await tokenBalance(lpAta1);
+    await claim();
+    expect(await tokenBalance(lpAta1)).to.be.greaterThan(balance);
+  });
+
+  it("Should NOT refund partially claimed voucher", async () => {
+    const keys = await createCapitalCall(1, 100, 1_000_097);
+    const vestStart = await currentBlockTime() - 500;
+    await program.methods.updateCapitalCall({vestStart: new BN(vestStart), vestCliff: new BN(0), vestDuration: new BN(100_000)})
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 1_000_097);
+    await program.methods.deposit(new BN(1_000_097), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+    await program.methods.mintLpTokens()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        liquidityPool: liquidityPool.publicKey,
+        lpMint: lpMint.publicKey,
+      }).rpc();
+
+    const lpAta1 = await getATA(user1.publicKey, lpMint.publicKey);
+    await program.methods.claimVested()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        feeLpAccount,
+        authority: user1.publicKey,
+        destination: lpAta1,
+      }).signers([user1]).rpc();
+
+    const [voucher1] = await PublicKey.findProgramAddress(
+      [anchor.utils.bytes.utf8.encode("voucher"), keys.capitalCall.toBuffer(), user1.publicKey.toBuffer()],
+      program.programId,
+    );
+    expect((await program.account.voucher.fetch(voucher1)).settledVia).to.be.deep.equal({claim: {}});
+
+    await expect(program.methods.refund()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        destination: ata1,
+      }).signers([user1]).rpc()
+    ).to.be.rejectedWith(/AlreadySettledOtherwise/);
+  });
+
+  it("Should record LP tokens minted and distributed by claims of capital call", async () => {
+    const keys = await createCapitalCall(1, 100, 1_000_098);
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const deposit = async (user: Keypair, amount: number) => {
+      const ata = await getATA(user.publicKey, mint.publicKey);
+      await mintTokens(ata, amount);
+      await program.methods.deposit(new BN(amount), null, NO_MEMO)
+        .accounts({
+          config: config.publicKey,
+          capitalCall: keys.capitalCall,
+          authority: user.publicKey,
+          source: ata,
+        }).signers([user]).rpc();
+    };
+    await deposit(user1, 600_000);
+    await deposit(user2, 400_098);
+    await program.methods.mintLpTokens()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        liquidityPool: liquidityPool.publicKey,
+        lpMint: lpMint.publicKey,
+      }).rpc();
+    const lpMinted = (await program.account.capitalCall.fetch(keys.capitalCall)).lpMinted.toNumber();
+
+    let distributed = 0;
+    for (const user of [user1, user2]) {
+      const claim = program.methods.claim()
+        .accounts({
+          config: config.publicKey,
+          capitalCall: keys.capitalCall,
+          feeLpAccount,
+          authority: user.publicKey,
+          destination: await getATA(user.publicKey, lpMint.publicKey),
+        }).signers([user]);
+      const simulation = await claim.simulate();
+      const event = simulation.events.find(e => e.name === "ClaimEvent");
+      distributed += event.data.lpAmount.toNumber() + event.data.claimFee.toNumber();
+      expect(event.data.lpMinted.toNumber()).to.be.equal(lpMinted);
+      expect(event.data.lpDistributed.toNumber()).to.be.equal(distributed);
+      await claim.rpc();
+    }
+
+    const ccAcc = await program.account.capitalCall.fetch(keys.capitalCall);
+    expect(ccAcc.lpDistributed.toNumber()).to.be.equal(distributed);
+    expect(ccAcc.lpDistributed.toNumber()).to.be.at.most(ccAcc.lpMinted.toNumber());
+  });
+
+  it("Should mint frozen participation badge to depositor once", async () => {
+    const [lpMintAuthority] = await PublicKey.findProgramAddress(
+      [anchor.utils.bytes.utf8.encode("lp_mint_authority"), config.publicKey.toBuffer()],
+      program.programId
+    );
+    const participationMint = Keypair.generate();
+    await tokenProgram.methods.initializeMint(0, lpMintAuthority, lpMintAuthority)
+      .accounts({
+        mint: participationMint.publicKey,
+        rent: web3.SYSVAR_RENT_PUBKEY,
+      })
+      .preInstructions([await tokenProgram.account.mint.createInstruction(participationMint)])
+      .signers([participationMint]).rpc();
+    const badge = await getATA(user1.publicKey, participationMint.publicKey);
+    const badge2 = await getATA(user2.publicKey, participationMint.publicKey);
+    await provider.sendAndConfirm(
+      new web3.Transaction().add(
+        createAssociatedTokenAccountInstruction(
+          provider.wallet.publicKey,
+          badge,
+          user1.publicKey,
+          participationMint.publicKey),
+        createAssociatedTokenAccountInstruction(
+          provider.wallet.publicKey,
+          badge2,
+          user2.publicKey,
+          participationMint.publicKey),
+      ));
+
+    const updateParticipationMint = (participationMint: PublicKey) => program.methods.updateConfig({participationMint})
+      .accounts({
+        config: config.publicKey,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await updateParticipationMint(participationMint.publicKey);
+
+    const keys = await createCapitalCall(1, 100, 1_000_099);
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 1_000);
+    const deposit = () => program.methods.deposit(new BN(1_000), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      })
+      .remainingAccounts([
+        {pubkey: participationMint.publicKey, isWritable: true, isSigner: false},
+        {pubkey: badge, isWritable: true, isSigner: false},
+        {pubkey: lpMintAuthority, isWritable: false, isSigner: false},
+      ])
+      .signers([user1]).rpc();
+    await deposit();
+
+    const badgeAcc = await tokenProgram.account.token.fetch(badge);
+    expect(badgeAcc.amount.toNumber()).to.be.equal(1);
+    expect(badgeAcc.state).to.be.deep.equal({frozen: {}});
+
+    // badge can't be transferred
+    await expect(tokenProgram.methods.transfer(new BN(1))
+      .accounts({
+        source: badge,
+        destination: badge2,
+        authority: user1.publicKey,
+      }).signers([user1]).rpc()
+    ).to.be.rejectedWith(/0x11/);
+
+    // depositor holding badge gets no other one after withdraw and deposit again
+    await program.methods.withdraw()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        destination: ata1,
+      }).signers([user1]).rpc();
+    await deposit();
+    expect(await tokenBalance(badge)).to.be.equal(1);
+
+    await updateParticipationMint(PublicKey.default);
+  });
+
+  it("Should sweep vault surplus to treasury when LP tokens are minted", async () => {
+    const treasury = await getATA(provider.wallet.publicKey, mint.publicKey);
+    const updateSurplusTreasury = (surplusTreasury: PublicKey) => program.methods.updateConfig({surplusTreasury})
+      .accounts({
+        config: config.publicKey,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await updateSurplusTreasury(treasury);
+
+    const keys = await createCapitalCall(1, 100, 1_000_100);
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 1_000_100);
+    await program.methods.deposit(new BN(1_000_100), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+
+    // surplus injected directly to vault
+    await mintTokens(keys.vault, 500);
+
+    // only mint_lp_tokens sweeps surplus
+    await expect(program.methods.finalizeSplit([10_000])
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        lpMint: lpMint.publicKey,
+        authority: authority.publicKey,
+      })
+      .remainingAccounts([{pubkey: liquidityPool.publicKey, isWritable: true, isSigner: false}])
+      .signers([authority]).rpc()).to.be.rejectedWith(/SurplusSweepUnsupported/);
+
+    const treasuryBefore = await tokenBalance(treasury);
+    const poolBefore = await tokenBalance(liquidityPool.publicKey);
+    const mintLpTokens = program.methods.mintLpTokens()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        liquidityPool: liquidityPool.publicKey,
+        lpMint: lpMint.publicKey,
+      })
+      .remainingAccounts([{pubkey: treasury, isWritable: true, isSigner: false}]);
+    const simulation = await mintLpTokens.simulate();
+    const event = simulation.events.find(e => e.name === "SurplusSweptEvent");
+    expect(event.data.amount.toNumber()).to.be.equal(500);
+    await mintLpTokens.rpc();
+
+    expect(await tokenBalance(treasury)).to.be.equal(treasuryBefore + 500);
+    expect(await tokenBalance(liquidityPool.publicKey)).to.be.equal(poolBefore + 1_000_100);
+    expect(await tokenBalance(keys.vault)).to.be.equal(0);
+
+    await updateSurplusTreasury(PublicKey.default);
+  });
+
+  it("Should auto extend only capital call filled over config minimum", async () => {
+    const updateMinFillToExtendBps = (minFillToExtendBps: number) => program.methods.updateConfig({minFillToExtendBps})
+      .accounts({
+        config: config.publicKey,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await updateMinFillToExtendBps(5_000);
+
+    const filled = await createCapitalCall(1, 4, 1_000_101);
+    const sparse = await createCapitalCall(1, 4, 1_000_102);
+    for (const keys of [filled, sparse]) {
+      await program.methods.updateCapitalCall({autoExtendThresholdBps: 100, autoExtendDuration: new BN(100)})
+        .accounts({
+          config: config.publicKey,
+          capitalCall: keys.capitalCall,
+          authority: authority.publicKey,
+        }).signers([authority]).rpc();
+    }
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 700_000);
+    const deposit = (keys, amount: number) => program.methods.deposit(new BN(amount), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+    await deposit(filled, 600_000);
+    await deposit(sparse, 100_000);
+    await new Promise(resolve => setTimeout(resolve, 4000));
+
+    const tryAutoExtend = (keys) => program.methods.tryAutoExtend()
+      .accounts({config: config.publicKey, capitalCall: keys.capitalCall})
+      .rpc();
+    await tryAutoExtend(filled);
+    expect((await program.account.capitalCall.fetch(filled.capitalCall)).autoExtended).to.be.true;
+    await expect(tryAutoExtend(sparse)).to.be.rejectedWith(/InsufficientFillToExtend/);
+    expect((await program.account.capitalCall.fetch(sparse.capitalCall)).autoExtended).to.be.false;
+
+    await updateMinFillToExtendBps(0);
+  });
+
+  it("Should claim only to destination owned by depositor if strict", async () => {
+    const updateStrictClaimDestination = (strictClaimDestination: boolean) => program.methods.updateConfig({strictClaimDestination})
+      .accounts({
+        config: config.publicKey,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+
+    const strict = await createCapitalCall(1, 100, 1_000_103);
+    const lax = await createCapitalCall(1, 100, 1_000_104);
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    const depositAndMint = async (keys, amount: number) => {
+      await mintTokens(ata1, amount);
+      await program.methods.deposit(new BN(amount), null, NO_MEMO)
+        .accounts({
+          config: config.publicKey,
+          capitalCall: keys.capitalCall,
+          authority: user1.publicKey,
+          source: ata1,
+        }).signers([user1]).rpc();
+      await program.methods.mintLpTokens()
+        .accounts({
+          config: config.publicKey,
+          capitalCall: keys.capitalCall,
+          liquidityPool: liquidityPool.publicKey,
+          lpMint: lpMint.publicKey,
+        }).rpc();
+    };
+    await depositAndMint(strict, 1_000_103);
+    await depositAndMint(lax, 1_000_104);
+
+    const lpAta1 = await getATA(user1.publicKey, lpMint.publicKey);
+    const lpAta2 = await getATA(user2.publicKey, lpMint.publicKey);
+    const claim = (keys, destination: PublicKey) => program.methods.claim()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        feeLpAccount,
+        authority: user1.publicKey,
+        destination,
+      }).signers([user1]).rpc();
+
+    await updateStrictClaimDestination(true);
+    await expect(claim(strict, lpAta2)).to.be.rejectedWith(/InvalidDestination/);
+    const balance1 = await tokenBalance(lpAta1);
+    await claim(strict, lpAta1);
+    expect(await tokenBalance(lpAta1)).to.be.greaterThan(balance1);
+
+    await updateStrictClaimDestination(false);
+    const balance2 = await tokenBalance(lpAta2);
+    await claim(lax, lpAta2);
+    expect(await tokenBalance(lpAta2)).to.be.greaterThan(balance2);
+  });
+
+  it("Should reject every capital call instruction while killed", async () => {
+    const keys = await createCapitalCall(1, 3, 1_000_105);
+    await program.methods.updateCapitalCall({lpLockup: new BN(4)})
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await program.methods.registerReferral()
+      .accounts({config: config.publicKey, capitalCall: keys.capitalCall, referrer: user2.publicKey})
+      .signers([user2]).rpc();
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 100_000);
+    await program.methods.deposit(new BN(100_000), user2.publicKey, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+
+    const [voucher] = await PublicKey.findProgramAddress(
+      [anchor.utils.bytes.utf8.encode("voucher"), keys.capitalCall.toBuffer(), user1.publicKey.toBuffer()],
+      program.programId,
+    );
+    const [lpLockup] = await PublicKey.findProgramAddress(
+      [anchor.utils.bytes.utf8.encode("lp_lockup"), keys.capitalCall.toBuffer(), user1.publicKey.toBuffer()],
+      program.programId,
+    );
+    const [escrow] = await PublicKey.findProgramAddress(
+      [anchor.utils.bytes.utf8.encode("lp_escrow"), keys.capitalCall.toBuffer(), user1.publicKey.toBuffer()],
+      program.programId,
+    );
+    await program.methods.initLpLockup()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        lpLockup,
+        escrow,
+        lpMint: lpMint.publicKey,
+        authority: user1.publicKey,
+      }).signers([user1]).rpc();
+    const lpAta1 = await getATA(user1.publicKey, lpMint.publicKey);
+    const lpAta2 = await getATA(user2.publicKey, lpMint.publicKey);
+    const [lpLockup2] = await PublicKey.findProgramAddress(
+      [anchor.utils.bytes.utf8.encode("lp_lockup"), keys.capitalCall.toBuffer(), user2.publicKey.toBuffer()],
+      program.programId,
+    );
+    const [escrow2] = await PublicKey.findProgramAddress(
+      [anchor.utils.bytes.utf8.encode("lp_escrow"), keys.capitalCall.toBuffer(), user2.publicKey.toBuffer()],
+      program.programId,
+    );
+    const takeSnapshot = program.methods.snapshot()
+      .accounts({config: config.publicKey, capitalCall: keys.capitalCall, authority: authority.publicKey})
+      .signers([authority]);
+    const snapshotKeys = await takeSnapshot.pubkeys();
+
+    const updateVoidTreasury = (voidTreasury: PublicKey) => program.methods.updateConfig({voidTreasury})
+      .accounts({
+        config: config.publicKey,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await updateVoidTreasury(feeLpAccount);
+    const setKilled = (killed: boolean) => program.methods.setKilled(killed)
+      .accounts({
+        config: config.publicKey,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await setKilled(true);
+
+    const calls = [
+      program.methods.withdraw()
+        .accounts({
+          config: config.publicKey,
+          capitalCall: keys.capitalCall,
+          authority: user1.publicKey,
+          destination: ata1,
+        }).signers([user1]),
+      program.methods.confirmDeposit()
+        .accounts({config: config.publicKey, capitalCall: keys.capitalCall, voucher}),
+      program.methods.cancelAndRefund()
+        .accounts({
+          config: config.publicKey,
+          capitalCall: keys.capitalCall,
+          authority: authority.publicKey,
+        }).signers([authority]),
+      program.methods.finalizeSplit([10_000])
+        .accounts({
+          config: config.publicKey,
+          capitalCall: keys.capitalCall,
+          lpMint: lpMint.publicKey,
+          authority: authority.publicKey,
+        })
+        .remainingAccounts([{pubkey: liquidityPool.publicKey, isWritable: true, isSigner: false}])
+        .signers([authority]),
+      program.methods.finalizeAndDistribute(0)
+        .accounts({
+          config: config.publicKey,
+          capitalCall: keys.capitalCall,
+          liquidityPool: liquidityPool.publicKey,
+          lpMint: lpMint.publicKey,
+          feeLpAccount,
+          authority: authority.publicKey,
+          receiver: provider.wallet.publicKey,
+        }).signers([authority]),
+      program.methods.claimVested()
+        .accounts({
+          config: config.publicKey,
+          capitalCall: keys.capitalCall,
+          feeLpAccount,
+          authority: user1.publicKey,
+          destination: lpAta1,
+        }).signers([user1]),
+      program.methods.releaseLp()
+        .accounts({
+          config: config.publicKey,
+          capitalCall: keys.capitalCall,
+          lpLockup,
+          escrow,
+          authority: user1.publicKey,
+          destination: lpAta1,
+        }).signers([user1]),
+      program.methods.voidVoucher()
+        .accounts({
+          config: config.publicKey,
+          capitalCall: keys.capitalCall,
+          voucher,
+          depositor: user1.publicKey,
+          treasury: feeLpAccount,
+          authority: authority.publicKey,
+        }).signers([authority]),
+      program.methods.claimReferral()
+        .accounts({
+          config: config.publicKey,
+          capitalCall: keys.capitalCall,
+          referrer: user2.publicKey,
+          destination: lpAta2,
+        }).signers([user2]),
+      program.methods.closeCancelled()
+        .accounts({
+          config: config.publicKey,
+          capitalCall: keys.capitalCall,
+          authority: authority.publicKey,
+          receiver: authority.publicKey,
+          destination: ata1,
+        }).signers([authority]),
+      program.methods.redeem(new BN(1))
+        .accounts({
+          config: config.publicKey,
+          capitalCall: keys.capitalCall,
+          lpMint: lpMint.publicKey,
+          authority: user1.publicKey,
+          source: lpAta1,
+          destination: ata1,
+        }).signers([user1]),
+      program.methods.unwind()
+        .accounts({
+          config: config.publicKey,
+          capitalCall: keys.capitalCall,
+          authority: authority.publicKey,
+          liquidityPool: liquidityPool.publicKey,
+          poolAuthority: provider.wallet.publicKey,
+          lpMint: lpMint.publicKey,
+        }).signers([authority]),
+      program.methods.recoverStrandedLp()
+        .accounts({
+          config: config.publicKey,
+          capitalCall: keys.capitalCall,
+          authority: authority.publicKey,
+          destination: lpAta1,
+        }).signers([authority]),
+      program.methods.tryAutoExtend()
+        .accounts({config: config.publicKey, capitalCall: keys.capitalCall}),
+      program.methods.cancel()
+        .accounts({
+          config: config.publicKey,
+          capitalCall: keys.capitalCall,
+          authority: authority.publicKey,
+        }).signers([authority]),
+      program.methods.updateCapitalCall({lpLockup: new BN(5)})
+        .accounts({
+          config: config.publicKey,
+          capitalCall: keys.capitalCall,
+          authority: authority.publicKey,
+        }).signers([authority]),
+      program.methods.setRefundHaircut(0)
+        .accounts({
+          config: config.publicKey,
+          capitalCall: keys.capitalCall,
+          authority: authority.publicKey,
+        }).signers([authority]),
+      program.methods.registerReferral()
+        .accounts({config: config.publicKey, capitalCall: keys.capitalCall, referrer: user1.publicKey})
+        .signers([user1]),
+      program.methods.initLpLockup()
+        .accounts({
+          config: config.publicKey,
+          capitalCall: keys.capitalCall,
+          lpLockup: lpLockup2,
+          escrow: escrow2,
+          lpMint: lpMint.publicKey,
+          authority: user2.publicKey,
+        }).signers([user2]),
+      takeSnapshot,
+      program.methods.sweepRetained()
+        .accounts({
+          config: config.publicKey,
+          capitalCall: keys.capitalCall,
+          authority: authority.publicKey,
+          receiver: provider.wallet.publicKey,
+          destination: ata1,
+        }).signers([authority]),
+    ];
+    for (const call of calls) {
+      await expect(call.rpc()).to.be.rejectedWith(/ProgramKilled/);
+    }
+
+    // snapshot_vouchers needs a snapshot taken before kill
+    await setKilled(false);
+    await takeSnapshot.rpc();
+    await setKilled(true);
+    await expect(program.methods.snapshotVouchers()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        snapshot: snapshotKeys.snapshot,
+        authority: authority.publicKey,
+      })
+      .remainingAccounts([{pubkey: voucher, isWritable: true, isSigner: false}])
+      .signers([authority]).rpc()).to.be.rejectedWith(/ProgramKilled/);
+
+    await setKilled(false);
+    await updateVoidTreasury(PublicKey.default);
+  });
+
+  it("Should finalize chunked capital call again after unwind", async () => {
+    const keys = await createCapitalCall(1, 100, 1_000_106);
+    await program.methods.updateCapitalCall({finalizeChunk: new BN(600_000)})
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+      }).signers([authority]).rpc();
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 1_000_106);
+    await program.methods.deposit(new BN(1_000_106), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+
+    const finalizeChunked = () => program.methods.finalizeChunked()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        liquidityPool: liquidityPool.publicKey,
+        lpMint: lpMint.publicKey,
+      }).rpc();
+    const poolBefore = await tokenBalance(liquidityPool.publicKey);
+    await finalizeChunked();
+    await finalizeChunked();
+
+    await program.methods.unwind()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: authority.publicKey,
+        liquidityPool: liquidityPool.publicKey,
+        poolAuthority: provider.wallet.publicKey,
+        lpMint: lpMint.publicKey,
+      }).signers([authority]).rpc();
+    let ccAcc = await program.account.capitalCall.fetch(keys.capitalCall);
+    expect(ccAcc.isLpMinted).to.be.false;
+    expect(ccAcc.finalizeCursor.toNumber()).to.be.equal(0);
+    expect(await tokenBalance(keys.vault)).to.be.equal(1_000_106);
+    expect(await tokenBalance(liquidityPool.publicKey)).to.be.equal(poolBefore);
+
+    await finalizeChunked();
+    ccAcc = await program.account.capitalCall.fetch(keys.capitalCall);
+    expect(ccAcc.isLpMinted).to.be.true;
+    expect(ccAcc.finalizeCursor.toNumber()).to.be.equal(600_000);
+    await finalizeChunked();
+    ccAcc = await program.account.capitalCall.fetch(keys.capitalCall);
+    expect(ccAcc.finalizeCursor.toNumber()).to.be.equal(1_000_106);
+    expect(await tokenBalance(keys.vault)).to.be.equal(0);
+    expect(await tokenBalance(liquidityPool.publicKey)).to.be.equal(poolBefore + 1_000_106);
+  });
+
+  it("Should value capital in liquidity pool units by oracle price", async () => {
+    const keys = await createCapitalCall(1, 100, 1_000_108);
+    const feed = Keypair.generate().publicKey;
+    const [oracle] = await PublicKey.findProgramAddress(
+      [anchor.utils.bytes.utf8.encode("price"), feed.toBuffer()],
+      callbackMock.programId,
+    );
+    // 2.5 pool base units per raised token
+    await callbackMock.methods.setPrice(feed, new BN(2_500_000), new BN(2_500), -6, new BN(await currentBlockTime()))
+      .accounts({
+        priceRecord: oracle,
+        payer: provider.wallet.publicKey,
+      }).rpc();
+    await program.methods.updateCapitalCall({
+      useOracleConversion: true,
+      oracle,
+      oracleMaxAge: new BN(600),
+      oracleMaxConfBps: 100,
+    }).accounts({
+      config: config.publicKey,
+      capitalCall: keys.capitalCall,
+      authority: authority.publicKey,
+    }).signers([authority]).rpc();
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const ata1 = await getATA(user1.publicKey, mint.publicKey);
+    await mintTokens(ata1, 1_000_108);
+    await program.methods.deposit(new BN(1_000_108), null, NO_MEMO)
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        authority: user1.publicKey,
+        source: ata1,
+      }).signers([user1]).rpc();
+
+    const mintLpTokens = (oracleAccounts: PublicKey[]) => program.methods.mintLpTokens()
+      .accounts({
+        config: config.publicKey,
+        capitalCall: keys.capitalCall,
+        liquidityPool: liquidityPool.publicKey,
+        lpMint: lpMint.publicKey,
+      })
+      .remainingAccounts(oracleAccounts.map(pubkey => ({pubkey, isWritable: false, isSigner: false})))
+      .rpc();
+    await expect(mintLpTokens([])).to.be.rejectedWith(/InvalidOracle/);
+    await expect(mintLpTokens([feed])).to.be.rejectedWith(/InvalidOracle/);
+    await mintLpTokens([oracle]);
+
+    const ccAcc = await program.account.capitalCall.fetch(keys.capitalCall);
+    expect(ccAcc.oracleRate.toNumber()).to.be.equal(2_500_000_000);
+    const lpAmount = new BN(2_500_270).mul(ccAcc.tokenLiquidity.add(ccAcc.creditOutstanding)).div(ccAcc.lpSupply).toNumber();
+    expect(await tokenBalance(keys.lpTokenPool)).to.be.equal(lpAmount);
+  });
+
   // TODO for tests (negative):
   // Should NOT init with invalid lp mint authority
   // Should NOT init with invalid liquidity_pool mint